@@ -0,0 +1,177 @@
+//! Attribute combinations the derives reject, checked as `compile_fail` doctests. Each snippet
+//! compiles once the offending attribute is removed.
+
+/// `with` already sets the serialization function, so `serialize_with` conflicts with it.
+///
+/// ```compile_fail
+/// use serde_state::SerializeState;
+///
+/// mod plain {
+///     pub fn serialize_state<S: serde::Serializer, State: ?Sized>(
+///         value: &u32,
+///         _state: &State,
+///         serializer: S,
+///     ) -> Result<S::Ok, S::Error> {
+///         serializer.serialize_u32(*value)
+///     }
+/// }
+///
+/// fn write<S: serde::Serializer>(value: &u32, serializer: S) -> Result<S::Ok, S::Error> {
+///     serializer.serialize_u32(*value)
+/// }
+///
+/// #[derive(SerializeState)]
+/// struct Conflict {
+///     #[serde(with = "plain", serialize_with = "write")]
+///     value: u32,
+/// }
+/// ```
+pub struct FieldWithConflict;
+
+/// Variants take the same functions as fields, and the same conflicts.
+///
+/// ```compile_fail
+/// use serde_state::DeserializeState;
+///
+/// fn read<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+///     serde::Deserialize::deserialize(deserializer)
+/// }
+///
+/// #[derive(DeserializeState)]
+/// enum Conflict {
+///     #[serde(deserialize_with = "read", deserialize_with = "read")]
+///     Value(u32),
+/// }
+/// ```
+pub struct VariantWithConflict;
+
+/// With a generic state, `from_state` has to name its proxy type:
+/// `from_state(type = "usize", fn = "lookup")`.
+///
+/// ```compile_fail
+/// use serde_state::DeserializeState;
+///
+/// fn lookup<State: ?Sized>(index: usize, _state: &State) -> Symbol {
+///     Symbol(index.to_string())
+/// }
+///
+/// #[derive(DeserializeState)]
+/// #[serde_state(from_state = "lookup")]
+/// struct Symbol(String);
+/// ```
+pub struct UnnamedStateProxy;
+
+/// A type is built from at most one proxy.
+///
+/// ```compile_fail
+/// use serde_state::DeserializeState;
+///
+/// #[derive(DeserializeState)]
+/// #[serde(from = "u32", try_from = "u32")]
+/// struct Number(u32);
+///
+/// impl From<u32> for Number {
+///     fn from(n: u32) -> Self {
+///         Number(n)
+///     }
+/// }
+/// ```
+pub struct ProxyConflict;
+
+/// A remote definition with getters is converted back through `From<Def>`,
+/// which the remote type must provide.
+///
+/// ```compile_fail
+/// use serde_state::DeserializeState;
+///
+/// mod foreign {
+///     pub struct Id(u32);
+///
+///     impl Id {
+///         pub fn get(&self) -> u32 {
+///             self.0
+///         }
+///     }
+/// }
+///
+/// #[derive(DeserializeState)]
+/// #[serde(remote = "foreign::Id")]
+/// struct IdDef(#[serde(getter = "foreign::Id::get")] u32);
+/// ```
+pub struct RemoteGetterWithoutFrom;
+
+/// Getters only make sense on remote structs.
+///
+/// ```compile_fail
+/// use serde_state::SerializeState;
+///
+/// #[derive(SerializeState)]
+/// struct Local(#[serde(getter = "Clone::clone")] u32);
+/// ```
+///
+/// ```compile_fail
+/// use serde_state::SerializeState;
+///
+/// #[derive(SerializeState)]
+/// #[serde(remote = "Option<u32>")]
+/// enum OptionDef {
+///     None,
+///     Some(#[serde(getter = "Clone::clone")] u32),
+/// }
+/// ```
+pub struct GetterOutsideRemoteStruct;
+
+/// Skipped tuple fields leave out an element, but a newtype has nothing else to write.
+///
+/// ```compile_fail
+/// use serde_state::SerializeState;
+///
+/// #[derive(SerializeState)]
+/// struct Wrapper(#[serde(skip)] u32);
+/// ```
+///
+/// ```compile_fail
+/// use serde_state::DeserializeState;
+///
+/// #[derive(DeserializeState)]
+/// enum Shape {
+///     Point(#[serde(skip_deserializing)] u32),
+/// }
+/// ```
+pub struct SkippedNewtypeField;
+
+/// Variant attributes that would change what gets written or accepted are rejected rather
+/// than ignored.
+///
+/// ```compile_fail
+/// use serde_state::SerializeState;
+///
+/// #[derive(SerializeState)]
+/// enum Legacy {
+///     #[serde(skip_serializing)]
+///     Old(u32),
+///     New(u32),
+/// }
+/// ```
+///
+/// ```compile_fail
+/// use serde_state::DeserializeState;
+///
+/// #[derive(DeserializeState)]
+/// enum Legacy {
+///     New(u32),
+///     #[serde(other)]
+///     Unknown,
+/// }
+/// ```
+///
+/// ```compile_fail
+/// use serde_state::DeserializeState;
+///
+/// #[derive(DeserializeState)]
+/// enum Legacy {
+///     #[serde(bound = "")]
+///     New(u32),
+/// }
+/// ```
+pub struct UnsupportedVariantAttribute;
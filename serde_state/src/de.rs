@@ -0,0 +1,183 @@
+//! Helpers for calling [`DeserializeState`] from plain serde code.
+
+use core::cell::{Cell, RefCell};
+use core::marker::PhantomData;
+use serde::de::{DeserializeSeed, EnumAccess, MapAccess, SeqAccess, VariantAccess};
+use serde::Deserializer;
+
+use crate::DeserializeState;
+
+/// A `DeserializeSeed` that produces a `T` by calling its `DeserializeState` impl with the
+/// captured state. Pass it to `SeqAccess::next_element_seed`, `MapAccess::next_value_seed` and
+/// similar methods to thread state through hand-written visitors.
+pub struct StateSeed<'s, T, State: ?Sized> {
+    state: &'s State,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<'s, T, State: ?Sized> StateSeed<'s, T, State> {
+    /// Creates a seed that deserializes a `T` with `state`.
+    pub fn new(state: &'s State) -> Self {
+        Self {
+            state,
+            marker: PhantomData,
+        }
+    }
+
+    /// The state passed to `T::deserialize_state`.
+    pub fn state(&self) -> &'s State {
+        self.state
+    }
+}
+
+impl<T, State: ?Sized> Copy for StateSeed<'_, T, State> {}
+impl<T, State: ?Sized> Clone for StateSeed<'_, T, State> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'de, T, State> DeserializeSeed<'de> for StateSeed<'_, T, State>
+where
+    T: DeserializeState<'de, State>,
+    State: ?Sized,
+{
+    type Value = T;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        T::deserialize_state(self.state, deserializer)
+    }
+}
+
+/// Stateful counterparts of the `SeqAccess` methods.
+pub trait SeqAccessExt<'de>: SeqAccess<'de> {
+    /// Deserializes the next element through its `DeserializeState` impl.
+    fn next_element_state<T, State>(&mut self, state: &State) -> Result<Option<T>, Self::Error>
+    where
+        T: DeserializeState<'de, State>,
+        State: ?Sized,
+    {
+        self.next_element_seed(StateSeed::new(state))
+    }
+}
+
+impl<'de, A: SeqAccess<'de> + ?Sized> SeqAccessExt<'de> for A {}
+
+/// Stateful counterparts of the `MapAccess` methods.
+pub trait MapAccessExt<'de>: MapAccess<'de> {
+    /// Deserializes the next key through its `DeserializeState` impl.
+    fn next_key_state<K, State>(&mut self, state: &State) -> Result<Option<K>, Self::Error>
+    where
+        K: DeserializeState<'de, State>,
+        State: ?Sized,
+    {
+        self.next_key_seed(StateSeed::new(state))
+    }
+
+    /// Deserializes the next value through its `DeserializeState` impl.
+    fn next_value_state<V, State>(&mut self, state: &State) -> Result<V, Self::Error>
+    where
+        V: DeserializeState<'de, State>,
+        State: ?Sized,
+    {
+        self.next_value_seed(StateSeed::new(state))
+    }
+
+    /// Deserializes the next entry, passing `state` to both the key and the value.
+    fn next_entry_state<K, V, State>(
+        &mut self,
+        state: &State,
+    ) -> Result<Option<(K, V)>, Self::Error>
+    where
+        K: DeserializeState<'de, State>,
+        V: DeserializeState<'de, State>,
+        State: ?Sized,
+    {
+        self.next_entry_seed(StateSeed::new(state), StateSeed::new(state))
+    }
+}
+
+impl<'de, A: MapAccess<'de> + ?Sized> MapAccessExt<'de> for A {}
+
+/// Stateful counterparts of the `EnumAccess` methods.
+pub trait EnumAccessExt<'de>: EnumAccess<'de> {
+    /// Identifies the variant through its `DeserializeState` impl.
+    fn variant_state<V, State>(self, state: &State) -> Result<(V, Self::Variant), Self::Error>
+    where
+        V: DeserializeState<'de, State>,
+        State: ?Sized,
+    {
+        self.variant_seed(StateSeed::new(state))
+    }
+}
+
+impl<'de, A: EnumAccess<'de>> EnumAccessExt<'de> for A {}
+
+/// Stateful counterparts of the `VariantAccess` methods.
+pub trait VariantAccessExt<'de>: VariantAccess<'de> {
+    /// Deserializes the content of a newtype variant through its `DeserializeState` impl.
+    fn newtype_variant_state<T, State>(self, state: &State) -> Result<T, Self::Error>
+    where
+        T: DeserializeState<'de, State>,
+        State: ?Sized,
+    {
+        self.newtype_variant_seed(StateSeed::new(state))
+    }
+}
+
+impl<'de, A: VariantAccess<'de>> VariantAccessExt<'de> for A {}
+
+/// States that can undo their own changes.
+///
+/// Untagged enums try each variant in turn against the same input, so a variant that fails
+/// halfway may already have updated the state. The derived impls take a snapshot before each
+/// attempt and restore it when the attempt fails. States with no interior mutability can use an
+/// empty snapshot, or the enum can opt out with `#[serde_state(no_rollback)]`.
+pub trait Rollback {
+    /// Whatever is needed to bring the state back to where it is now.
+    type Snapshot;
+
+    /// Captures the current state.
+    fn snapshot(&self) -> Self::Snapshot;
+
+    /// Undoes every change made since `snapshot` was taken.
+    fn restore(&self, snapshot: Self::Snapshot);
+}
+
+impl Rollback for () {
+    type Snapshot = ();
+
+    fn snapshot(&self) {}
+
+    fn restore(&self, _snapshot: ()) {}
+}
+
+impl<T: Copy> Rollback for Cell<T> {
+    type Snapshot = T;
+
+    fn snapshot(&self) -> T {
+        self.get()
+    }
+
+    fn restore(&self, snapshot: T) {
+        self.set(snapshot)
+    }
+}
+
+/// Snapshots clone the whole value, once per untagged variant tried for every value read. For
+/// a large state such as an interner, implement `Rollback` on the state itself with a cheap
+/// checkpoint instead, e.g. the number of entries to truncate back to.
+impl<T: Clone> Rollback for RefCell<T> {
+    type Snapshot = T;
+
+    fn snapshot(&self) -> T {
+        self.borrow().clone()
+    }
+
+    fn restore(&self, snapshot: T) {
+        *self.borrow_mut() = snapshot;
+    }
+}
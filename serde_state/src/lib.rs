@@ -1,8 +1,42 @@
-use serde::ser::{SerializeSeq, SerializeTuple};
+use serde::ser::{SerializeMap, SerializeSeq, SerializeStruct, SerializeTuple};
 use serde::Serialize;
-pub use serde_state_derive::{DeserializeState, SerializeState};
+pub use serde_state_derive::{DeserializeState, SerializeState, StatelessState};
+
+pub mod de;
+pub mod ser;
+
+#[doc(hidden)]
+#[path = "private/mod.rs"]
+pub mod __private;
+
+#[cfg(doctest)]
+mod compile_fail;
+
+use de::{MapAccessExt, SeqAccessExt, StateSeed, VariantAccessExt};
+use ser::{SerializeMapExt, SerializeSeqExt, SerializeStructExt, SerializeTupleExt, StateRef};
+use std::borrow::Cow;
 use std::boxed::Box;
+use std::cell::{Cell, RefCell};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet, LinkedList, VecDeque};
+use std::ffi::{CStr, CString};
+#[cfg(any(unix, windows))]
+use std::ffi::{OsStr, OsString};
+use std::hash::{BuildHasher, Hash};
 use std::marker::PhantomData;
+use std::mem::{ManuallyDrop, MaybeUninit};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::num::{
+    NonZeroI128, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroIsize, NonZeroU128,
+    NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU8, NonZeroUsize, Saturating, Wrapping,
+};
+use std::ops::{Bound, Range, RangeFrom, RangeInclusive, RangeTo};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::ptr;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
 
 pub trait SerializeState<State: ?Sized> {
     fn serialize_state<S>(&self, state: &State, serializer: S) -> Result<S::Ok, S::Error>
@@ -45,6 +79,150 @@ where
     }
 }
 
+macro_rules! impl_serialize_state_deref {
+    ($($ty:ident),* $(,)?) => {
+        $(
+            impl<State: ?Sized, T: SerializeState<State> + ?Sized> SerializeState<State> for $ty<T> {
+                fn serialize_state<S>(&self, state: &State, serializer: S) -> Result<S::Ok, S::Error>
+                where
+                    S: serde::Serializer,
+                {
+                    T::serialize_state(&**self, state, serializer)
+                }
+            }
+        )*
+    };
+}
+
+/// Deserializes the inner value and wraps it with the container's `new` constructor.
+macro_rules! impl_deserialize_state_new {
+    ($($ty:ident),* $(,)?) => {
+        $(
+            impl<'de, State: ?Sized, T> DeserializeState<'de, State> for $ty<T>
+            where
+                T: DeserializeState<'de, State>,
+            {
+                fn deserialize_state<D>(state: &State, deserializer: D) -> Result<Self, D::Error>
+                where
+                    D: serde::Deserializer<'de>,
+                {
+                    T::deserialize_state(state, deserializer).map($ty::new)
+                }
+            }
+        )*
+    };
+}
+
+impl_serialize_state_deref!(Rc, Arc);
+impl_deserialize_state_new!(Rc, Arc, RefCell, Mutex, RwLock);
+
+impl<State: ?Sized, T: SerializeState<State> + ?Sized> SerializeState<State> for Pin<Box<T>> {
+    fn serialize_state<S>(&self, state: &State, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        T::serialize_state(&**self, state, serializer)
+    }
+}
+impl<'de, State: ?Sized, T> DeserializeState<'de, State> for Pin<Box<T>>
+where
+    T: DeserializeState<'de, State>,
+{
+    fn deserialize_state<D>(state: &State, deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        T::deserialize_state(state, deserializer).map(Box::pin)
+    }
+}
+
+impl<State: ?Sized, T> SerializeState<State> for Cow<'_, T>
+where
+    T: SerializeState<State> + ToOwned + ?Sized,
+{
+    fn serialize_state<S>(&self, state: &State, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        T::serialize_state(&**self, state, serializer)
+    }
+}
+impl<'de, State: ?Sized, T> DeserializeState<'de, State> for Cow<'_, T>
+where
+    T: ToOwned + ?Sized,
+    T::Owned: DeserializeState<'de, State>,
+{
+    fn deserialize_state<D>(state: &State, deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        T::Owned::deserialize_state(state, deserializer).map(Cow::Owned)
+    }
+}
+
+impl<State: ?Sized, T> SerializeState<State> for Cell<T>
+where
+    T: SerializeState<State> + Copy,
+{
+    fn serialize_state<S>(&self, state: &State, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.get().serialize_state(state, serializer)
+    }
+}
+impl<'de, State: ?Sized, T> DeserializeState<'de, State> for Cell<T>
+where
+    T: DeserializeState<'de, State> + Copy,
+{
+    fn deserialize_state<D>(state: &State, deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        T::deserialize_state(state, deserializer).map(Cell::new)
+    }
+}
+
+impl<State: ?Sized, T: SerializeState<State> + ?Sized> SerializeState<State> for RefCell<T> {
+    fn serialize_state<S>(&self, state: &State, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self.try_borrow() {
+            Ok(value) => value.serialize_state(state, serializer),
+            Err(_) => Err(serde::ser::Error::custom("already mutably borrowed")),
+        }
+    }
+}
+
+impl<State: ?Sized, T: SerializeState<State> + ?Sized> SerializeState<State> for Mutex<T> {
+    fn serialize_state<S>(&self, state: &State, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self.lock() {
+            Ok(locked) => locked.serialize_state(state, serializer),
+            Err(_) => Err(serde::ser::Error::custom(
+                "lock poison error while serializing",
+            )),
+        }
+    }
+}
+
+impl<State: ?Sized, T: SerializeState<State> + ?Sized> SerializeState<State> for RwLock<T> {
+    fn serialize_state<S>(&self, state: &State, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self.read() {
+            Ok(locked) => locked.serialize_state(state, serializer),
+            Err(_) => Err(serde::ser::Error::custom(
+                "lock poison error while serializing",
+            )),
+        }
+    }
+}
+
 impl<State: ?Sized, T> SerializeState<State> for PhantomData<T> {
     fn serialize_state<S>(&self, _state: &State, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -64,9 +242,10 @@ impl<'de, State: ?Sized, T> DeserializeState<'de, State> for PhantomData<T> {
     }
 }
 
-macro_rules! impl_state_passthrough {
-    ($($ty:ty),* $(,)?) => {
+macro_rules! impl_serialize_state_passthrough {
+    ($($(#[$attr:meta])* $ty:ty),* $(,)?) => {
         $(
+            $(#[$attr])*
             impl<State: ?Sized> SerializeState<State> for $ty {
                 fn serialize_state<S>(
                     &self,
@@ -79,7 +258,14 @@ macro_rules! impl_state_passthrough {
                     serde::Serialize::serialize(self, serializer)
                 }
             }
+        )*
+    };
+}
 
+macro_rules! impl_deserialize_state_passthrough {
+    ($($(#[$attr:meta])* $ty:ty),* $(,)?) => {
+        $(
+            $(#[$attr])*
             impl<'de, State: ?Sized> DeserializeState<'de, State> for $ty {
                 fn deserialize_state<D>(
                     _state: &State,
@@ -95,11 +281,190 @@ macro_rules! impl_state_passthrough {
     };
 }
 
+macro_rules! impl_state_passthrough {
+    ($($(#[$attr:meta])* $ty:ty),* $(,)?) => {
+        $(
+            impl_serialize_state_passthrough!($(#[$attr])* $ty);
+            impl_deserialize_state_passthrough!($(#[$attr])* $ty);
+        )*
+    };
+}
+
+/// Implements `SerializeState` and `DeserializeState` by delegating to the type's plain serde
+/// impls, as `#[derive(StatelessState)]` does, for types that can't use the derive.
+///
+/// `impl_stateless_state!(Type, ...)` covers every state. The orphan rules only allow that in
+/// the crate defining `Type`, so for a type from another crate, such as `uuid::Uuid`, name the
+/// state instead: `impl_stateless_state!(MyState => uuid::Uuid, ...)`.
+#[macro_export]
+macro_rules! impl_stateless_state {
+    ($state:ty => $($ty:ty),+ $(,)?) => {
+        $(
+            impl $crate::SerializeState<$state> for $ty {
+                fn serialize_state<__S>(
+                    &self,
+                    _state: &$state,
+                    serializer: __S,
+                ) -> ::core::result::Result<__S::Ok, __S::Error>
+                where
+                    __S: $crate::__private::serde::Serializer,
+                {
+                    $crate::__private::serde::Serialize::serialize(self, serializer)
+                }
+            }
+
+            impl<'de> $crate::DeserializeState<'de, $state> for $ty {
+                fn deserialize_state<__D>(
+                    _state: &$state,
+                    deserializer: __D,
+                ) -> ::core::result::Result<Self, __D::Error>
+                where
+                    __D: $crate::__private::serde::Deserializer<'de>,
+                {
+                    $crate::__private::serde::Deserialize::deserialize(deserializer)
+                }
+            }
+        )+
+    };
+    ($($ty:ty),+ $(,)?) => {
+        $(
+            impl<__State: ?Sized> $crate::SerializeState<__State> for $ty {
+                fn serialize_state<__S>(
+                    &self,
+                    _state: &__State,
+                    serializer: __S,
+                ) -> ::core::result::Result<__S::Ok, __S::Error>
+                where
+                    __S: $crate::__private::serde::Serializer,
+                {
+                    $crate::__private::serde::Serialize::serialize(self, serializer)
+                }
+            }
+
+            impl<'de, __State: ?Sized> $crate::DeserializeState<'de, __State> for $ty {
+                fn deserialize_state<__D>(
+                    _state: &__State,
+                    deserializer: __D,
+                ) -> ::core::result::Result<Self, __D::Error>
+                where
+                    __D: $crate::__private::serde::Deserializer<'de>,
+                {
+                    $crate::__private::serde::Deserialize::deserialize(deserializer)
+                }
+            }
+        )+
+    };
+}
+
+impl_state_passthrough!(());
 impl_state_passthrough!(
     bool, char, String, u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize,
 );
+impl_state_passthrough!(f32, f64);
+impl_state_passthrough!(
+    NonZeroU8,
+    NonZeroU16,
+    NonZeroU32,
+    NonZeroU64,
+    NonZeroU128,
+    NonZeroUsize,
+    NonZeroI8,
+    NonZeroI16,
+    NonZeroI32,
+    NonZeroI64,
+    NonZeroI128,
+    NonZeroIsize,
+);
+impl_state_passthrough!(
+    Saturating<u8>,
+    Saturating<u16>,
+    Saturating<u32>,
+    Saturating<u64>,
+    Saturating<u128>,
+    Saturating<usize>,
+    Saturating<i8>,
+    Saturating<i16>,
+    Saturating<i32>,
+    Saturating<i64>,
+    Saturating<i128>,
+    Saturating<isize>,
+);
+impl_state_passthrough!(Duration, SystemTime);
+impl_state_passthrough!(
+    PathBuf,
+    CString,
+    #[cfg(any(unix, windows))]
+    OsString,
+);
+impl_state_passthrough!(
+    IpAddr,
+    Ipv4Addr,
+    Ipv6Addr,
+    SocketAddr,
+    SocketAddrV4,
+    SocketAddrV6,
+);
+impl_serialize_state_passthrough!(
+    str,
+    Path,
+    CStr,
+    #[cfg(any(unix, windows))]
+    OsStr,
+);
+// These serialize through the `Box<T>` impl above.
+impl_deserialize_state_passthrough!(
+    Box<Path>,
+    Box<CStr>,
+    #[cfg(any(unix, windows))]
+    Box<OsStr>,
+);
+impl_state_passthrough!(
+    #[cfg(target_has_atomic = "8")]
+    std::sync::atomic::AtomicBool,
+    #[cfg(target_has_atomic = "8")]
+    std::sync::atomic::AtomicI8,
+    #[cfg(target_has_atomic = "16")]
+    std::sync::atomic::AtomicI16,
+    #[cfg(target_has_atomic = "32")]
+    std::sync::atomic::AtomicI32,
+    #[cfg(target_has_atomic = "64")]
+    std::sync::atomic::AtomicI64,
+    #[cfg(target_has_atomic = "ptr")]
+    std::sync::atomic::AtomicIsize,
+    #[cfg(target_has_atomic = "8")]
+    std::sync::atomic::AtomicU8,
+    #[cfg(target_has_atomic = "16")]
+    std::sync::atomic::AtomicU16,
+    #[cfg(target_has_atomic = "32")]
+    std::sync::atomic::AtomicU32,
+    #[cfg(target_has_atomic = "64")]
+    std::sync::atomic::AtomicU64,
+    #[cfg(target_has_atomic = "ptr")]
+    std::sync::atomic::AtomicUsize,
+);
+
+macro_rules! impl_deserialize_state_borrowed {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl<'de: 'a, 'a, State: ?Sized> DeserializeState<'de, State> for &'a $ty {
+                fn deserialize_state<D>(_state: &State, deserializer: D) -> Result<Self, D::Error>
+                where
+                    D: serde::Deserializer<'de>,
+                {
+                    serde::Deserialize::deserialize(deserializer)
+                }
+            }
+        )*
+    };
+}
+
+// Borrowed from the input, so like serde's impls these fail when the format can't lend data.
+impl_deserialize_state_borrowed!(str, [u8]);
 
 /// A value with attached state. Its Serialize impl calls `T`'s SerializeState impl.
+///
+/// When `T` is `PhantomData<U>`, it is instead a `DeserializeSeed` that produces a `U` through
+/// its `DeserializeState` impl, mirroring serde's `DeserializeSeed` impl for `PhantomData`.
 pub struct WithState<'state, T, State: ?Sized> {
     value: T,
     state: &'state State,
@@ -112,6 +477,26 @@ where
     pub fn new(value: T, state: &'state State) -> Self {
         Self { value, state }
     }
+
+    /// Returns the wrapped value.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+
+    /// The attached state.
+    pub fn state(&self) -> &'state State {
+        self.state
+    }
+}
+
+impl<'state, T, State> WithState<'state, PhantomData<T>, State>
+where
+    State: ?Sized,
+{
+    /// Creates a seed that deserializes a `T` with `state`.
+    pub fn seed(state: &'state State) -> Self {
+        Self::new(PhantomData, state)
+    }
 }
 
 impl<T, State: ?Sized> Serialize for WithState<'_, T, State>
@@ -126,96 +511,20 @@ where
     }
 }
 
-pub mod __private {
-    use serde::de::DeserializeSeed;
-    use serde::{Deserializer, Serialize, Serializer};
-
-    use crate::{DeserializeState, SerializeState};
-
-    pub struct SerializeRef<'state, T: ?Sized, State: ?Sized> {
-        value: &'state T,
-        state: &'state State,
-    }
-
-    impl<'state, T, State> SerializeRef<'state, T, State>
-    where
-        T: ?Sized,
-        State: ?Sized,
-    {
-        pub fn new(value: &'state T, state: &'state State) -> Self {
-            Self { value, state }
-        }
-    }
-
-    impl<'state, T, State> Serialize for SerializeRef<'state, T, State>
-    where
-        T: SerializeState<State> + ?Sized,
-        State: ?Sized,
-    {
-        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-        where
-            S: Serializer,
-        {
-            self.value.serialize_state(self.state, serializer)
-        }
-    }
-
-    pub fn wrap_serialize<'state, T, State>(
-        value: &'state T,
-        state: &'state State,
-    ) -> SerializeRef<'state, T, State>
-    where
-        T: SerializeState<State> + ?Sized,
-        State: ?Sized,
-    {
-        SerializeRef::new(value, state)
-    }
-
-    pub struct DeserializeStateSeed<'state, T, State: ?Sized> {
-        state: &'state State,
-        _marker: core::marker::PhantomData<T>,
-    }
-
-    impl<T, State: ?Sized> Copy for DeserializeStateSeed<'_, T, State> {}
-    impl<T, State: ?Sized> Clone for DeserializeStateSeed<'_, T, State> {
-        fn clone(&self) -> Self {
-            Self {
-                state: self.state,
-                _marker: self._marker,
-            }
-        }
-    }
-
-    impl<'state, T, State: ?Sized> DeserializeStateSeed<'state, T, State> {
-        pub fn new(state: &'state State) -> Self {
-            Self {
-                state,
-                _marker: core::marker::PhantomData,
-            }
-        }
-    }
+impl<'de, T, State: ?Sized> serde::de::DeserializeSeed<'de> for WithState<'_, PhantomData<T>, State>
+where
+    T: DeserializeState<'de, State>,
+{
+    type Value = T;
 
-    impl<'de, 'state, T, State> DeserializeSeed<'de> for DeserializeStateSeed<'state, T, State>
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
     where
-        T: DeserializeState<'de, State>,
-        State: ?Sized,
+        D: serde::Deserializer<'de>,
     {
-        type Value = T;
-
-        fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
-        where
-            D: Deserializer<'de>,
-        {
-            T::deserialize_state(self.state, deserializer)
-        }
-    }
-
-    pub fn wrap_deserialize_seed<'state, T, State: ?Sized>(
-        state: &'state State,
-    ) -> DeserializeStateSeed<'state, T, State> {
-        DeserializeStateSeed::new(state)
+        T::deserialize_state(self.state, deserializer)
     }
 }
+
 impl<State: ?Sized, T> SerializeState<State> for Vec<T>
 where
     T: SerializeState<State>,
@@ -226,7 +535,7 @@ where
     {
         let mut seq = serializer.serialize_seq(Some(self.len()))?;
         for value in self {
-            seq.serialize_element(&crate::__private::wrap_serialize(value, state))?;
+            seq.serialize_element_state(value, state)?;
         }
         seq.end()
     }
@@ -260,9 +569,7 @@ where
                 A: serde::de::SeqAccess<'de>,
             {
                 let mut values = Vec::new();
-                while let Some(value) = seq.next_element_seed(
-                    crate::__private::wrap_deserialize_seed::<T, State>(self.state),
-                )? {
+                while let Some(value) = seq.next_element_state::<T, State>(self.state)? {
                     values.push(value);
                 }
                 Ok(values)
@@ -276,6 +583,48 @@ where
     }
 }
 
+impl<State: ?Sized, T> SerializeState<State> for [T]
+where
+    T: SerializeState<State>,
+{
+    fn serialize_state<S>(&self, state: &State, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serialize_seq_state(self, self.len(), state, serializer)
+    }
+}
+
+/// Deserializes unsized pointers by going through their owned counterpart.
+macro_rules! impl_deserialize_state_unsized {
+    ($($ty:ident),* $(,)?) => {
+        $(
+            impl<'de, State: ?Sized, T> DeserializeState<'de, State> for $ty<[T]>
+            where
+                T: DeserializeState<'de, State>,
+            {
+                fn deserialize_state<D>(state: &State, deserializer: D) -> Result<Self, D::Error>
+                where
+                    D: serde::Deserializer<'de>,
+                {
+                    Vec::<T>::deserialize_state(state, deserializer).map(Into::into)
+                }
+            }
+
+            impl<'de, State: ?Sized> DeserializeState<'de, State> for $ty<str> {
+                fn deserialize_state<D>(state: &State, deserializer: D) -> Result<Self, D::Error>
+                where
+                    D: serde::Deserializer<'de>,
+                {
+                    String::deserialize_state(state, deserializer).map(Into::into)
+                }
+            }
+        )*
+    };
+}
+
+impl_deserialize_state_unsized!(Box, Rc, Arc);
+
 impl<State: ?Sized, T> SerializeState<State> for Option<T>
 where
     T: SerializeState<State>,
@@ -285,9 +634,7 @@ where
         S: serde::Serializer,
     {
         match self {
-            Some(value) => {
-                serializer.serialize_some(&crate::__private::wrap_serialize(value, state))
-            }
+            Some(value) => serializer.serialize_some(&StateRef::new(value, state)),
             None => serializer.serialize_none(),
         }
     }
@@ -320,7 +667,7 @@ where
             where
                 D: serde::Deserializer<'de>,
             {
-                let seed = crate::__private::wrap_deserialize_seed::<T, State>(self.state);
+                let seed = StateSeed::<T, State>::new(self.state);
                 let value = serde::de::DeserializeSeed::deserialize(seed, deserializer)?;
                 Ok(Some(value))
             }
@@ -346,72 +693,974 @@ where
         })
     }
 }
-impl<State: ?Sized, A, B> SerializeState<State> for (A, B)
-where
-    A: SerializeState<State>,
-    B: SerializeState<State>,
-{
-    fn serialize_state<S>(&self, state: &State, serializer: S) -> Result<S::Ok, S::Error>
-    where
+macro_rules! impl_state_tuple {
+    ($($len:literal => ($($n:tt $name:ident)+))+) => {
+        $(
+            impl<State: ?Sized, $($name),+> SerializeState<State> for ($($name,)+)
+            where
+                $($name: SerializeState<State>,)+
+            {
+                fn serialize_state<S>(&self, state: &State, serializer: S) -> Result<S::Ok, S::Error>
+                where
+                    S: serde::Serializer,
+                {
+                    let mut seq = serializer.serialize_tuple($len)?;
+                    $(
+                        seq.serialize_element_state(&self.$n, state)?;
+                    )+
+                    seq.end()
+                }
+            }
+
+            impl<'de, State: ?Sized, $($name),+> DeserializeState<'de, State> for ($($name,)+)
+            where
+                $($name: DeserializeState<'de, State>,)+
+            {
+                fn deserialize_state<D>(state: &State, deserializer: D) -> Result<Self, D::Error>
+                where
+                    D: serde::Deserializer<'de>,
+                {
+                    struct TupleVisitor<'state, State: ?Sized, $($name),+> {
+                        state: &'state State,
+                        marker: PhantomData<($($name,)+)>,
+                    }
+
+                    impl<'de, 'state, State: ?Sized, $($name),+> serde::de::Visitor<'de>
+                        for TupleVisitor<'state, State, $($name),+>
+                    where
+                        $($name: DeserializeState<'de, State>,)+
+                    {
+                        type Value = ($($name,)+);
+
+                        fn expecting(
+                            &self,
+                            formatter: &mut core::fmt::Formatter<'_>,
+                        ) -> core::fmt::Result {
+                            formatter.write_str(concat!("a tuple of length ", $len))
+                        }
+
+                        #[allow(non_snake_case)]
+                        fn visit_seq<Seq>(self, mut seq: Seq) -> Result<Self::Value, Seq::Error>
+                        where
+                            Seq: serde::de::SeqAccess<'de>,
+                        {
+                            $(
+                                let $name = seq
+                                    .next_element_state::<$name, State>(self.state)?
+                                    .ok_or_else(|| serde::de::Error::invalid_length($n, &self))?;
+                            )+
+                            Ok(($($name,)+))
+                        }
+                    }
+
+                    deserializer.deserialize_tuple(
+                        $len,
+                        TupleVisitor {
+                            state,
+                            marker: PhantomData,
+                        },
+                    )
+                }
+            }
+        )+
+    };
+}
+
+impl_state_tuple! {
+    1 => (0 T0)
+    2 => (0 T0 1 T1)
+    3 => (0 T0 1 T1 2 T2)
+    4 => (0 T0 1 T1 2 T2 3 T3)
+    5 => (0 T0 1 T1 2 T2 3 T3 4 T4)
+    6 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5)
+    7 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6)
+    8 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7)
+    9 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8)
+    10 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9)
+    11 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10)
+    12 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10 11 T11)
+    13 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10 11 T11 12 T12)
+    14 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10 11 T11 12 T12 13 T13)
+    15 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10 11 T11 12 T12 13 T13 14 T14)
+    16 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10 11 T11 12 T12 13 T13 14 T14 15 T15)
+}
+
+impl<State: ?Sized, T, const N: usize> SerializeState<State> for [T; N]
+where
+    T: SerializeState<State>,
+{
+    fn serialize_state<S>(&self, state: &State, serializer: S) -> Result<S::Ok, S::Error>
+    where
         S: serde::Serializer,
     {
-        let mut seq = serializer.serialize_tuple(2)?;
-        seq.serialize_element(&crate::__private::wrap_serialize(&self.0, state))?;
-        seq.serialize_element(&crate::__private::wrap_serialize(&self.1, state))?;
+        let mut seq = serializer.serialize_tuple(N)?;
+        for value in self {
+            seq.serialize_element_state(value, state)?;
+        }
         seq.end()
     }
 }
 
-impl<'de, State: ?Sized, A, B> DeserializeState<'de, State> for (A, B)
+impl<'de, State: ?Sized, T, const N: usize> DeserializeState<'de, State> for [T; N]
 where
-    A: DeserializeState<'de, State>,
-    B: DeserializeState<'de, State>,
+    T: DeserializeState<'de, State>,
 {
     fn deserialize_state<D>(state: &State, deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        struct Tuple2Visitor<'state, State: ?Sized, A, B> {
+        /// The elements read so far, dropped if a later one fails.
+        struct PartialArray<T, const N: usize> {
+            values: [MaybeUninit<T>; N],
+            len: usize,
+        }
+
+        impl<T, const N: usize> Drop for PartialArray<T, N> {
+            fn drop(&mut self) {
+                for value in &mut self.values[..self.len] {
+                    // SAFETY: the first `len` elements are initialized.
+                    unsafe { value.assume_init_drop() };
+                }
+            }
+        }
+
+        struct ArrayVisitor<'state, State: ?Sized, T, const N: usize> {
             state: &'state State,
-            marker: PhantomData<(A, B)>,
+            marker: PhantomData<[T; N]>,
         }
 
-        impl<'de, 'state, State: ?Sized, A, B> serde::de::Visitor<'de>
-            for Tuple2Visitor<'state, State, A, B>
+        impl<'de, 'state, State: ?Sized, T, const N: usize> serde::de::Visitor<'de>
+            for ArrayVisitor<'state, State, T, N>
         where
-            A: DeserializeState<'de, State>,
-            B: DeserializeState<'de, State>,
+            T: DeserializeState<'de, State>,
         {
-            type Value = (A, B);
+            type Value = [T; N];
 
             fn expecting(&self, formatter: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-                formatter.write_str("a tuple of length 2")
+                write!(formatter, "an array of length {N}")
             }
 
-            fn visit_seq<Seq>(self, mut seq: Seq) -> Result<Self::Value, Seq::Error>
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
             where
-                Seq: serde::de::SeqAccess<'de>,
+                A: serde::de::SeqAccess<'de>,
             {
-                let first = seq
-                    .next_element_seed(crate::__private::wrap_deserialize_seed::<A, State>(
-                        self.state,
-                    ))?
-                    .ok_or_else(|| serde::de::Error::invalid_length(0, &self))?;
-                let second = seq
-                    .next_element_seed(crate::__private::wrap_deserialize_seed::<B, State>(
-                        self.state,
-                    ))?
-                    .ok_or_else(|| serde::de::Error::invalid_length(1, &self))?;
-                Ok((first, second))
+                let mut array = PartialArray::<T, N> {
+                    values: [const { MaybeUninit::uninit() }; N],
+                    len: 0,
+                };
+                while array.len < N {
+                    let value = seq
+                        .next_element_state::<T, State>(self.state)?
+                        .ok_or_else(|| serde::de::Error::invalid_length(array.len, &self))?;
+                    array.values[array.len].write(value);
+                    array.len += 1;
+                }
+                let array = ManuallyDrop::new(array);
+                // SAFETY: all `N` elements are initialized, and `[MaybeUninit<T>; N]` has the
+                // same layout as `[T; N]`. `ManuallyDrop` keeps them from being dropped twice.
+                Ok(unsafe { ptr::read(array.values.as_ptr().cast::<[T; N]>()) })
             }
         }
 
         deserializer.deserialize_tuple(
-            2,
-            Tuple2Visitor {
+            N,
+            ArrayVisitor {
                 state,
                 marker: PhantomData,
             },
         )
     }
 }
+
+/// Newtype wrappers from std that serde serializes transparently.
+macro_rules! impl_state_transparent_newtype {
+    ($($ty:ident),* $(,)?) => {
+        $(
+            impl<State: ?Sized, T: SerializeState<State>> SerializeState<State> for $ty<T> {
+                fn serialize_state<S>(&self, state: &State, serializer: S) -> Result<S::Ok, S::Error>
+                where
+                    S: serde::Serializer,
+                {
+                    self.0.serialize_state(state, serializer)
+                }
+            }
+
+            impl<'de, State: ?Sized, T> DeserializeState<'de, State> for $ty<T>
+            where
+                T: DeserializeState<'de, State>,
+            {
+                fn deserialize_state<D>(state: &State, deserializer: D) -> Result<Self, D::Error>
+                where
+                    D: serde::Deserializer<'de>,
+                {
+                    T::deserialize_state(state, deserializer).map($ty)
+                }
+            }
+        )*
+    };
+}
+
+impl_state_transparent_newtype!(Wrapping, Reverse);
+
+/// Serializes a range the way serde does: as a struct with `start` and/or `end` fields.
+fn serialize_range_state<State, Idx, S>(
+    name: &'static str,
+    start: Option<&Idx>,
+    end: Option<&Idx>,
+    state: &State,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    State: ?Sized,
+    Idx: SerializeState<State>,
+    S: serde::Serializer,
+{
+    let len = start.is_some() as usize + end.is_some() as usize;
+    let mut fields = serializer.serialize_struct(name, len)?;
+    if let Some(start) = start {
+        fields.serialize_field_state("start", start, state)?;
+    }
+    if let Some(end) = end {
+        fields.serialize_field_state("end", end, state)?;
+    }
+    fields.end()
+}
+
+enum RangeField {
+    Start,
+    End,
+}
+
+/// Identifies a range field, rejecting any name that is not in `fields`.
+struct RangeFieldVisitor {
+    fields: &'static [&'static str],
+}
+
+impl<'de> serde::de::DeserializeSeed<'de> for RangeFieldVisitor {
+    type Value = RangeField;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_identifier(self)
+    }
+}
+
+impl<'de> serde::de::Visitor<'de> for RangeFieldVisitor {
+    type Value = RangeField;
+
+    fn expecting(&self, formatter: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for (index, field) in self.fields.iter().enumerate() {
+            if index > 0 {
+                formatter.write_str(" or ")?;
+            }
+            write!(formatter, "`{field}`")?;
+        }
+        Ok(())
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        if !self.fields.contains(&value) {
+            return Err(E::unknown_field(value, self.fields));
+        }
+        match value {
+            "start" => Ok(RangeField::Start),
+            _ => Ok(RangeField::End),
+        }
+    }
+
+    fn visit_bytes<E>(self, value: &[u8]) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        match core::str::from_utf8(value) {
+            Ok(value) => self.visit_str(value),
+            Err(_) => Err(E::invalid_value(serde::de::Unexpected::Bytes(value), &self)),
+        }
+    }
+}
+
+/// Deserializes the `start`/`end` fields named in `fields`; the ones listed are always `Some`.
+fn deserialize_range_state<'de, State, Idx, D>(
+    name: &'static str,
+    fields: &'static [&'static str],
+    state: &State,
+    deserializer: D,
+) -> Result<(Option<Idx>, Option<Idx>), D::Error>
+where
+    State: ?Sized,
+    Idx: DeserializeState<'de, State>,
+    D: serde::Deserializer<'de>,
+{
+    struct RangeVisitor<'state, State: ?Sized, Idx> {
+        name: &'static str,
+        fields: &'static [&'static str],
+        state: &'state State,
+        marker: PhantomData<Idx>,
+    }
+
+    impl<'de, 'state, State: ?Sized, Idx> serde::de::Visitor<'de> for RangeVisitor<'state, State, Idx>
+    where
+        Idx: DeserializeState<'de, State>,
+    {
+        type Value = (Option<Idx>, Option<Idx>);
+
+        fn expecting(&self, formatter: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            write!(formatter, "struct {}", self.name)
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: serde::de::SeqAccess<'de>,
+        {
+            let mut start = None;
+            let mut end = None;
+            for (index, field) in self.fields.iter().enumerate() {
+                let value = seq
+                    .next_element_state::<Idx, State>(self.state)?
+                    .ok_or_else(|| serde::de::Error::invalid_length(index, &self))?;
+                match *field {
+                    "start" => start = Some(value),
+                    _ => end = Some(value),
+                }
+            }
+            Ok((start, end))
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+        where
+            A: serde::de::MapAccess<'de>,
+        {
+            let mut start = None;
+            let mut end = None;
+            while let Some(key) = map.next_key_seed(RangeFieldVisitor {
+                fields: self.fields,
+            })? {
+                let (slot, name) = match key {
+                    RangeField::Start => (&mut start, "start"),
+                    RangeField::End => (&mut end, "end"),
+                };
+                if slot.is_some() {
+                    return Err(serde::de::Error::duplicate_field(name));
+                }
+                *slot = Some(map.next_value_state::<Idx, State>(self.state)?);
+            }
+            if self.fields.contains(&"start") && start.is_none() {
+                return Err(serde::de::Error::missing_field("start"));
+            }
+            if self.fields.contains(&"end") && end.is_none() {
+                return Err(serde::de::Error::missing_field("end"));
+            }
+            Ok((start, end))
+        }
+    }
+
+    deserializer.deserialize_struct(
+        name,
+        fields,
+        RangeVisitor {
+            name,
+            fields,
+            state,
+            marker: PhantomData,
+        },
+    )
+}
+
+impl<State: ?Sized, Idx: SerializeState<State>> SerializeState<State> for Range<Idx> {
+    fn serialize_state<S>(&self, state: &State, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serialize_range_state(
+            "Range",
+            Some(&self.start),
+            Some(&self.end),
+            state,
+            serializer,
+        )
+    }
+}
+impl<'de, State: ?Sized, Idx> DeserializeState<'de, State> for Range<Idx>
+where
+    Idx: DeserializeState<'de, State>,
+{
+    fn deserialize_state<D>(state: &State, deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        match deserialize_range_state("Range", &["start", "end"], state, deserializer)? {
+            (Some(start), Some(end)) => Ok(start..end),
+            _ => unreachable!("missing range fields are reported as errors"),
+        }
+    }
+}
+
+impl<State: ?Sized, Idx: SerializeState<State>> SerializeState<State> for RangeInclusive<Idx> {
+    fn serialize_state<S>(&self, state: &State, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serialize_range_state(
+            "RangeInclusive",
+            Some(self.start()),
+            Some(self.end()),
+            state,
+            serializer,
+        )
+    }
+}
+impl<'de, State: ?Sized, Idx> DeserializeState<'de, State> for RangeInclusive<Idx>
+where
+    Idx: DeserializeState<'de, State>,
+{
+    fn deserialize_state<D>(state: &State, deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        match deserialize_range_state("RangeInclusive", &["start", "end"], state, deserializer)? {
+            (Some(start), Some(end)) => Ok(start..=end),
+            _ => unreachable!("missing range fields are reported as errors"),
+        }
+    }
+}
+
+impl<State: ?Sized, Idx: SerializeState<State>> SerializeState<State> for RangeFrom<Idx> {
+    fn serialize_state<S>(&self, state: &State, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serialize_range_state("RangeFrom", Some(&self.start), None, state, serializer)
+    }
+}
+impl<'de, State: ?Sized, Idx> DeserializeState<'de, State> for RangeFrom<Idx>
+where
+    Idx: DeserializeState<'de, State>,
+{
+    fn deserialize_state<D>(state: &State, deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        match deserialize_range_state("RangeFrom", &["start"], state, deserializer)? {
+            (Some(start), _) => Ok(start..),
+            _ => unreachable!("missing range fields are reported as errors"),
+        }
+    }
+}
+
+impl<State: ?Sized, Idx: SerializeState<State>> SerializeState<State> for RangeTo<Idx> {
+    fn serialize_state<S>(&self, state: &State, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serialize_range_state("RangeTo", None, Some(&self.end), state, serializer)
+    }
+}
+impl<'de, State: ?Sized, Idx> DeserializeState<'de, State> for RangeTo<Idx>
+where
+    Idx: DeserializeState<'de, State>,
+{
+    fn deserialize_state<D>(state: &State, deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        match deserialize_range_state("RangeTo", &["end"], state, deserializer)? {
+            (_, Some(end)) => Ok(..end),
+            _ => unreachable!("missing range fields are reported as errors"),
+        }
+    }
+}
+
+impl<State: ?Sized, T: SerializeState<State>> SerializeState<State> for Bound<T> {
+    fn serialize_state<S>(&self, state: &State, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            Bound::Unbounded => serializer.serialize_unit_variant("Bound", 0, "Unbounded"),
+            Bound::Included(value) => serializer.serialize_newtype_variant(
+                "Bound",
+                1,
+                "Included",
+                &StateRef::new(value, state),
+            ),
+            Bound::Excluded(value) => serializer.serialize_newtype_variant(
+                "Bound",
+                2,
+                "Excluded",
+                &StateRef::new(value, state),
+            ),
+        }
+    }
+}
+
+impl<'de, State: ?Sized, T> DeserializeState<'de, State> for Bound<T>
+where
+    T: DeserializeState<'de, State>,
+{
+    fn deserialize_state<D>(state: &State, deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        const VARIANTS: &[&str] = &["Unbounded", "Included", "Excluded"];
+
+        enum Variant {
+            Unbounded,
+            Included,
+            Excluded,
+        }
+
+        struct VariantVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for VariantVisitor {
+            type Value = Variant;
+
+            fn expecting(&self, formatter: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                formatter.write_str("`Unbounded`, `Included` or `Excluded`")
+            }
+
+            fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                match value {
+                    0 => Ok(Variant::Unbounded),
+                    1 => Ok(Variant::Included),
+                    2 => Ok(Variant::Excluded),
+                    _ => Err(E::invalid_value(
+                        serde::de::Unexpected::Unsigned(value),
+                        &self,
+                    )),
+                }
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                match value {
+                    "Unbounded" => Ok(Variant::Unbounded),
+                    "Included" => Ok(Variant::Included),
+                    "Excluded" => Ok(Variant::Excluded),
+                    _ => Err(E::unknown_variant(value, VARIANTS)),
+                }
+            }
+
+            fn visit_bytes<E>(self, value: &[u8]) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                match core::str::from_utf8(value) {
+                    Ok(value) => self.visit_str(value),
+                    Err(_) => Err(E::invalid_value(serde::de::Unexpected::Bytes(value), &self)),
+                }
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for Variant {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                deserializer.deserialize_identifier(VariantVisitor)
+            }
+        }
+
+        struct BoundVisitor<'state, State: ?Sized, T> {
+            state: &'state State,
+            marker: PhantomData<T>,
+        }
+
+        impl<'de, 'state, State: ?Sized, T> serde::de::Visitor<'de> for BoundVisitor<'state, State, T>
+        where
+            T: DeserializeState<'de, State>,
+        {
+            type Value = Bound<T>;
+
+            fn expecting(&self, formatter: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                formatter.write_str("enum Bound")
+            }
+
+            fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::EnumAccess<'de>,
+            {
+                use serde::de::VariantAccess;
+
+                match data.variant()? {
+                    (Variant::Unbounded, variant) => {
+                        variant.unit_variant().map(|()| Bound::Unbounded)
+                    }
+                    (Variant::Included, variant) => variant
+                        .newtype_variant_state::<T, State>(self.state)
+                        .map(Bound::Included),
+                    (Variant::Excluded, variant) => variant
+                        .newtype_variant_state::<T, State>(self.state)
+                        .map(Bound::Excluded),
+                }
+            }
+        }
+
+        deserializer.deserialize_enum(
+            "Bound",
+            VARIANTS,
+            BoundVisitor {
+                state,
+                marker: PhantomData,
+            },
+        )
+    }
+}
+
+impl<State: ?Sized, T, E> SerializeState<State> for Result<T, E>
+where
+    T: SerializeState<State>,
+    E: SerializeState<State>,
+{
+    fn serialize_state<S>(&self, state: &State, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            Ok(value) => serializer.serialize_newtype_variant(
+                "Result",
+                0,
+                "Ok",
+                &StateRef::new(value, state),
+            ),
+            Err(value) => serializer.serialize_newtype_variant(
+                "Result",
+                1,
+                "Err",
+                &StateRef::new(value, state),
+            ),
+        }
+    }
+}
+
+impl<'de, State: ?Sized, T, E> DeserializeState<'de, State> for Result<T, E>
+where
+    T: DeserializeState<'de, State>,
+    E: DeserializeState<'de, State>,
+{
+    fn deserialize_state<D>(state: &State, deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        const VARIANTS: &[&str] = &["Ok", "Err"];
+
+        enum Variant {
+            Ok,
+            Err,
+        }
+
+        struct VariantVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for VariantVisitor {
+            type Value = Variant;
+
+            fn expecting(&self, formatter: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                formatter.write_str("`Ok` or `Err`")
+            }
+
+            fn visit_u64<Error>(self, value: u64) -> Result<Self::Value, Error>
+            where
+                Error: serde::de::Error,
+            {
+                match value {
+                    0 => Ok(Variant::Ok),
+                    1 => Ok(Variant::Err),
+                    _ => Err(Error::invalid_value(
+                        serde::de::Unexpected::Unsigned(value),
+                        &self,
+                    )),
+                }
+            }
+
+            fn visit_str<Error>(self, value: &str) -> Result<Self::Value, Error>
+            where
+                Error: serde::de::Error,
+            {
+                match value {
+                    "Ok" => Ok(Variant::Ok),
+                    "Err" => Ok(Variant::Err),
+                    _ => Err(Error::unknown_variant(value, VARIANTS)),
+                }
+            }
+
+            fn visit_bytes<Error>(self, value: &[u8]) -> Result<Self::Value, Error>
+            where
+                Error: serde::de::Error,
+            {
+                match core::str::from_utf8(value) {
+                    Ok(value) => self.visit_str(value),
+                    Err(_) => Err(Error::invalid_value(
+                        serde::de::Unexpected::Bytes(value),
+                        &self,
+                    )),
+                }
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for Variant {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                deserializer.deserialize_identifier(VariantVisitor)
+            }
+        }
+
+        struct ResultVisitor<'state, State: ?Sized, T, E> {
+            state: &'state State,
+            marker: PhantomData<Result<T, E>>,
+        }
+
+        impl<'de, 'state, State: ?Sized, T, E> serde::de::Visitor<'de>
+            for ResultVisitor<'state, State, T, E>
+        where
+            T: DeserializeState<'de, State>,
+            E: DeserializeState<'de, State>,
+        {
+            type Value = Result<T, E>;
+
+            fn expecting(&self, formatter: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                formatter.write_str("enum Result")
+            }
+
+            fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::EnumAccess<'de>,
+            {
+                match data.variant()? {
+                    (Variant::Ok, variant) => variant
+                        .newtype_variant_state::<T, State>(self.state)
+                        .map(Ok),
+                    (Variant::Err, variant) => variant
+                        .newtype_variant_state::<E, State>(self.state)
+                        .map(Err),
+                }
+            }
+        }
+
+        deserializer.deserialize_enum(
+            "Result",
+            VARIANTS,
+            ResultVisitor {
+                state,
+                marker: PhantomData,
+            },
+        )
+    }
+}
+
+fn serialize_seq_state<'a, State, T, I, S>(
+    iter: I,
+    len: usize,
+    state: &State,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    State: ?Sized,
+    T: SerializeState<State> + 'a,
+    I: IntoIterator<Item = &'a T>,
+    S: serde::Serializer,
+{
+    let mut seq = serializer.serialize_seq(Some(len))?;
+    for value in iter {
+        seq.serialize_element_state(value, state)?;
+    }
+    seq.end()
+}
+
+fn serialize_map_state<'a, State, K, V, I, S>(
+    iter: I,
+    len: usize,
+    state: &State,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    State: ?Sized,
+    K: SerializeState<State> + 'a,
+    V: SerializeState<State> + 'a,
+    I: IntoIterator<Item = (&'a K, &'a V)>,
+    S: serde::Serializer,
+{
+    let mut map = serializer.serialize_map(Some(len))?;
+    for (key, value) in iter {
+        map.serialize_entry_state(key, value, state)?;
+    }
+    map.end()
+}
+
+/// Deserializes any collection that can be built by extending it one element at a time.
+fn deserialize_seq_state<'de, State, T, C, D>(state: &State, deserializer: D) -> Result<C, D::Error>
+where
+    State: ?Sized,
+    T: DeserializeState<'de, State>,
+    C: Default + Extend<T>,
+    D: serde::Deserializer<'de>,
+{
+    struct SeqVisitor<'state, State: ?Sized, T, C> {
+        state: &'state State,
+        marker: PhantomData<(T, C)>,
+    }
+
+    impl<'de, 'state, State: ?Sized, T, C> serde::de::Visitor<'de> for SeqVisitor<'state, State, T, C>
+    where
+        T: DeserializeState<'de, State>,
+        C: Default + Extend<T>,
+    {
+        type Value = C;
+
+        fn expecting(&self, formatter: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            formatter.write_str("a sequence")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: serde::de::SeqAccess<'de>,
+        {
+            let mut values = C::default();
+            while let Some(value) = seq.next_element_state::<T, State>(self.state)? {
+                values.extend(Some(value));
+            }
+            Ok(values)
+        }
+    }
+
+    deserializer.deserialize_seq(SeqVisitor {
+        state,
+        marker: PhantomData,
+    })
+}
+
+/// Deserializes any map that can be built by extending it one entry at a time.
+fn deserialize_map_state<'de, State, K, V, C, D>(
+    state: &State,
+    deserializer: D,
+) -> Result<C, D::Error>
+where
+    State: ?Sized,
+    K: DeserializeState<'de, State>,
+    V: DeserializeState<'de, State>,
+    C: Default + Extend<(K, V)>,
+    D: serde::Deserializer<'de>,
+{
+    struct MapVisitor<'state, State: ?Sized, K, V, C> {
+        state: &'state State,
+        marker: PhantomData<(K, V, C)>,
+    }
+
+    impl<'de, 'state, State: ?Sized, K, V, C> serde::de::Visitor<'de>
+        for MapVisitor<'state, State, K, V, C>
+    where
+        K: DeserializeState<'de, State>,
+        V: DeserializeState<'de, State>,
+        C: Default + Extend<(K, V)>,
+    {
+        type Value = C;
+
+        fn expecting(&self, formatter: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            formatter.write_str("a map")
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+        where
+            A: serde::de::MapAccess<'de>,
+        {
+            let mut values = C::default();
+            while let Some(entry) = map.next_entry_state::<K, V, State>(self.state)? {
+                values.extend(Some(entry));
+            }
+            Ok(values)
+        }
+    }
+
+    deserializer.deserialize_map(MapVisitor {
+        state,
+        marker: PhantomData,
+    })
+}
+
+macro_rules! impl_state_seq {
+    ($($ty:ident <T $(, $param:ident)*> where [$($de_bounds:tt)*]),* $(,)?) => {
+        $(
+            impl<State: ?Sized, T $(, $param)*> SerializeState<State> for $ty<T $(, $param)*>
+            where
+                T: SerializeState<State>,
+            {
+                fn serialize_state<S>(&self, state: &State, serializer: S) -> Result<S::Ok, S::Error>
+                where
+                    S: serde::Serializer,
+                {
+                    serialize_seq_state(self, self.len(), state, serializer)
+                }
+            }
+
+            impl<'de, State: ?Sized, T $(, $param)*> DeserializeState<'de, State> for $ty<T $(, $param)*>
+            where
+                T: DeserializeState<'de, State>,
+                $($de_bounds)*
+            {
+                fn deserialize_state<D>(state: &State, deserializer: D) -> Result<Self, D::Error>
+                where
+                    D: serde::Deserializer<'de>,
+                {
+                    deserialize_seq_state(state, deserializer)
+                }
+            }
+        )*
+    };
+}
+
+impl_state_seq!(
+    VecDeque<T> where [],
+    LinkedList<T> where [],
+    BinaryHeap<T> where [T: Ord],
+    BTreeSet<T> where [T: Ord],
+    HashSet<T, H> where [T: Eq + Hash, H: BuildHasher + Default],
+);
+
+macro_rules! impl_state_map {
+    ($($ty:ident <K, V $(, $param:ident)*> where [$($de_bounds:tt)*]),* $(,)?) => {
+        $(
+            impl<State: ?Sized, K, V $(, $param)*> SerializeState<State> for $ty<K, V $(, $param)*>
+            where
+                K: SerializeState<State>,
+                V: SerializeState<State>,
+            {
+                fn serialize_state<S>(&self, state: &State, serializer: S) -> Result<S::Ok, S::Error>
+                where
+                    S: serde::Serializer,
+                {
+                    serialize_map_state(self, self.len(), state, serializer)
+                }
+            }
+
+            impl<'de, State: ?Sized, K, V $(, $param)*> DeserializeState<'de, State>
+                for $ty<K, V $(, $param)*>
+            where
+                K: DeserializeState<'de, State>,
+                V: DeserializeState<'de, State>,
+                $($de_bounds)*
+            {
+                fn deserialize_state<D>(state: &State, deserializer: D) -> Result<Self, D::Error>
+                where
+                    D: serde::Deserializer<'de>,
+                {
+                    deserialize_map_state(state, deserializer)
+                }
+            }
+        )*
+    };
+}
+
+impl_state_map!(
+    BTreeMap<K, V> where [K: Ord],
+    HashMap<K, V, H> where [K: Eq + Hash, H: BuildHasher + Default],
+);
@@ -0,0 +1,1041 @@
+//! Deserializer adapters for the derives: buffering of input for the non-externally tagged enum
+//! representations and flattened fields, and a stand-in for missing fields.
+//!
+//! The stateful derives can't use the buffering types serde generates code against because
+//! they are not part of serde's public API, so this is a self-contained copy of the subset they
+//! need.
+
+use core::fmt;
+use core::marker::PhantomData;
+use serde::de::value::{MapDeserializer, SeqAccessDeserializer, SeqDeserializer};
+use serde::de::{
+    self, Deserialize, DeserializeSeed, Deserializer, EnumAccess, IgnoredAny, IntoDeserializer,
+    MapAccess, SeqAccess, Unexpected, VariantAccess, Visitor,
+};
+
+/// A buffered value of the serde data model.
+#[derive(Clone, Debug)]
+pub enum Content<'de> {
+    Bool(bool),
+
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+
+    F32(f32),
+    F64(f64),
+
+    Char(char),
+    String(String),
+    Str(&'de str),
+    ByteBuf(Vec<u8>),
+    Bytes(&'de [u8]),
+
+    None,
+    Some(Box<Content<'de>>),
+
+    Unit,
+    Newtype(Box<Content<'de>>),
+    Seq(Vec<Content<'de>>),
+    Map(Vec<(Content<'de>, Content<'de>)>),
+}
+
+impl<'de> Content<'de> {
+    /// Returns the content as a string if it is one, which is how tags and keys are compared.
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Content::String(ref value) => Some(value),
+            Content::Str(value) => Some(value),
+            Content::ByteBuf(ref value) => core::str::from_utf8(value).ok(),
+            Content::Bytes(value) => core::str::from_utf8(value).ok(),
+            _ => None,
+        }
+    }
+
+    fn unexpected(&self) -> Unexpected<'_> {
+        match *self {
+            Content::Bool(value) => Unexpected::Bool(value),
+            Content::U8(value) => Unexpected::Unsigned(value as u64),
+            Content::U16(value) => Unexpected::Unsigned(value as u64),
+            Content::U32(value) => Unexpected::Unsigned(value as u64),
+            Content::U64(value) => Unexpected::Unsigned(value),
+            Content::I8(value) => Unexpected::Signed(value as i64),
+            Content::I16(value) => Unexpected::Signed(value as i64),
+            Content::I32(value) => Unexpected::Signed(value as i64),
+            Content::I64(value) => Unexpected::Signed(value),
+            Content::F32(value) => Unexpected::Float(value as f64),
+            Content::F64(value) => Unexpected::Float(value),
+            Content::Char(value) => Unexpected::Char(value),
+            Content::String(ref value) => Unexpected::Str(value),
+            Content::Str(value) => Unexpected::Str(value),
+            Content::ByteBuf(ref value) => Unexpected::Bytes(value),
+            Content::Bytes(value) => Unexpected::Bytes(value),
+            Content::None | Content::Some(_) => Unexpected::Option,
+            Content::Unit => Unexpected::Unit,
+            Content::Newtype(_) => Unexpected::NewtypeStruct,
+            Content::Seq(_) => Unexpected::Seq,
+            Content::Map(_) => Unexpected::Map,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Content<'de> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ContentVisitor)
+    }
+}
+
+struct ContentVisitor;
+
+impl<'de> Visitor<'de> for ContentVisitor {
+    type Value = Content<'de>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("any value")
+    }
+
+    fn visit_bool<E>(self, value: bool) -> Result<Self::Value, E> {
+        Ok(Content::Bool(value))
+    }
+
+    fn visit_i8<E>(self, value: i8) -> Result<Self::Value, E> {
+        Ok(Content::I8(value))
+    }
+
+    fn visit_i16<E>(self, value: i16) -> Result<Self::Value, E> {
+        Ok(Content::I16(value))
+    }
+
+    fn visit_i32<E>(self, value: i32) -> Result<Self::Value, E> {
+        Ok(Content::I32(value))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E> {
+        Ok(Content::I64(value))
+    }
+
+    fn visit_u8<E>(self, value: u8) -> Result<Self::Value, E> {
+        Ok(Content::U8(value))
+    }
+
+    fn visit_u16<E>(self, value: u16) -> Result<Self::Value, E> {
+        Ok(Content::U16(value))
+    }
+
+    fn visit_u32<E>(self, value: u32) -> Result<Self::Value, E> {
+        Ok(Content::U32(value))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E> {
+        Ok(Content::U64(value))
+    }
+
+    fn visit_f32<E>(self, value: f32) -> Result<Self::Value, E> {
+        Ok(Content::F32(value))
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<Self::Value, E> {
+        Ok(Content::F64(value))
+    }
+
+    fn visit_char<E>(self, value: char) -> Result<Self::Value, E> {
+        Ok(Content::Char(value))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E> {
+        Ok(Content::String(value.to_owned()))
+    }
+
+    fn visit_borrowed_str<E>(self, value: &'de str) -> Result<Self::Value, E> {
+        Ok(Content::Str(value))
+    }
+
+    fn visit_string<E>(self, value: String) -> Result<Self::Value, E> {
+        Ok(Content::String(value))
+    }
+
+    fn visit_bytes<E>(self, value: &[u8]) -> Result<Self::Value, E> {
+        Ok(Content::ByteBuf(value.to_vec()))
+    }
+
+    fn visit_borrowed_bytes<E>(self, value: &'de [u8]) -> Result<Self::Value, E> {
+        Ok(Content::Bytes(value))
+    }
+
+    fn visit_byte_buf<E>(self, value: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(Content::ByteBuf(value))
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E> {
+        Ok(Content::Unit)
+    }
+
+    fn visit_none<E>(self) -> Result<Self::Value, E> {
+        Ok(Content::None)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let content = Content::deserialize(deserializer)?;
+        Ok(Content::Some(Box::new(content)))
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let content = Content::deserialize(deserializer)?;
+        Ok(Content::Newtype(Box::new(content)))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut values = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+        Ok(Content::Seq(values))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut entries = Vec::with_capacity(map.size_hint().unwrap_or(0).min(4096));
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(Content::Map(entries))
+    }
+
+    fn visit_enum<A>(self, _data: A) -> Result<Self::Value, A::Error>
+    where
+        A: EnumAccess<'de>,
+    {
+        Err(de::Error::custom(
+            "untagged and internally tagged enums do not support enum input",
+        ))
+    }
+}
+
+/// Replays buffered [`Content`] into a visitor.
+pub struct ContentDeserializer<'de, E> {
+    content: Content<'de>,
+    err: PhantomData<E>,
+}
+
+impl<'de, E> ContentDeserializer<'de, E>
+where
+    E: de::Error,
+{
+    pub fn new(content: Content<'de>) -> Self {
+        ContentDeserializer {
+            content,
+            err: PhantomData,
+        }
+    }
+}
+
+fn visit_content_seq<'de, V, E>(content: Vec<Content<'de>>, visitor: V) -> Result<V::Value, E>
+where
+    V: Visitor<'de>,
+    E: de::Error,
+{
+    let mut seq = SeqDeserializer::new(content.into_iter());
+    let value = visitor.visit_seq(&mut seq)?;
+    seq.end()?;
+    Ok(value)
+}
+
+fn visit_content_map<'de, V, E>(
+    content: Vec<(Content<'de>, Content<'de>)>,
+    visitor: V,
+) -> Result<V::Value, E>
+where
+    V: Visitor<'de>,
+    E: de::Error,
+{
+    let mut map = MapDeserializer::new(content.into_iter());
+    let value = visitor.visit_map(&mut map)?;
+    map.end()?;
+    Ok(value)
+}
+
+impl<'de, E> Deserializer<'de> for ContentDeserializer<'de, E>
+where
+    E: de::Error,
+{
+    type Error = E;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.content {
+            Content::Bool(value) => visitor.visit_bool(value),
+            Content::U8(value) => visitor.visit_u8(value),
+            Content::U16(value) => visitor.visit_u16(value),
+            Content::U32(value) => visitor.visit_u32(value),
+            Content::U64(value) => visitor.visit_u64(value),
+            Content::I8(value) => visitor.visit_i8(value),
+            Content::I16(value) => visitor.visit_i16(value),
+            Content::I32(value) => visitor.visit_i32(value),
+            Content::I64(value) => visitor.visit_i64(value),
+            Content::F32(value) => visitor.visit_f32(value),
+            Content::F64(value) => visitor.visit_f64(value),
+            Content::Char(value) => visitor.visit_char(value),
+            Content::String(value) => visitor.visit_string(value),
+            Content::Str(value) => visitor.visit_borrowed_str(value),
+            Content::ByteBuf(value) => visitor.visit_byte_buf(value),
+            Content::Bytes(value) => visitor.visit_borrowed_bytes(value),
+            Content::Unit => visitor.visit_unit(),
+            Content::None => visitor.visit_none(),
+            Content::Some(value) => visitor.visit_some(ContentDeserializer::new(*value)),
+            Content::Newtype(value) => {
+                visitor.visit_newtype_struct(ContentDeserializer::new(*value))
+            }
+            Content::Seq(values) => visit_content_seq(values, visitor),
+            Content::Map(entries) => visit_content_map(entries, visitor),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.content {
+            Content::None => visitor.visit_none(),
+            Content::Some(value) => visitor.visit_some(ContentDeserializer::new(*value)),
+            Content::Unit => visitor.visit_unit(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.content {
+            // A unit struct inside an internally tagged newtype variant is written as a map that
+            // only holds the tag, which is gone by the time the content is replayed.
+            Content::Map(ref entries) if entries.is_empty() => visitor.visit_unit(),
+            Content::Seq(ref values) if values.is_empty() => visitor.visit_unit(),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.content {
+            Content::Newtype(value) => {
+                visitor.visit_newtype_struct(ContentDeserializer::new(*value))
+            }
+            _ => visitor.visit_newtype_struct(self),
+        }
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        let (variant, value) = match self.content {
+            Content::Map(entries) => {
+                let mut iter = entries.into_iter();
+                let (variant, value) = match iter.next() {
+                    Some(entry) => entry,
+                    None => {
+                        return Err(de::Error::invalid_value(
+                            Unexpected::Map,
+                            &"map with a single key",
+                        ));
+                    }
+                };
+                if iter.next().is_some() {
+                    return Err(de::Error::invalid_value(
+                        Unexpected::Map,
+                        &"map with a single key",
+                    ));
+                }
+                (variant, Some(value))
+            }
+            content @ (Content::String(_) | Content::Str(_)) => (content, None),
+            other => {
+                return Err(de::Error::invalid_type(
+                    other.unexpected(),
+                    &"string or map",
+                ));
+            }
+        };
+
+        visitor.visit_enum(EnumDeserializer {
+            variant,
+            value,
+            err: PhantomData,
+        })
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        drop(self);
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit seq tuple tuple_struct map struct identifier
+    }
+}
+
+impl<'de, E> IntoDeserializer<'de, E> for Content<'de>
+where
+    E: de::Error,
+{
+    type Deserializer = ContentDeserializer<'de, E>;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        ContentDeserializer::new(self)
+    }
+}
+
+struct EnumDeserializer<'de, E> {
+    variant: Content<'de>,
+    value: Option<Content<'de>>,
+    err: PhantomData<E>,
+}
+
+impl<'de, E> EnumAccess<'de> for EnumDeserializer<'de, E>
+where
+    E: de::Error,
+{
+    type Error = E;
+    type Variant = VariantDeserializer<'de, E>;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), E>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(ContentDeserializer::<E>::new(self.variant))?;
+        Ok((
+            variant,
+            VariantDeserializer {
+                value: self.value,
+                err: PhantomData,
+            },
+        ))
+    }
+}
+
+struct VariantDeserializer<'de, E> {
+    value: Option<Content<'de>>,
+    err: PhantomData<E>,
+}
+
+impl<'de, E> VariantAccess<'de> for VariantDeserializer<'de, E>
+where
+    E: de::Error,
+{
+    type Error = E;
+
+    fn unit_variant(self) -> Result<(), E> {
+        match self.value {
+            Some(value) => Deserialize::deserialize(ContentDeserializer::<E>::new(value)),
+            None => Ok(()),
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, E>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.value {
+            Some(value) => seed.deserialize(ContentDeserializer::new(value)),
+            None => Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"newtype variant",
+            )),
+        }
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Some(Content::Seq(values)) => visit_content_seq(values, visitor),
+            Some(other) => Err(de::Error::invalid_type(
+                other.unexpected(),
+                &"tuple variant",
+            )),
+            None => Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"tuple variant",
+            )),
+        }
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Some(Content::Map(entries)) => visit_content_map(entries, visitor),
+            Some(Content::Seq(values)) => visit_content_seq(values, visitor),
+            Some(other) => Err(de::Error::invalid_type(
+                other.unexpected(),
+                &"struct variant",
+            )),
+            None => Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"struct variant",
+            )),
+        }
+    }
+}
+
+/// Splits the tag of an internally tagged enum from the rest of the input, which is buffered.
+pub struct TaggedContentVisitor<T> {
+    tag_name: &'static str,
+    expecting: &'static str,
+    value: PhantomData<T>,
+}
+
+impl<T> TaggedContentVisitor<T> {
+    pub fn new(tag_name: &'static str, expecting: &'static str) -> Self {
+        TaggedContentVisitor {
+            tag_name,
+            expecting,
+            value: PhantomData,
+        }
+    }
+}
+
+impl<'de, T> Visitor<'de> for TaggedContentVisitor<T>
+where
+    T: Deserialize<'de>,
+{
+    type Value = (T, Content<'de>);
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str(self.expecting)
+    }
+
+    fn visit_seq<S>(self, mut seq: S) -> Result<Self::Value, S::Error>
+    where
+        S: SeqAccess<'de>,
+    {
+        let tag = match seq.next_element()? {
+            Some(tag) => tag,
+            None => return Err(de::Error::missing_field(self.tag_name)),
+        };
+        let rest = Content::deserialize(SeqAccessDeserializer::new(seq))?;
+        Ok((tag, rest))
+    }
+
+    fn visit_map<M>(self, mut map: M) -> Result<Self::Value, M::Error>
+    where
+        M: MapAccess<'de>,
+    {
+        let mut tag = None;
+        let mut entries = Vec::with_capacity(map.size_hint().unwrap_or(0).min(4096));
+        while let Some(key) = map.next_key::<Content<'de>>()? {
+            if key.as_str() == Some(self.tag_name) {
+                if tag.is_some() {
+                    return Err(de::Error::duplicate_field(self.tag_name));
+                }
+                tag = Some(map.next_value()?);
+            } else {
+                entries.push((key, map.next_value()?));
+            }
+        }
+        match tag {
+            Some(tag) => Ok((tag, Content::Map(entries))),
+            None => Err(de::Error::missing_field(self.tag_name)),
+        }
+    }
+}
+
+/// Accepts whatever is left of an internally tagged unit variant once the tag is removed.
+pub struct InternallyTaggedUnitVisitor {
+    type_name: &'static str,
+    variant_name: &'static str,
+}
+
+impl InternallyTaggedUnitVisitor {
+    pub fn new(type_name: &'static str, variant_name: &'static str) -> Self {
+        InternallyTaggedUnitVisitor {
+            type_name,
+            variant_name,
+        }
+    }
+}
+
+impl<'de> Visitor<'de> for InternallyTaggedUnitVisitor {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "unit variant {}::{}",
+            self.type_name, self.variant_name
+        )
+    }
+
+    fn visit_unit<E>(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_seq<S>(self, mut seq: S) -> Result<(), S::Error>
+    where
+        S: SeqAccess<'de>,
+    {
+        while let Some(IgnoredAny) = seq.next_element()? {}
+        Ok(())
+    }
+
+    fn visit_map<M>(self, mut map: M) -> Result<(), M::Error>
+    where
+        M: MapAccess<'de>,
+    {
+        while let Some((IgnoredAny, IgnoredAny)) = map.next_entry()? {}
+        Ok(())
+    }
+}
+
+/// Accepts the content of an untagged unit variant, which like in serde is either unit or none.
+pub struct UntaggedUnitVisitor {
+    type_name: &'static str,
+    variant_name: &'static str,
+}
+
+impl UntaggedUnitVisitor {
+    pub fn new(type_name: &'static str, variant_name: &'static str) -> Self {
+        UntaggedUnitVisitor {
+            type_name,
+            variant_name,
+        }
+    }
+}
+
+impl<'de> Visitor<'de> for UntaggedUnitVisitor {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "unit variant {}::{}",
+            self.type_name, self.variant_name
+        )
+    }
+
+    fn visit_unit<E>(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_none<E>(self) -> Result<(), E> {
+        Ok(())
+    }
+}
+
+/// A key of an adjacently tagged enum.
+pub enum TagContentOtherField {
+    Tag,
+    Content,
+    Other,
+}
+
+/// Classifies the keys of an adjacently tagged enum.
+pub struct TagContentOtherFieldVisitor {
+    tag: &'static str,
+    content: &'static str,
+}
+
+impl TagContentOtherFieldVisitor {
+    pub fn new(tag: &'static str, content: &'static str) -> Self {
+        TagContentOtherFieldVisitor { tag, content }
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for TagContentOtherFieldVisitor {
+    type Value = TagContentOtherField;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_identifier(self)
+    }
+}
+
+impl<'de> Visitor<'de> for TagContentOtherFieldVisitor {
+    type Value = TagContentOtherField;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "{:?}, {:?}, or other ignored fields",
+            self.tag, self.content
+        )
+    }
+
+    fn visit_str<E>(self, field: &str) -> Result<Self::Value, E> {
+        Ok(if field == self.tag {
+            TagContentOtherField::Tag
+        } else if field == self.content {
+            TagContentOtherField::Content
+        } else {
+            TagContentOtherField::Other
+        })
+    }
+
+    fn visit_bytes<E>(self, field: &[u8]) -> Result<Self::Value, E> {
+        Ok(if field == self.tag.as_bytes() {
+            TagContentOtherField::Tag
+        } else if field == self.content.as_bytes() {
+            TagContentOtherField::Content
+        } else {
+            TagContentOtherField::Other
+        })
+    }
+}
+
+/// Deserializes a `#[serde(flatten)]` field out of the entries its parent did not recognize.
+///
+/// Structs take the entries named by their fields so later flattened fields don't see them
+/// again; maps see every entry that is left.
+pub struct FlatMapDeserializer<'a, 'de, E> {
+    entries: &'a mut Vec<Option<(Content<'de>, Content<'de>)>>,
+    err: PhantomData<E>,
+}
+
+impl<'a, 'de, E> FlatMapDeserializer<'a, 'de, E>
+where
+    E: de::Error,
+{
+    pub fn new(entries: &'a mut Vec<Option<(Content<'de>, Content<'de>)>>) -> Self {
+        FlatMapDeserializer {
+            entries,
+            err: PhantomData,
+        }
+    }
+
+    fn unsupported(what: &str) -> E {
+        de::Error::custom(format_args!(
+            "can only flatten structs and maps (got {})",
+            what
+        ))
+    }
+}
+
+macro_rules! forward_to_unsupported {
+    ($($method:ident => $what:expr,)*) => {
+        $(
+            fn $method<V>(self, _visitor: V) -> Result<V::Value, E>
+            where
+                V: Visitor<'de>,
+            {
+                Err(Self::unsupported($what))
+            }
+        )*
+    };
+}
+
+impl<'a, 'de, E> Deserializer<'de> for FlatMapDeserializer<'a, 'de, E>
+where
+    E: de::Error,
+{
+    type Error = E;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_map(FlatMapAccess {
+            iter: self.entries.iter(),
+            pending: None,
+            err: PhantomData,
+        })
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_map(FlatStructAccess {
+            iter: self.entries.iter_mut(),
+            pending: None,
+            fields,
+            err: PhantomData,
+        })
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        for entry in self.entries.iter_mut() {
+            if let Some((key, value)) = take_entry(entry, variants) {
+                return visitor.visit_enum(EnumDeserializer {
+                    variant: key,
+                    value: Some(value),
+                    err: PhantomData,
+                });
+            }
+        }
+        Err(de::Error::custom(format_args!(
+            "no variant of enum {} found in flattened data",
+            name
+        )))
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, _visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        Err(Self::unsupported("a tuple"))
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        _visitor: V,
+    ) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        Err(Self::unsupported("a tuple struct"))
+    }
+
+    forward_to_unsupported! {
+        deserialize_bool => "a boolean",
+        deserialize_i8 => "an integer",
+        deserialize_i16 => "an integer",
+        deserialize_i32 => "an integer",
+        deserialize_i64 => "an integer",
+        deserialize_u8 => "an integer",
+        deserialize_u16 => "an integer",
+        deserialize_u32 => "an integer",
+        deserialize_u64 => "an integer",
+        deserialize_f32 => "a float",
+        deserialize_f64 => "a float",
+        deserialize_char => "a char",
+        deserialize_str => "a string",
+        deserialize_string => "a string",
+        deserialize_bytes => "a byte array",
+        deserialize_byte_buf => "a byte array",
+        deserialize_seq => "a sequence",
+        deserialize_identifier => "an identifier",
+    }
+}
+
+/// Takes the entry out of the buffer if its key is one of `recognized`.
+fn take_entry<'de>(
+    entry: &mut Option<(Content<'de>, Content<'de>)>,
+    recognized: &[&str],
+) -> Option<(Content<'de>, Content<'de>)> {
+    let is_recognized = match entry {
+        Some((key, _)) => key.as_str().is_some_and(|name| recognized.contains(&name)),
+        None => false,
+    };
+    if is_recognized {
+        entry.take()
+    } else {
+        None
+    }
+}
+
+struct FlatMapAccess<'a, 'de, E> {
+    iter: core::slice::Iter<'a, Option<(Content<'de>, Content<'de>)>>,
+    pending: Option<&'a Content<'de>>,
+    err: PhantomData<E>,
+}
+
+impl<'a, 'de, E> MapAccess<'de> for FlatMapAccess<'a, 'de, E>
+where
+    E: de::Error,
+{
+    type Error = E;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, E>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        // Entries are left in place: a map does not know which of them a later flattened field
+        // still needs.
+        match self.iter.by_ref().flatten().next() {
+            Some((key, value)) => {
+                self.pending = Some(value);
+                seed.deserialize(ContentDeserializer::new(key.clone()))
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, E>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        match self.pending.take() {
+            Some(value) => seed.deserialize(ContentDeserializer::new(value.clone())),
+            None => Err(de::Error::custom("value is missing")),
+        }
+    }
+}
+
+struct FlatStructAccess<'a, 'de, E> {
+    iter: core::slice::IterMut<'a, Option<(Content<'de>, Content<'de>)>>,
+    pending: Option<Content<'de>>,
+    fields: &'static [&'static str],
+    err: PhantomData<E>,
+}
+
+impl<'a, 'de, E> MapAccess<'de> for FlatStructAccess<'a, 'de, E>
+where
+    E: de::Error,
+{
+    type Error = E;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, E>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        for entry in self.iter.by_ref() {
+            if let Some((key, value)) = take_entry(entry, self.fields) {
+                self.pending = Some(value);
+                return seed.deserialize(ContentDeserializer::new(key)).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, E>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        match self.pending.take() {
+            Some(value) => seed.deserialize(ContentDeserializer::new(value)),
+            None => Err(de::Error::custom("value is missing")),
+        }
+    }
+}
+
+/// Stands in for a field that is missing from the input. `Option` fields read it as `None`;
+/// anything else reports the field as missing.
+pub struct MissingFieldDeserializer<E> {
+    field: &'static str,
+    err: PhantomData<E>,
+}
+
+impl<E> MissingFieldDeserializer<E>
+where
+    E: de::Error,
+{
+    pub fn new(field: &'static str) -> Self {
+        MissingFieldDeserializer {
+            field,
+            err: PhantomData,
+        }
+    }
+}
+
+impl<'de, E> Deserializer<'de> for MissingFieldDeserializer<E>
+where
+    E: de::Error,
+{
+    type Error = E;
+
+    fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        Err(de::Error::missing_field(self.field))
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_none()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct seq tuple tuple_struct
+        map struct enum identifier ignored_any
+    }
+}
@@ -0,0 +1,30 @@
+//! Support code for the derive macros. Not public API.
+
+pub mod de;
+pub mod ser;
+
+pub use serde;
+
+use crate::de::StateSeed;
+use crate::ser::StateRef;
+use crate::SerializeState;
+
+pub type SerializeRef<'state, T, State> = StateRef<'state, T, State>;
+pub type DeserializeStateSeed<'state, T, State> = StateSeed<'state, T, State>;
+
+pub fn wrap_serialize<'state, T, State>(
+    value: &'state T,
+    state: &'state State,
+) -> StateRef<'state, T, State>
+where
+    T: SerializeState<State> + ?Sized,
+    State: ?Sized,
+{
+    StateRef::new(value, state)
+}
+
+pub fn wrap_deserialize_seed<'state, T, State: ?Sized>(
+    state: &'state State,
+) -> StateSeed<'state, T, State> {
+    StateSeed::new(state)
+}
@@ -0,0 +1,206 @@
+//! Helpers for calling [`SerializeState`] from plain serde code.
+
+use serde::ser::{
+    SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
+    SerializeTupleStruct, SerializeTupleVariant,
+};
+use serde::{Serialize, Serializer};
+
+use crate::SerializeState;
+
+/// A borrowed value paired with its state. Its `Serialize` impl calls `T`'s `SerializeState`
+/// impl, which makes it usable anywhere serde expects a `Serialize` value, e.g. as the element
+/// passed to `SerializeSeq::serialize_element`.
+pub struct StateRef<'s, T: ?Sized, State: ?Sized> {
+    value: &'s T,
+    state: &'s State,
+}
+
+impl<'s, T: ?Sized, State: ?Sized> StateRef<'s, T, State> {
+    /// Pairs `value` with the `state` it should be serialized with.
+    pub fn new(value: &'s T, state: &'s State) -> Self {
+        Self { value, state }
+    }
+
+    /// The wrapped value.
+    pub fn value(&self) -> &'s T {
+        self.value
+    }
+
+    /// The state the value is serialized with.
+    pub fn state(&self) -> &'s State {
+        self.state
+    }
+}
+
+impl<T: ?Sized, State: ?Sized> Copy for StateRef<'_, T, State> {}
+impl<T: ?Sized, State: ?Sized> Clone for StateRef<'_, T, State> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T, State> Serialize for StateRef<'_, T, State>
+where
+    T: SerializeState<State> + ?Sized,
+    State: ?Sized,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.value.serialize_state(self.state, serializer)
+    }
+}
+
+/// Stateful counterparts of the `SerializeSeq` methods.
+pub trait SerializeSeqExt: SerializeSeq {
+    /// Serializes a sequence element through its `SerializeState` impl.
+    fn serialize_element_state<T, State>(
+        &mut self,
+        value: &T,
+        state: &State,
+    ) -> Result<(), Self::Error>
+    where
+        T: SerializeState<State> + ?Sized,
+        State: ?Sized,
+    {
+        self.serialize_element(&StateRef::new(value, state))
+    }
+}
+
+impl<S: SerializeSeq + ?Sized> SerializeSeqExt for S {}
+
+/// Stateful counterparts of the `SerializeTuple` methods.
+pub trait SerializeTupleExt: SerializeTuple {
+    /// Serializes a tuple element through its `SerializeState` impl.
+    fn serialize_element_state<T, State>(
+        &mut self,
+        value: &T,
+        state: &State,
+    ) -> Result<(), Self::Error>
+    where
+        T: SerializeState<State> + ?Sized,
+        State: ?Sized,
+    {
+        self.serialize_element(&StateRef::new(value, state))
+    }
+}
+
+impl<S: SerializeTuple + ?Sized> SerializeTupleExt for S {}
+
+/// Stateful counterparts of the `SerializeTupleStruct` methods.
+pub trait SerializeTupleStructExt: SerializeTupleStruct {
+    /// Serializes a tuple struct field through its `SerializeState` impl.
+    fn serialize_field_state<T, State>(
+        &mut self,
+        value: &T,
+        state: &State,
+    ) -> Result<(), Self::Error>
+    where
+        T: SerializeState<State> + ?Sized,
+        State: ?Sized,
+    {
+        self.serialize_field(&StateRef::new(value, state))
+    }
+}
+
+impl<S: SerializeTupleStruct + ?Sized> SerializeTupleStructExt for S {}
+
+/// Stateful counterparts of the `SerializeTupleVariant` methods.
+pub trait SerializeTupleVariantExt: SerializeTupleVariant {
+    /// Serializes a tuple variant field through its `SerializeState` impl.
+    fn serialize_field_state<T, State>(
+        &mut self,
+        value: &T,
+        state: &State,
+    ) -> Result<(), Self::Error>
+    where
+        T: SerializeState<State> + ?Sized,
+        State: ?Sized,
+    {
+        self.serialize_field(&StateRef::new(value, state))
+    }
+}
+
+impl<S: SerializeTupleVariant + ?Sized> SerializeTupleVariantExt for S {}
+
+/// Stateful counterparts of the `SerializeMap` methods.
+pub trait SerializeMapExt: SerializeMap {
+    /// Serializes a map key through its `SerializeState` impl.
+    fn serialize_key_state<K, State>(&mut self, key: &K, state: &State) -> Result<(), Self::Error>
+    where
+        K: SerializeState<State> + ?Sized,
+        State: ?Sized,
+    {
+        self.serialize_key(&StateRef::new(key, state))
+    }
+
+    /// Serializes a map value through its `SerializeState` impl.
+    fn serialize_value_state<V, State>(
+        &mut self,
+        value: &V,
+        state: &State,
+    ) -> Result<(), Self::Error>
+    where
+        V: SerializeState<State> + ?Sized,
+        State: ?Sized,
+    {
+        self.serialize_value(&StateRef::new(value, state))
+    }
+
+    /// Serializes a key and its value, both through their `SerializeState` impls.
+    fn serialize_entry_state<K, V, State>(
+        &mut self,
+        key: &K,
+        value: &V,
+        state: &State,
+    ) -> Result<(), Self::Error>
+    where
+        K: SerializeState<State> + ?Sized,
+        V: SerializeState<State> + ?Sized,
+        State: ?Sized,
+    {
+        self.serialize_entry(&StateRef::new(key, state), &StateRef::new(value, state))
+    }
+}
+
+impl<S: SerializeMap + ?Sized> SerializeMapExt for S {}
+
+/// Stateful counterparts of the `SerializeStruct` methods.
+pub trait SerializeStructExt: SerializeStruct {
+    /// Serializes a struct field through its `SerializeState` impl.
+    fn serialize_field_state<T, State>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+        state: &State,
+    ) -> Result<(), Self::Error>
+    where
+        T: SerializeState<State> + ?Sized,
+        State: ?Sized,
+    {
+        self.serialize_field(key, &StateRef::new(value, state))
+    }
+}
+
+impl<S: SerializeStruct + ?Sized> SerializeStructExt for S {}
+
+/// Stateful counterparts of the `SerializeStructVariant` methods.
+pub trait SerializeStructVariantExt: SerializeStructVariant {
+    /// Serializes a struct variant field through its `SerializeState` impl.
+    fn serialize_field_state<T, State>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+        state: &State,
+    ) -> Result<(), Self::Error>
+    where
+        T: SerializeState<State> + ?Sized,
+        State: ?Sized,
+    {
+        self.serialize_field(key, &StateRef::new(value, state))
+    }
+}
+
+impl<S: SerializeStructVariant + ?Sized> SerializeStructVariantExt for S {}
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use serde_state::{DeserializeState, SerializeState};
+use serde_state::{DeserializeState, SerializeState, StatelessState};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
 use std::thread_local;
 use std::{cell::Cell, marker::PhantomData};
 
@@ -35,9 +37,22 @@ impl RecorderLike for Recorder {
     }
 }
 
+impl serde_state::de::Rollback for Recorder {
+    type Snapshot = (usize, usize);
+
+    fn snapshot(&self) -> (usize, usize) {
+        (self.serialized.get(), self.deserialized.get())
+    }
+
+    fn restore(&self, (serialized, deserialized): (usize, usize)) {
+        self.serialized.set(serialized);
+        self.deserialized.set(deserialized);
+    }
+}
+
 thread_local! {
-    static GLOBAL_SERIALIZED: Cell<usize> = Cell::new(0);
-    static GLOBAL_DESERIALIZED: Cell<usize> = Cell::new(0);
+    static GLOBAL_SERIALIZED: Cell<usize> = const { Cell::new(0) };
+    static GLOBAL_DESERIALIZED: Cell<usize> = const { Cell::new(0) };
 }
 
 #[derive(Clone, Copy, Default)]
@@ -76,7 +91,7 @@ impl RecorderLike for GlobalRecorder {
     }
 }
 
-#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 struct CounterValue(u32);
 
 #[derive(Clone, Debug, PartialEq)]
@@ -800,123 +815,1857 @@ fn recursive_enum_threads_state() {
 }
 
 #[test]
-fn postcard_named_struct_deserializes_from_seq_and_threads_state() {
-    let value = Example {
-        first: CounterValue(31),
-        second: CounterValue(32),
+fn stateless_state_derive_delegates_to_serde() {
+    #[derive(Serialize, Deserialize, StatelessState, Debug, PartialEq)]
+    struct Span<T> {
+        lo: T,
+        hi: T,
+    }
+
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    struct Spanned {
+        span: Span<u32>,
+        counter: CounterValue,
+    }
+
+    let value = Spanned {
+        span: Span { lo: 1, hi: 2 },
+        counter: CounterValue(3),
     };
 
-    let ser_state = Recorder::default();
-    let bytes = postcard::to_allocvec(&serde_state::__private::wrap_serialize(&value, &ser_state))
-        .expect("postcard serialize named struct");
-    assert_eq!(ser_state.serialized.get(), 2);
+    let state = Recorder::default();
+    let mut buffer = Vec::new();
+    {
+        let mut serializer = serde_json::Serializer::new(&mut buffer);
+        value
+            .serialize_state(&state, &mut serializer)
+            .expect("stateless derive serialization");
+    }
+    assert_eq!(state.serialized.get(), 1);
+    let json_value: serde_json::Value = serde_json::from_slice(&buffer).unwrap();
+    assert_eq!(
+        json_value,
+        json!({"span": {"lo": 1, "hi": 2}, "counter": 3})
+    );
 
-    let de_state = Recorder::default();
-    let mut deserializer = postcard::Deserializer::from_bytes(&bytes);
-    let decoded = Example::deserialize_state(&de_state, &mut deserializer)
-        .expect("postcard deserialize named struct");
+    let state = Recorder::default();
+    let mut deserializer = serde_json::Deserializer::from_slice(&buffer);
+    let decoded = Spanned::deserialize_state(&state, &mut deserializer).unwrap();
+    assert_eq!(decoded, value);
+    assert_eq!(state.deserialized.get(), 1);
+}
+
+// `serde_json::Value` comes from another crate, so only a named state can be covered.
+serde_state::impl_stateless_state!(Recorder => serde_json::Value);
+
+#[test]
+fn stateless_state_macro_delegates_to_serde() {
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Plain(u32);
+
+    serde_state::impl_stateless_state!(Plain);
+
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    #[serde_state(state = Recorder)]
+    struct Tagged {
+        plain: Plain,
+        extra: serde_json::Value,
+        counter: CounterValue,
+    }
+
+    assert_json_round_trip(
+        &Tagged {
+            plain: Plain(1),
+            extra: json!({"any": [true]}),
+            counter: CounterValue(2),
+        },
+        json!({"plain": 1, "extra": {"any": [true]}, "counter": 2}),
+        (1, 1),
+    );
+    // The generic form works with any state.
+    assert_eq!(Plain::deserialize_state(&(), json!(3)).unwrap(), Plain(3));
+}
+
+#[test]
+fn public_seed_and_wrapper_types_thread_state() {
+    use serde::de::DeserializeSeed;
+    use serde_state::de::StateSeed;
+    use serde_state::ser::StateRef;
+    use serde_state::WithState;
+
+    let value = Example {
+        first: CounterValue(1),
+        second: CounterValue(2),
+    };
+    let state = Recorder::default();
+    let wrapped = StateRef::new(&value, &state);
+    let copy = wrapped;
     assert_eq!(
-        deserializer.finalize().expect("postcard remainder").len(),
-        0
+        serde_json::to_value(wrapped).unwrap(),
+        json!({"first": 1, "second": 2})
+    );
+    assert_eq!(
+        serde_json::to_value(copy).unwrap(),
+        json!({"first": 1, "second": 2})
     );
+    assert_eq!(state.serialized.get(), 4);
+
+    let state = Recorder::default();
+    let seed = StateSeed::<Example, Recorder>::new(&state);
+    for _ in 0..2 {
+        let mut deserializer = serde_json::Deserializer::from_str(r#"{"first":1,"second":2}"#);
+        assert_eq!(seed.deserialize(&mut deserializer).unwrap(), value);
+    }
+    assert_eq!(state.deserialized.get(), 4);
+
+    let state = Recorder::default();
+    let mut deserializer = serde_json::Deserializer::from_str(r#"{"first":1,"second":2}"#);
+    let decoded = WithState::<PhantomData<Example>, _>::seed(&state)
+        .deserialize(&mut deserializer)
+        .unwrap();
     assert_eq!(decoded, value);
-    assert_eq!(de_state.deserialized.get(), 2);
+    assert_eq!(state.deserialized.get(), 2);
 }
 
 #[test]
-fn postcard_enum_variants_deserialize_from_numeric_tags() {
-    fn round_trip(value: Action, expected_hits: usize) {
-        let ser_state = Recorder::default();
-        let bytes =
-            postcard::to_allocvec(&serde_state::__private::wrap_serialize(&value, &ser_state))
-                .expect("postcard serialize enum");
-        assert_eq!(ser_state.serialized.get(), expected_hits);
+fn internally_tagged_enums_thread_state_through_buffered_content() {
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    struct Reply {
+        counter: CounterValue,
+    }
 
-        let de_state = Recorder::default();
-        let mut deserializer = postcard::Deserializer::from_bytes(&bytes);
-        let decoded = Action::deserialize_state(&de_state, &mut deserializer)
-            .expect("postcard deserialize enum");
-        assert_eq!(
-            deserializer.finalize().expect("postcard remainder").len(),
-            0
-        );
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    #[serde(tag = "type")]
+    enum Message {
+        Call {
+            target: CounterValue,
+            args: Vec<CounterValue>,
+        },
+        Reply(Reply),
+        Ping,
+    }
+
+    fn round_trip(value: Message, expected: serde_json::Value, counters: usize) {
+        let state = Recorder::default();
+        let mut buffer = Vec::new();
+        {
+            let mut serializer = serde_json::Serializer::new(&mut buffer);
+            value
+                .serialize_state(&state, &mut serializer)
+                .expect("tagged serialization");
+        }
+        assert_eq!(state.serialized.get(), counters);
+        let json_value: serde_json::Value = serde_json::from_slice(&buffer).unwrap();
+        assert_eq!(json_value, expected);
+
+        let state = Recorder::default();
+        let mut deserializer = serde_json::Deserializer::from_slice(&buffer);
+        let decoded = Message::deserialize_state(&state, &mut deserializer).unwrap();
         assert_eq!(decoded, value);
-        assert_eq!(de_state.deserialized.get(), expected_hits);
+        assert_eq!(state.deserialized.get(), counters);
     }
 
-    round_trip(Action::Idle, 0);
-    round_trip(Action::Reset(CounterValue(51)), 1);
-    round_trip(Action::Combine(CounterValue(52), CounterValue(53)), 2);
     round_trip(
-        Action::Record {
-            first: CounterValue(54),
-            second: CounterValue(55),
+        Message::Call {
+            target: CounterValue(1),
+            args: vec![CounterValue(2), CounterValue(3)],
         },
-        2,
+        json!({"type": "Call", "target": 1, "args": [2, 3]}),
+        3,
+    );
+    round_trip(
+        Message::Reply(Reply {
+            counter: CounterValue(4),
+        }),
+        json!({"type": "Reply", "counter": 4}),
+        1,
+    );
+    round_trip(Message::Ping, json!({"type": "Ping"}), 0);
+
+    // The tag does not have to come first.
+    let state = Recorder::default();
+    let mut deserializer =
+        serde_json::Deserializer::from_str(r#"{"target": 5, "args": [], "type": "Call"}"#);
+    let decoded = Message::deserialize_state(&state, &mut deserializer).unwrap();
+    assert_eq!(
+        decoded,
+        Message::Call {
+            target: CounterValue(5),
+            args: Vec::new(),
+        }
     );
+    assert_eq!(state.deserialized.get(), 1);
+
+    let state = Recorder::default();
+    let mut deserializer = serde_json::Deserializer::from_str(r#"{"counter": 1}"#);
+    let err = Message::deserialize_state(&state, &mut deserializer).unwrap_err();
+    assert!(err.to_string().contains("missing field `type`"));
 }
 
 #[test]
-fn postcard_serialize_matches_serialize_state_for_auto_serde_container() {
-    let value = AutoSerdeContainer {
-        value: GenericCounterValue(123),
-    };
-    assert_postcard_serde_and_state_equal_with_counts(&value, 1);
+fn adjacently_tagged_enums_thread_state_in_any_key_order() {
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    #[serde(tag = "t", content = "c")]
+    enum Event {
+        Reset,
+        Single(CounterValue),
+        Pair(CounterValue, u32),
+        Named { counter: CounterValue },
+    }
+
+    fn decode(input: &str, expected_counters: usize) -> Event {
+        let state = Recorder::default();
+        let mut deserializer = serde_json::Deserializer::from_str(input);
+        let decoded = Event::deserialize_state(&state, &mut deserializer).unwrap();
+        assert_eq!(state.deserialized.get(), expected_counters);
+        decoded
+    }
+
+    let cases = [
+        (Event::Reset, json!({"t": "Reset"}), 0),
+        (
+            Event::Single(CounterValue(1)),
+            json!({"t": "Single", "c": 1}),
+            1,
+        ),
+        (
+            Event::Pair(CounterValue(2), 3),
+            json!({"t": "Pair", "c": [2, 3]}),
+            1,
+        ),
+        (
+            Event::Named {
+                counter: CounterValue(4),
+            },
+            json!({"t": "Named", "c": {"counter": 4}}),
+            1,
+        ),
+    ];
+    for (value, expected, counters) in cases {
+        let state = Recorder::default();
+        let mut buffer = Vec::new();
+        {
+            let mut serializer = serde_json::Serializer::new(&mut buffer);
+            value
+                .serialize_state(&state, &mut serializer)
+                .expect("adjacent serialization");
+        }
+        assert_eq!(state.serialized.get(), counters);
+        let json_value: serde_json::Value = serde_json::from_slice(&buffer).unwrap();
+        assert_eq!(json_value, expected);
+        assert_eq!(
+            decode(std::str::from_utf8(&buffer).unwrap(), counters),
+            value
+        );
+
+        // As with serde_derive, a unit variant is written without its content, which a
+        // sequence can't leave out.
+        if value == Event::Reset {
+            continue;
+        }
+        let ser_state = Recorder::default();
+        let bytes = postcard::to_allocvec(&serde_state::ser::StateRef::new(&value, &ser_state))
+            .expect("postcard serialize adjacent enum");
+        let de_state = Recorder::default();
+        let mut deserializer = postcard::Deserializer::from_bytes(&bytes);
+        let decoded = Event::deserialize_state(&de_state, &mut deserializer)
+            .expect("postcard deserialize adjacent enum");
+        assert_eq!(decoded, value);
+        assert_eq!(de_state.deserialized.get(), counters);
+    }
+
+    // Content before the tag is buffered and still receives the state.
+    assert_eq!(
+        decode(r#"{"c": {"counter": 5}, "extra": true, "t": "Named"}"#, 1),
+        Event::Named {
+            counter: CounterValue(5)
+        }
+    );
+    assert_eq!(decode(r#"{"t": "Reset", "c": null}"#, 0), Event::Reset);
+
+    let state = Recorder::default();
+    let mut deserializer = serde_json::Deserializer::from_str(r#"{"t": "Single"}"#);
+    let err = Event::deserialize_state(&state, &mut deserializer).unwrap_err();
+    assert!(err.to_string().contains("missing field `c`"));
+
+    // In sequence form the content is required, even for unit variants.
+    assert_eq!(decode(r#"["Reset", null]"#, 0), Event::Reset);
+    assert_eq!(
+        decode(r#"["Single", 6]"#, 1),
+        Event::Single(CounterValue(6))
+    );
+    for input in [r#"["Reset"]"#, r#"["Single"]"#] {
+        let state = Recorder::default();
+        let mut deserializer = serde_json::Deserializer::from_str(input);
+        let err = Event::deserialize_state(&state, &mut deserializer).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("invalid length 1, expected adjacently tagged enum Event"),
+            "{err}"
+        );
+    }
 }
 
 #[test]
-fn postcard_serialize_matches_serialize_state_for_vec_of_auto_serde_container() {
-    let values = vec![
-        AutoSerdeContainer {
-            value: GenericCounterValue(7),
+fn untagged_enums_roll_back_state_between_attempts() {
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    #[serde(untagged)]
+    enum Entry {
+        Pair(CounterValue, u32),
+        Counted {
+            first: CounterValue,
+            second: CounterValue,
         },
-        AutoSerdeContainer {
-            value: GenericCounterValue(8),
+        Labelled {
+            first: CounterValue,
+            second: String,
         },
+        Single(CounterValue),
+    }
+
+    fn decode<T>(input: &str) -> (Result<T, serde_json::Error>, usize)
+    where
+        T: for<'de> DeserializeState<'de, Recorder>,
+    {
+        let state = Recorder::default();
+        let mut deserializer = serde_json::Deserializer::from_str(input);
+        let decoded = T::deserialize_state(&state, &mut deserializer);
+        (decoded, state.deserialized.get())
+    }
+
+    let cases = [
+        (
+            Entry::Counted {
+                first: CounterValue(1),
+                second: CounterValue(2),
+            },
+            json!({"first": 1, "second": 2}),
+            2,
+        ),
+        (
+            Entry::Labelled {
+                first: CounterValue(3),
+                second: "x".to_owned(),
+            },
+            json!({"first": 3, "second": "x"}),
+            1,
+        ),
+        (Entry::Pair(CounterValue(4), 5), json!([4, 5]), 1),
+        (Entry::Single(CounterValue(6)), json!(6), 1),
     ];
+    for (value, expected, counters) in cases {
+        let state = Recorder::default();
+        let json_value = serde_json::to_value(serde_state::ser::StateRef::new(&value, &state))
+            .expect("untagged serialization");
+        assert_eq!(json_value, expected);
+        assert_eq!(state.serialized.get(), counters);
+
+        // Attempts that fail after touching the state must not leave their counts behind.
+        let (decoded, deserialized) = decode::<Entry>(&expected.to_string());
+        assert_eq!(decoded.unwrap(), value);
+        assert_eq!(deserialized, counters);
+    }
 
-    assert_postcard_serde_and_state_equal_with_counts(&values, 2);
+    let (decoded, deserialized) = decode::<Entry>(r#"{"first": "x"}"#);
+    assert!(decoded
+        .unwrap_err()
+        .to_string()
+        .contains("data did not match any variant of untagged enum Entry"));
+    assert_eq!(deserialized, 0);
+
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    enum Value {
+        Counter(CounterValue),
+        #[serde(untagged)]
+        Raw(CounterValue),
+    }
+
+    let state = Recorder::default();
+    let json_value = serde_json::to_value(serde_state::ser::StateRef::new(
+        &Value::Raw(CounterValue(7)),
+        &state,
+    ))
+    .unwrap();
+    assert_eq!(json_value, json!(7));
+    let (decoded, deserialized) = decode::<Value>(r#"{"Counter": 8}"#);
+    assert_eq!(decoded.unwrap(), Value::Counter(CounterValue(8)));
+    assert_eq!(deserialized, 1);
+    let (decoded, deserialized) = decode::<Value>("9");
+    assert_eq!(decoded.unwrap(), Value::Raw(CounterValue(9)));
+    assert_eq!(deserialized, 1);
+    let (decoded, deserialized) = decode::<Value>(r#"{"Counter": "x"}"#);
+    assert!(decoded.is_err());
+    assert_eq!(deserialized, 0);
 }
 
 #[test]
-fn postcard_serialize_matches_serialize_state_for_multi_field_struct() {
-    let value = Example {
-        first: CounterValue(31),
-        second: CounterValue(32),
-    };
+fn untagged_struct_variants_only_accept_maps() {
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    #[serde(untagged)]
+    enum Shape {
+        Named { x: CounterValue, y: CounterValue },
+        Positional(CounterValue, CounterValue),
+    }
 
-    assert_postcard_serde_and_state_equal_with_counts(&value, 2);
+    assert_json_round_trip(
+        &Shape::Positional(CounterValue(1), CounterValue(2)),
+        json!([1, 2]),
+        (2, 2),
+    );
+    assert_json_round_trip(
+        &Shape::Named {
+            x: CounterValue(3),
+            y: CounterValue(4),
+        },
+        json!({"x": 3, "y": 4}),
+        (2, 2),
+    );
 }
 
-fn assert_postcard_serde_and_state_equal_with_counts<T>(value: &T, expected_hits: usize)
-where
-    T: Serialize
-        + for<'de> Deserialize<'de>
-        + SerializeState<Recorder>
-        + for<'de> DeserializeState<'de, Recorder>
-        + PartialEq
-        + std::fmt::Debug,
-{
-    let serde_bytes = postcard::to_allocvec(value).expect("postcard serde serialize");
+#[test]
+fn untagged_unit_variants_accept_unit_and_none() {
+    // Hands out a none, which serde_json never produces on its own.
+    struct NoneDeserializer;
 
-    let ser_state = Recorder::default();
-    let state_bytes =
-        postcard::to_allocvec(&serde_state::__private::wrap_serialize(value, &ser_state))
-            .expect("postcard state serialize");
-    assert_eq!(ser_state.serialized_count(), expected_hits);
-    assert_eq!(serde_bytes, state_bytes);
+    impl<'de> serde::Deserializer<'de> for NoneDeserializer {
+        type Error = serde::de::value::Error;
 
-    let serde_decoded: T = postcard::from_bytes(&state_bytes).expect("postcard serde deserialize");
-    assert_eq!(&serde_decoded, value);
+        fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: serde::de::Visitor<'de>,
+        {
+            visitor.visit_none()
+        }
 
-    let de_state = Recorder::default();
-    let mut deserializer = postcard::Deserializer::from_bytes(&serde_bytes);
-    let state_decoded = T::deserialize_state(&de_state, &mut deserializer)
-        .expect("postcard state deserialize");
-    assert_eq!(
-        deserializer.finalize().expect("postcard remainder").len(),
-        0
-    );
-    assert_eq!(de_state.deserialized_count(), expected_hits);
-    assert_eq!(&state_decoded, value);
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map struct enum identifier ignored_any
+        }
+    }
+
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    #[serde(untagged)]
+    enum Slot {
+        Filled(CounterValue),
+        Empty,
+    }
+
+    assert_json_round_trip(&Slot::Empty, json!(null), (0, 0));
+    let state = Recorder::default();
+    let decoded = Slot::deserialize_state(&state, NoneDeserializer).unwrap();
+    assert_eq!(decoded, Slot::Empty);
+}
+
+#[test]
+fn untagged_enums_only_roll_back_when_they_can() {
+    // Has no `Rollback` impl.
+    struct Config;
+
+    #[derive(DeserializeState, Debug, PartialEq)]
+    #[serde(untagged)]
+    #[serde_state(state = Config, no_rollback)]
+    enum Explicit {
+        Pair(u32, String),
+        Number(u32),
+    }
+
+    #[derive(DeserializeState, Debug, PartialEq)]
+    #[serde(untagged)]
+    #[serde_state(stateless)]
+    enum Stateless {
+        Pair(u32, String),
+        Number(u32),
+    }
+
+    let decoded = Explicit::deserialize_state(&Config, json!([1, "a"])).unwrap();
+    assert_eq!(decoded, Explicit::Pair(1, "a".to_owned()));
+    let decoded = Explicit::deserialize_state(&Config, json!(2)).unwrap();
+    assert_eq!(decoded, Explicit::Number(2));
+    let decoded = Stateless::deserialize_state(&Config, json!(3)).unwrap();
+    assert_eq!(decoded, Stateless::Number(3));
+}
+
+#[test]
+fn flattened_fields_receive_state() {
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    struct Metadata {
+        owner: CounterValue,
+        revision: u32,
+    }
+
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    struct Record {
+        id: CounterValue,
+        #[serde(flatten)]
+        metadata: Metadata,
+        #[serde(flatten)]
+        extra: HashMap<String, CounterValue>,
+    }
+
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    enum Change {
+        Edit {
+            #[serde(flatten)]
+            metadata: Metadata,
+            line: u32,
+        },
+    }
+
+    let record = Record {
+        id: CounterValue(1),
+        metadata: Metadata {
+            owner: CounterValue(2),
+            revision: 3,
+        },
+        extra: HashMap::from([("score".to_owned(), CounterValue(4))]),
+    };
+    let expected = json!({"id": 1, "owner": 2, "revision": 3, "score": 4});
+
+    let state = Recorder::default();
+    let json_value = serde_json::to_value(serde_state::ser::StateRef::new(&record, &state))
+        .expect("flattened serialization");
+    assert_eq!(json_value, expected);
+    assert_eq!(state.serialized.get(), 3);
+
+    let state = Recorder::default();
+    let mut deserializer =
+        serde_json::Deserializer::from_str(r#"{"score": 4, "owner": 2, "id": 1, "revision": 3}"#);
+    let decoded = Record::deserialize_state(&state, &mut deserializer).unwrap();
+    assert_eq!(decoded, record);
+    assert_eq!(state.deserialized.get(), 3);
+
+    let state = Recorder::default();
+    let mut deserializer = serde_json::Deserializer::from_str(r#"{"id": 1, "revision": 3}"#);
+    let err = Record::deserialize_state(&state, &mut deserializer).unwrap_err();
+    assert!(err.to_string().contains("missing field `owner`"));
+
+    let change = Change::Edit {
+        metadata: Metadata {
+            owner: CounterValue(5),
+            revision: 6,
+        },
+        line: 7,
+    };
+    let state = Recorder::default();
+    let json_value = serde_json::to_value(serde_state::ser::StateRef::new(&change, &state))
+        .expect("flattened variant serialization");
+    assert_eq!(
+        json_value,
+        json!({"Edit": {"owner": 5, "revision": 6, "line": 7}})
+    );
+    assert_eq!(state.serialized.get(), 1);
+
+    let state = Recorder::default();
+    let input = json_value.to_string();
+    let mut deserializer = serde_json::Deserializer::from_str(&input);
+    let decoded = Change::deserialize_state(&state, &mut deserializer).unwrap();
+    assert_eq!(decoded, change);
+    assert_eq!(state.deserialized.get(), 1);
+}
+
+#[test]
+fn absent_flattened_option_becomes_none() {
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    struct Metadata {
+        owner: CounterValue,
+        revision: u32,
+    }
+
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    struct Record {
+        id: CounterValue,
+        #[serde(flatten)]
+        metadata: Option<Metadata>,
+    }
+
+    assert_json_round_trip(
+        &Record {
+            id: CounterValue(1),
+            metadata: None,
+        },
+        json!({"id": 1}),
+        (1, 1),
+    );
+    assert_json_round_trip(
+        &Record {
+            id: CounterValue(2),
+            metadata: Some(Metadata {
+                owner: CounterValue(3),
+                revision: 4,
+            }),
+        },
+        json!({"id": 2, "owner": 3, "revision": 4}),
+        (2, 2),
+    );
+}
+
+#[test]
+fn unbuildable_flattened_option_leaves_its_entries() {
+    #[derive(DeserializeState, Debug, PartialEq)]
+    struct Metadata {
+        owner: CounterValue,
+        revision: u32,
+    }
+
+    #[derive(DeserializeState, Debug, PartialEq)]
+    struct Record {
+        id: CounterValue,
+        #[serde(flatten)]
+        metadata: Option<Metadata>,
+        #[serde(flatten)]
+        extra: HashMap<String, u32>,
+    }
+
+    // Unrelated keys don't stop the option from being `None`, and the failed attempt at
+    // `Metadata` is rolled back and leaves `owner` to the map.
+    let state = Recorder::default();
+    let decoded =
+        Record::deserialize_state(&state, json!({"id": 1, "owner": 2, "extra": 5})).unwrap();
+    assert_eq!(
+        decoded,
+        Record {
+            id: CounterValue(1),
+            metadata: None,
+            extra: HashMap::from([("owner".to_owned(), 2), ("extra".to_owned(), 5)]),
+        }
+    );
+    assert_eq!(state.deserialized.get(), 1);
+
+    // A built option takes its entries, and errors after it are still reported.
+    let state = Recorder::default();
+    let decoded = Record::deserialize_state(
+        &state,
+        json!({"id": 1, "owner": 2, "revision": 3, "extra": 5}),
+    )
+    .unwrap();
+    assert_eq!(
+        decoded,
+        Record {
+            id: CounterValue(1),
+            metadata: Some(Metadata {
+                owner: CounterValue(2),
+                revision: 3,
+            }),
+            extra: HashMap::from([("extra".to_owned(), 5)]),
+        }
+    );
+    assert_eq!(state.deserialized.get(), 2);
+    let err = Record::deserialize_state(&state, json!({"id": 1, "extra": "x"})).unwrap_err();
+    assert!(err.to_string().contains("invalid type: string \"x\""));
+}
+
+#[test]
+fn missing_fields_fall_back_to_defaults() {
+    fn default_port() -> u32 {
+        8080
+    }
+
+    fn owner_from_state(state: &Recorder) -> CounterValue {
+        CounterValue(100 + state.deserialized.get() as u32)
+    }
+
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    #[serde_state(state = Recorder)]
+    struct Config {
+        name: String,
+        #[serde(default)]
+        retries: u32,
+        #[serde(default = "default_port")]
+        port: u32,
+        #[serde_state(default_with_state = "owner_from_state")]
+        owner: CounterValue,
+    }
+
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    #[serde(default)]
+    struct Limits {
+        lower: CounterValue,
+        upper: CounterValue,
+    }
+
+    impl Default for Limits {
+        fn default() -> Self {
+            Limits {
+                lower: CounterValue(1),
+                upper: CounterValue(10),
+            }
+        }
+    }
+
+    let state = Recorder::default();
+    let mut deserializer = serde_json::Deserializer::from_str(r#"{"name": "main"}"#);
+    let decoded = Config::deserialize_state(&state, &mut deserializer).unwrap();
+    assert_eq!(
+        decoded,
+        Config {
+            name: "main".to_owned(),
+            retries: 0,
+            port: 8080,
+            owner: CounterValue(100),
+        }
+    );
+
+    let state = Recorder::default();
+    let mut deserializer =
+        serde_json::Deserializer::from_str(r#"{"name": "main", "port": 1, "owner": 2}"#);
+    let decoded = Config::deserialize_state(&state, &mut deserializer).unwrap();
+    assert_eq!(decoded.port, 1);
+    assert_eq!(decoded.owner, CounterValue(2));
+    assert_eq!(state.deserialized.get(), 1);
+
+    let state = Recorder::default();
+    let mut deserializer = serde_json::Deserializer::from_str(r#"{"port": 1}"#);
+    let err = Config::deserialize_state(&state, &mut deserializer).unwrap_err();
+    assert!(err.to_string().contains("missing field `name`"));
+
+    let state = Recorder::default();
+    let mut deserializer = serde_json::Deserializer::from_str(r#"{"upper": 20}"#);
+    let decoded = Limits::deserialize_state(&state, &mut deserializer).unwrap();
+    assert_eq!(
+        decoded,
+        Limits {
+            lower: CounterValue(1),
+            upper: CounterValue(20),
+        }
+    );
+    assert_eq!(state.deserialized.get(), 1);
+
+    // Trailing elements of a sequence may be left out as well.
+    let state = Recorder::default();
+    let mut deserializer = serde_json::Deserializer::from_str("[5]");
+    let decoded = Limits::deserialize_state(&state, &mut deserializer).unwrap();
+    assert_eq!(decoded.lower, CounterValue(5));
+    assert_eq!(decoded.upper, CounterValue(10));
+}
+
+#[test]
+fn missing_option_fields_become_none() {
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    struct Profile {
+        id: CounterValue,
+        nickname: Option<String>,
+        manager: Option<CounterValue>,
+        #[serde_state(stateless)]
+        score: Option<PlainValue>,
+    }
+
+    let state = Recorder::default();
+    let mut deserializer = serde_json::Deserializer::from_str(r#"{"id": 1}"#);
+    let decoded = Profile::deserialize_state(&state, &mut deserializer).unwrap();
+    assert_eq!(
+        decoded,
+        Profile {
+            id: CounterValue(1),
+            nickname: None,
+            manager: None,
+            score: None,
+        }
+    );
+    assert_eq!(state.deserialized.get(), 1);
+
+    let state = Recorder::default();
+    let mut deserializer = serde_json::Deserializer::from_str(
+        r#"{"id": 1, "nickname": null, "manager": 2, "score": 3}"#,
+    );
+    let decoded = Profile::deserialize_state(&state, &mut deserializer).unwrap();
+    assert_eq!(decoded.nickname, None);
+    assert_eq!(decoded.manager, Some(CounterValue(2)));
+    assert_eq!(decoded.score, Some(PlainValue(3)));
+    assert_eq!(state.deserialized.get(), 2);
+
+    let state = Recorder::default();
+    let mut deserializer = serde_json::Deserializer::from_str(r#"{"manager": 2}"#);
+    let err = Profile::deserialize_state(&state, &mut deserializer).unwrap_err();
+    assert!(err.to_string().contains("missing field `id`"));
+}
+
+#[test]
+fn rename_rules_apply_to_fields_and_variants() {
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    #[serde(rename_all = "camelCase")]
+    struct Settings {
+        first_counter: CounterValue,
+        #[serde(rename = "Second")]
+        second_counter: CounterValue,
+        #[serde(rename(serialize = "out", deserialize = "in"))]
+        plain_value: u32,
+    }
+
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    #[serde(rename_all = "SCREAMING-KEBAB-CASE", rename_all_fields = "PascalCase")]
+    enum Command {
+        StartNow {
+            counter_value: CounterValue,
+        },
+        #[serde(rename = "halt")]
+        StopLater(CounterValue),
+    }
+
+    let value = Settings {
+        first_counter: CounterValue(1),
+        second_counter: CounterValue(2),
+        plain_value: 3,
+    };
+    let state = Recorder::default();
+    let json_value = serde_json::to_value(serde_state::ser::StateRef::new(&value, &state)).unwrap();
+    assert_eq!(
+        json_value,
+        json!({"firstCounter": 1, "Second": 2, "out": 3})
+    );
+    assert_eq!(state.serialized.get(), 2);
+
+    let state = Recorder::default();
+    let mut deserializer =
+        serde_json::Deserializer::from_str(r#"{"firstCounter": 1, "Second": 2, "in": 3}"#);
+    let decoded = Settings::deserialize_state(&state, &mut deserializer).unwrap();
+    assert_eq!(decoded, value);
+    assert_eq!(state.deserialized.get(), 2);
+
+    let cases = [
+        (
+            Command::StartNow {
+                counter_value: CounterValue(4),
+            },
+            json!({"START-NOW": {"CounterValue": 4}}),
+        ),
+        (Command::StopLater(CounterValue(5)), json!({"halt": 5})),
+    ];
+    for (value, expected) in cases {
+        let state = Recorder::default();
+        let json_value =
+            serde_json::to_value(serde_state::ser::StateRef::new(&value, &state)).unwrap();
+        assert_eq!(json_value, expected);
+        let state = Recorder::default();
+        let input = json_value.to_string();
+        let mut deserializer = serde_json::Deserializer::from_str(&input);
+        let decoded = Command::deserialize_state(&state, &mut deserializer).unwrap();
+        assert_eq!(decoded, value);
+        assert_eq!(state.deserialized.get(), 1);
+    }
+
+    let state = Recorder::default();
+    let mut deserializer = serde_json::Deserializer::from_str(r#"{"StartNow": {}}"#);
+    let err = Command::deserialize_state(&state, &mut deserializer).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("unknown variant `StartNow`, expected `START-NOW` or `halt`"));
+}
+
+#[test]
+fn aliases_are_accepted_when_deserializing() {
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    struct Renamed {
+        #[serde(alias = "old_counter", alias = "legacy")]
+        counter: CounterValue,
+        #[serde(rename = "total", alias = "sum")]
+        amount: u32,
+    }
+
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    #[serde(tag = "kind")]
+    enum Shape {
+        #[serde(alias = "Box")]
+        Square { side: CounterValue },
+        #[serde(rename = "circle", alias = "Round")]
+        Circle {
+            #[serde(alias = "r")]
+            radius: u32,
+        },
+    }
+
+    for input in [
+        r#"{"counter": 1, "total": 2}"#,
+        r#"{"old_counter": 1, "sum": 2}"#,
+        r#"{"legacy": 1, "total": 2}"#,
+    ] {
+        let state = Recorder::default();
+        let mut deserializer = serde_json::Deserializer::from_str(input);
+        let decoded = Renamed::deserialize_state(&state, &mut deserializer).unwrap();
+        assert_eq!(
+            decoded,
+            Renamed {
+                counter: CounterValue(1),
+                amount: 2,
+            }
+        );
+        assert_eq!(state.deserialized.get(), 1);
+    }
+
+    let state = Recorder::default();
+    let mut deserializer =
+        serde_json::Deserializer::from_str(r#"{"counter": 1, "legacy": 2, "total": 3}"#);
+    let err = Renamed::deserialize_state(&state, &mut deserializer).unwrap_err();
+    assert!(err.to_string().contains("duplicate field `counter`"));
+
+    // A flattened struct claims its aliased keys before a flattened map sees the rest.
+    #[derive(DeserializeState, Debug, PartialEq)]
+    struct Outer {
+        #[serde(flatten)]
+        inner: Renamed,
+        #[serde(flatten)]
+        rest: BTreeMap<String, u32>,
+    }
+    let state = Recorder::default();
+    let mut deserializer =
+        serde_json::Deserializer::from_str(r#"{"legacy": 1, "sum": 2, "other": 3}"#);
+    let decoded = Outer::deserialize_state(&state, &mut deserializer).unwrap();
+    assert_eq!(decoded.inner.amount, 2);
+    assert_eq!(decoded.rest, BTreeMap::from([("other".to_owned(), 3)]));
+
+    // Serialization keeps using the main names.
+    let state = Recorder::default();
+    let value = Shape::Circle { radius: 3 };
+    let json_value = serde_json::to_value(serde_state::ser::StateRef::new(&value, &state)).unwrap();
+    assert_eq!(json_value, json!({"kind": "circle", "radius": 3}));
+
+    for (input, expected) in [
+        (
+            r#"{"kind": "Box", "side": 4}"#,
+            Shape::Square {
+                side: CounterValue(4),
+            },
+        ),
+        (r#"{"kind": "Round", "r": 5}"#, Shape::Circle { radius: 5 }),
+        (
+            r#"{"kind": "circle", "radius": 6}"#,
+            Shape::Circle { radius: 6 },
+        ),
+    ] {
+        let state = Recorder::default();
+        let mut deserializer = serde_json::Deserializer::from_str(input);
+        let decoded = Shape::deserialize_state(&state, &mut deserializer).unwrap();
+        assert_eq!(decoded, expected);
+    }
+}
+
+#[test]
+fn deny_unknown_fields_rejects_unexpected_keys() {
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    #[serde(deny_unknown_fields)]
+    struct Strict {
+        counter: CounterValue,
+        #[serde(alias = "amount")]
+        total: u32,
+    }
+
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    #[serde(tag = "kind", deny_unknown_fields)]
+    enum StrictEvent {
+        Named { counter: CounterValue },
+    }
+
+    #[derive(DeserializeState, Debug, PartialEq)]
+    #[serde(deny_unknown_fields)]
+    struct StrictOuter {
+        id: u32,
+        #[serde(flatten)]
+        inner: Inner,
+    }
+
+    #[derive(DeserializeState, Debug, PartialEq)]
+    struct Inner {
+        counter: CounterValue,
+    }
+
+    fn error<T>(input: &str) -> String
+    where
+        T: for<'de> DeserializeState<'de, Recorder> + std::fmt::Debug,
+    {
+        let state = Recorder::default();
+        let mut deserializer = serde_json::Deserializer::from_str(input);
+        T::deserialize_state(&state, &mut deserializer)
+            .unwrap_err()
+            .to_string()
+    }
+
+    let state = Recorder::default();
+    let mut deserializer = serde_json::Deserializer::from_str(r#"{"counter": 1, "amount": 2}"#);
+    let decoded = Strict::deserialize_state(&state, &mut deserializer).unwrap();
+    assert_eq!(
+        decoded,
+        Strict {
+            counter: CounterValue(1),
+            total: 2,
+        }
+    );
+
+    assert!(error::<Strict>(r#"{"counter": 1, "total": 2, "typo": 3}"#)
+        .contains("unknown field `typo`, expected one of `counter`, `amount`, `total`"));
+    assert!(
+        error::<StrictEvent>(r#"{"kind": "Named", "counter": 1, "typo": 2}"#)
+            .contains("unknown field `typo`, expected `counter`")
+    );
+    assert!(
+        error::<StrictOuter>(r#"{"id": 1, "counter": 2, "typo": 3}"#)
+            .contains("unknown field `typo`, expected `id`")
+    );
+
+    let state = Recorder::default();
+    let mut deserializer = serde_json::Deserializer::from_str(r#"{"kind": "Named", "counter": 1}"#);
+    let decoded = StrictEvent::deserialize_state(&state, &mut deserializer).unwrap();
+    assert_eq!(
+        decoded,
+        StrictEvent::Named {
+            counter: CounterValue(1)
+        }
+    );
+    let state = Recorder::default();
+    let mut deserializer = serde_json::Deserializer::from_str(r#"{"id": 1, "counter": 2}"#);
+    let decoded = StrictOuter::deserialize_state(&state, &mut deserializer).unwrap();
+    assert_eq!(decoded.inner.counter, CounterValue(2));
+}
+
+#[test]
+fn skipped_tuple_fields_leave_out_their_element() {
+    // Neither serializable nor deserializable, so it can only appear in skipped fields.
+    #[derive(Default, Debug, PartialEq)]
+    struct Cache(u32);
+
+    fn seven() -> u32 {
+        7
+    }
+
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    struct Entry(CounterValue, #[serde(skip)] Cache, u32);
+
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    enum Shape {
+        Pair(
+            CounterValue,
+            #[serde(skip_serializing)] u32,
+            #[serde(skip_deserializing, default = "seven")] u32,
+        ),
+    }
+
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    #[serde(untagged)]
+    enum Loose {
+        Pair(CounterValue, #[serde(skip)] Cache, u32),
+    }
+
+    assert_json_round_trip(
+        &Entry(CounterValue(1), Cache::default(), 2),
+        json!([1, 2]),
+        (1, 1),
+    );
+    assert_json_round_trip(
+        &Loose::Pair(CounterValue(1), Cache::default(), 2),
+        json!([1, 2]),
+        (1, 1),
+    );
+
+    let state = Recorder::default();
+    let value = Shape::Pair(CounterValue(1), 2, 3);
+    let json_value = serde_json::to_value(serde_state::ser::StateRef::new(&value, &state)).unwrap();
+    assert_eq!(json_value, json!({"Pair": [1, 3]}));
+    let decoded = Shape::deserialize_state(&state, json!({"Pair": [1, 2]})).unwrap();
+    assert_eq!(decoded, Shape::Pair(CounterValue(1), 2, 7));
+    let err = Shape::deserialize_state(&state, json!({"Pair": [1]})).unwrap_err();
+    assert!(err.to_string().contains("invalid length 1"));
+
+    // Non-self-describing formats see the shorter length too.
+    let state = Recorder::default();
+    let value = Entry(CounterValue(4), Cache(5), 6);
+    let bytes = postcard::to_allocvec(&serde_state::ser::StateRef::new(&value, &state)).unwrap();
+    let mut deserializer = postcard::Deserializer::from_bytes(&bytes);
+    let decoded = Entry::deserialize_state(&state, &mut deserializer).unwrap();
+    assert_eq!(decoded, Entry(CounterValue(4), Cache::default(), 6));
+}
+
+#[test]
+fn conditional_and_one_directional_skips() {
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    struct Compact {
+        counter: CounterValue,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        extra: Option<CounterValue>,
+        #[serde(skip_serializing_if = "Vec::is_empty", default)]
+        tags: Vec<String>,
+        #[serde(skip_serializing)]
+        input_only: u32,
+        #[serde(skip_deserializing)]
+        output_only: u32,
+    }
+
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    #[serde(tag = "kind")]
+    enum Event {
+        Named {
+            #[serde(skip_serializing_if = "Option::is_none")]
+            counter: Option<CounterValue>,
+        },
+    }
+
+    fn to_json<T: SerializeState<Recorder>>(
+        value: &T,
+        expected_counters: usize,
+    ) -> serde_json::Value {
+        let state = Recorder::default();
+        let json_value =
+            serde_json::to_value(serde_state::ser::StateRef::new(value, &state)).unwrap();
+        assert_eq!(state.serialized.get(), expected_counters);
+        json_value
+    }
+
+    let value = Compact {
+        counter: CounterValue(1),
+        extra: None,
+        tags: Vec::new(),
+        input_only: 2,
+        output_only: 3,
+    };
+    assert_eq!(to_json(&value, 1), json!({"counter": 1, "output_only": 3}));
+
+    let value = Compact {
+        extra: Some(CounterValue(4)),
+        tags: vec!["a".to_owned()],
+        ..value
+    };
+    assert_eq!(
+        to_json(&value, 2),
+        json!({"counter": 1, "extra": 4, "tags": ["a"], "output_only": 3})
+    );
+
+    let state = Recorder::default();
+    let mut deserializer =
+        serde_json::Deserializer::from_str(r#"{"counter": 1, "input_only": 5, "output_only": 6}"#);
+    let decoded = Compact::deserialize_state(&state, &mut deserializer).unwrap();
+    assert_eq!(
+        decoded,
+        Compact {
+            counter: CounterValue(1),
+            extra: None,
+            tags: Vec::new(),
+            input_only: 5,
+            output_only: 0,
+        }
+    );
+    assert_eq!(state.deserialized.get(), 1);
+
+    assert_eq!(
+        to_json(&Event::Named { counter: None }, 0),
+        json!({"kind": "Named"})
+    );
+    assert_eq!(
+        to_json(
+            &Event::Named {
+                counter: Some(CounterValue(7))
+            },
+            1
+        ),
+        json!({"kind": "Named", "counter": 7})
+    );
+}
+
+#[test]
+fn split_and_stateless_with_functions() {
+    // Implements neither the serde nor the stateful traits.
+    #[derive(Debug, PartialEq)]
+    struct Opaque(u32);
+
+    fn write_opaque<S: serde::Serializer>(
+        value: &Opaque,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(value.0)
+    }
+
+    fn read_opaque<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Opaque, D::Error> {
+        u32::deserialize(deserializer).map(Opaque)
+    }
+
+    fn write_doubled<S: serde::Serializer>(
+        value: &CounterValue,
+        state: &Recorder,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        state.serialized.set(state.serialized.get() + 10);
+        serializer.serialize_u32(value.0 * 2)
+    }
+
+    fn read_halved<'de, D: serde::Deserializer<'de>>(
+        state: &Recorder,
+        deserializer: D,
+    ) -> Result<CounterValue, D::Error> {
+        state.deserialized.set(state.deserialized.get() + 10);
+        Ok(CounterValue(u32::deserialize(deserializer)? / 2))
+    }
+
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    #[serde_state(state = Recorder)]
+    struct Overrides {
+        #[serde(serialize_with = "write_opaque", deserialize_with = "read_opaque")]
+        opaque: Opaque,
+        #[serde_state(serialize_state_with = "write_doubled")]
+        written: CounterValue,
+        #[serde_state(deserialize_state_with = "read_halved")]
+        read: CounterValue,
+    }
+
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    enum Wrapped {
+        Plain(#[serde(serialize_with = "write_opaque", deserialize_with = "read_opaque")] Opaque),
+    }
+
+    let value = Overrides {
+        opaque: Opaque(1),
+        written: CounterValue(2),
+        read: CounterValue(3),
+    };
+    let state = Recorder::default();
+    let json_value = serde_json::to_value(serde_state::ser::StateRef::new(&value, &state)).unwrap();
+    assert_eq!(json_value, json!({"opaque": 1, "written": 4, "read": 3}));
+    // `written` goes through the function, `read` through `CounterValue`'s impl.
+    assert_eq!(state.serialized.get(), 11);
+
+    let state = Recorder::default();
+    let mut deserializer =
+        serde_json::Deserializer::from_str(r#"{"opaque": 1, "written": 2, "read": 6}"#);
+    let decoded = Overrides::deserialize_state(&state, &mut deserializer).unwrap();
+    assert_eq!(decoded, value);
+    assert_eq!(state.deserialized.get(), 11);
+
+    assert_json_round_trip(&Wrapped::Plain(Opaque(5)), json!({"Plain": 5}), (0, 0));
+
+    // Errors from the functions reach the caller unchanged.
+    let state = Recorder::default();
+    let mut deserializer = serde_json::Deserializer::from_str(r#"{"Plain": "five"}"#);
+    let error = Wrapped::deserialize_state(&state, &mut deserializer).unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .starts_with("invalid type: string \"five\", expected u32"),
+        "{error}"
+    );
+}
+
+#[test]
+fn variant_with_functions() {
+    // Written as a single "x,y" string.
+    fn write_point<S: serde::Serializer>(
+        x: &u32,
+        y: &u32,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!("{x},{y}"))
+    }
+
+    fn read_point<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<(u32, u32), D::Error> {
+        let text = String::deserialize(deserializer)?;
+        let (x, y) = text
+            .split_once(',')
+            .ok_or_else(|| serde::de::Error::custom("expected `x,y`"))?;
+        let parse = |n: &str| n.parse().map_err(serde::de::Error::custom);
+        Ok((parse(x)?, parse(y)?))
+    }
+
+    fn write_pair<S: serde::Serializer>(
+        counter: &CounterValue,
+        plain: &u32,
+        state: &Recorder,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        state.serialized.set(state.serialized.get() + 10);
+        serializer.serialize_u32(counter.0 * 1000 + plain)
+    }
+
+    fn read_pair<'de, D: serde::Deserializer<'de>>(
+        state: &Recorder,
+        deserializer: D,
+    ) -> Result<(CounterValue, u32), D::Error> {
+        state.deserialized.set(state.deserialized.get() + 10);
+        let packed = u32::deserialize(deserializer)?;
+        Ok((CounterValue(packed / 1000), packed % 1000))
+    }
+
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    enum Shape {
+        #[serde(serialize_with = "write_point", deserialize_with = "read_point")]
+        Point {
+            x: u32,
+            y: u32,
+        },
+        #[serde(with = "counter_passthrough")]
+        Passthrough(CounterValue),
+        Counter(CounterValue),
+    }
+
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    #[serde(tag = "type", content = "data")]
+    #[serde_state(state = Recorder)]
+    enum Packed {
+        #[serde_state(
+            serialize_state_with = "write_pair",
+            deserialize_state_with = "read_pair"
+        )]
+        Pair(CounterValue, u32),
+        Counter(CounterValue),
+    }
+
+    assert_json_round_trip(
+        &Shape::Point { x: 1, y: 2 },
+        json!({"Point": "1,2"}),
+        (0, 0),
+    );
+    assert_json_round_trip(
+        &Shape::Passthrough(CounterValue(3)),
+        json!({"Passthrough": 103}),
+        (0, 0),
+    );
+    assert_json_round_trip(
+        &Shape::Counter(CounterValue(4)),
+        json!({"Counter": 4}),
+        (1, 1),
+    );
+    assert_json_round_trip(
+        &Packed::Pair(CounterValue(5), 6),
+        json!({"type": "Pair", "data": 5006}),
+        (10, 10),
+    );
+    assert_json_round_trip(
+        &Packed::Counter(CounterValue(7)),
+        json!({"type": "Counter", "data": 7}),
+        (1, 1),
+    );
+
+    let state = Recorder::default();
+    let error = Shape::deserialize_state(&state, json!({"Point": "1;2"})).unwrap_err();
+    assert_eq!(error.to_string(), "expected `x,y`");
+}
+
+#[test]
+fn with_functions_on_generic_fields() {
+    use serde::ser::SerializeSeq;
+    use serde_state::ser::SerializeSeqExt;
+
+    // Written as a count followed by the elements.
+    fn write_counted<T: serde::Serialize, S: serde::Serializer>(
+        values: &Vec<T>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        (values.len(), values).serialize(serializer)
+    }
+
+    fn read_counted<'de, T: serde::Deserialize<'de>, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<T>, D::Error> {
+        let (_, values) = <(usize, Vec<T>)>::deserialize(deserializer)?;
+        Ok(values)
+    }
+
+    fn write_reversed<T: SerializeState<Recorder>, S: serde::Serializer>(
+        values: &[T],
+        state: &Recorder,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(values.len()))?;
+        for value in values.iter().rev() {
+            seq.serialize_element_state(value, state)?;
+        }
+        seq.end()
+    }
+
+    fn read_reversed<'de, T: DeserializeState<'de, Recorder>, D: serde::Deserializer<'de>>(
+        state: &Recorder,
+        deserializer: D,
+    ) -> Result<Vec<T>, D::Error> {
+        let mut values = Vec::<T>::deserialize_state(state, deserializer)?;
+        values.reverse();
+        Ok(values)
+    }
+
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    #[serde(bound(
+        serialize = "T: serde::Serialize",
+        deserialize = "T: serde::Deserialize<'de>"
+    ))]
+    struct Counted<T> {
+        #[serde(serialize_with = "write_counted", deserialize_with = "read_counted")]
+        values: Vec<T>,
+    }
+
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    #[serde(bound(
+        serialize = "T: SerializeState<Recorder> + Serialize",
+        deserialize = "T: DeserializeState<'de, Recorder> + Deserialize<'de>"
+    ))]
+    #[serde_state(state = Recorder)]
+    enum Ordered<T> {
+        Reversed(
+            #[serde_state(
+                serialize_state_with = "write_reversed",
+                deserialize_state_with = "read_reversed"
+            )]
+            Vec<T>,
+            u32,
+        ),
+        #[serde(serialize_with = "write_counted", deserialize_with = "read_counted")]
+        Counted(Vec<T>),
+    }
+
+    assert_json_round_trip(
+        &Counted {
+            values: vec![CounterValue(1), CounterValue(2)],
+        },
+        json!({"values": [2, [1, 2]]}),
+        (0, 0),
+    );
+    assert_json_round_trip(
+        &Ordered::Reversed(vec![CounterValue(1), CounterValue(2)], 3),
+        json!({"Reversed": [[2, 1], 3]}),
+        (2, 2),
+    );
+    assert_json_round_trip(
+        &Ordered::Counted(vec![CounterValue(4)]),
+        json!({"Counted": [1, [4]]}),
+        (0, 0),
+    );
+}
+
+#[test]
+fn bound_attributes_replace_inferred_bounds() {
+    // Recursive, so the bound inferred for `Box<List<T>>` would never be satisfied.
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    enum List<T> {
+        Nil,
+        Cons(T, #[serde(bound = "")] Box<List<T>>),
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct NoImpls;
+
+    // With an explicit state `T` would need the stateful traits, though it is only a marker.
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    #[serde(bound = "")]
+    #[serde_state(state = Recorder)]
+    struct Marked<T> {
+        counter: CounterValue,
+        #[serde(skip)]
+        marker: PhantomData<T>,
+    }
+
+    let list = List::Cons(
+        CounterValue(1),
+        Box::new(List::Cons(CounterValue(2), Box::new(List::Nil))),
+    );
+    let state = Recorder::default();
+    let json_value = serde_json::to_value(serde_state::ser::StateRef::new(&list, &state)).unwrap();
+    assert_eq!(json_value, json!({"Cons": [1, {"Cons": [2, "Nil"]}]}));
+    assert_eq!(state.serialized.get(), 2);
+    let decoded = List::<CounterValue>::deserialize_state(&state, json_value).unwrap();
+    assert_eq!(decoded, list);
+    assert_eq!(state.deserialized.get(), 2);
+
+    let marked = Marked::<NoImpls> {
+        counter: CounterValue(3),
+        marker: PhantomData,
+    };
+    let state = Recorder::default();
+    let json_value =
+        serde_json::to_value(serde_state::ser::StateRef::new(&marked, &state)).unwrap();
+    assert_eq!(json_value, json!({"counter": 3}));
+    let decoded = Marked::<NoImpls>::deserialize_state(&state, json_value).unwrap();
+    assert_eq!(decoded, marked);
+}
+
+#[test]
+fn proxy_conversions_thread_state() {
+    // Written as a `CounterValue` holding half the value, which still receives the state.
+    #[derive(SerializeState, DeserializeState, Clone, Debug, PartialEq)]
+    #[serde(from = "CounterValue", into = "CounterValue")]
+    struct Doubled(u32);
+
+    impl From<CounterValue> for Doubled {
+        fn from(counter: CounterValue) -> Self {
+            Doubled(counter.0 * 2)
+        }
+    }
+
+    impl From<Doubled> for CounterValue {
+        fn from(doubled: Doubled) -> Self {
+            CounterValue(doubled.0 / 2)
+        }
+    }
+
+    #[derive(DeserializeState, Debug, PartialEq)]
+    #[serde(try_from = "CounterValue")]
+    struct Even(u32);
+
+    impl TryFrom<CounterValue> for Even {
+        type Error = String;
+
+        fn try_from(counter: CounterValue) -> Result<Self, String> {
+            match counter.0 % 2 {
+                0 => Ok(Even(counter.0)),
+                _ => Err(format!("{} is odd", counter.0)),
+            }
+        }
+    }
+
+    #[derive(Default)]
+    struct Interner {
+        names: RefCell<Vec<String>>,
+    }
+
+    // Written as its index in the interner.
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    #[serde_state(state = Interner, into_state = "Symbol::index", from_state = "Symbol::lookup")]
+    struct Symbol(String);
+
+    impl Symbol {
+        fn index(&self, interner: &Interner) -> usize {
+            let mut names = interner.names.borrow_mut();
+            match names.iter().position(|name| *name == self.0) {
+                Some(index) => index,
+                None => {
+                    names.push(self.0.clone());
+                    names.len() - 1
+                }
+            }
+        }
+
+        fn lookup(index: usize, interner: &Interner) -> Self {
+            Symbol(interner.names.borrow()[index].clone())
+        }
+    }
+
+    // Rejects indices the interner hasn't handed out.
+    #[derive(DeserializeState, Debug, PartialEq)]
+    #[serde_state(state = Interner, try_from_state = "Known::lookup")]
+    struct Known(String);
+
+    impl Known {
+        fn lookup(index: usize, interner: &Interner) -> Result<Self, String> {
+            match interner.names.borrow().get(index) {
+                Some(name) => Ok(Known(name.clone())),
+                None => Err(format!("unknown symbol {index}")),
+            }
+        }
+    }
+
+    assert_json_round_trip(&Doubled(8), json!(4), (1, 1));
+
+    let state = Recorder::default();
+    assert_eq!(Even::deserialize_state(&state, json!(6)).unwrap(), Even(6));
+    // The conversion error reaches the format's `Error::custom` as is.
+    let mut deserializer = serde_json::Deserializer::from_str("7");
+    let error = Even::deserialize_state(&state, &mut deserializer).unwrap_err();
+    assert!(error.is_data(), "{error}");
+    assert_eq!(error.to_string(), "7 is odd");
+    assert_eq!(state.deserialized.get(), 2);
+
+    let interner = Interner::default();
+    let symbols = vec![
+        Symbol("a".to_owned()),
+        Symbol("b".to_owned()),
+        Symbol("a".to_owned()),
+    ];
+    let json_value =
+        serde_json::to_value(serde_state::ser::StateRef::new(&symbols, &interner)).unwrap();
+    assert_eq!(json_value, json!([0, 1, 0]));
+    let decoded = Vec::<Symbol>::deserialize_state(&interner, json_value).unwrap();
+    assert_eq!(decoded, symbols);
+
+    assert_eq!(
+        Known::deserialize_state(&interner, json!(1)).unwrap(),
+        Known("b".to_owned())
+    );
+    let mut deserializer = serde_json::Deserializer::from_str("2");
+    let error = Known::deserialize_state(&interner, &mut deserializer).unwrap_err();
+    assert!(error.is_data(), "{error}");
+    assert_eq!(error.to_string(), "unknown symbol 2");
+}
+
+#[test]
+fn named_state_proxies_accept_generic_states() {
+    trait HasNames {
+        fn names(&self) -> &RefCell<Vec<String>>;
+    }
+
+    #[derive(Default)]
+    struct Interner {
+        names: RefCell<Vec<String>>,
+    }
+
+    impl HasNames for Interner {
+        fn names(&self) -> &RefCell<Vec<String>> {
+            &self.names
+        }
+    }
+
+    // Naming the proxy type lets the derive bound it on the generic state.
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    #[serde_state(
+        state_implements = HasNames,
+        into_state(type = "usize", fn = "Symbol::index"),
+        from_state(type = "usize", fn = "Symbol::lookup")
+    )]
+    struct Symbol(String);
+
+    impl Symbol {
+        fn index<S: HasNames + ?Sized>(&self, state: &S) -> usize {
+            let mut names = state.names().borrow_mut();
+            match names.iter().position(|name| *name == self.0) {
+                Some(index) => index,
+                None => {
+                    names.push(self.0.clone());
+                    names.len() - 1
+                }
+            }
+        }
+
+        fn lookup<S: HasNames + ?Sized>(index: usize, state: &S) -> Self {
+            Symbol(state.names().borrow()[index].clone())
+        }
+    }
+
+    let interner = Interner::default();
+    let symbols = vec![Symbol("x".to_owned()), Symbol("x".to_owned())];
+    let json_value =
+        serde_json::to_value(serde_state::ser::StateRef::new(&symbols, &interner)).unwrap();
+    assert_eq!(json_value, json!([0, 0]));
+    let decoded = Vec::<Symbol>::deserialize_state(&interner, json_value).unwrap();
+    assert_eq!(decoded, symbols);
+}
+
+/// Stand-ins for types from another crate, which can't implement the stateful traits.
+mod foreign {
+    use super::CounterValue;
+
+    #[derive(Debug, PartialEq)]
+    pub struct Span {
+        pub lo: u32,
+        pub hi: CounterValue,
+    }
+
+    #[derive(Debug, PartialEq)]
+    pub struct Id(u32);
+
+    impl Id {
+        pub fn new(id: u32) -> Self {
+            Id(id)
+        }
+
+        pub fn get(&self) -> u32 {
+            self.0
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    pub enum Shape {
+        Dot,
+        Line(CounterValue, CounterValue),
+    }
+}
+
+#[test]
+fn remote_definitions_are_usable_with_with() {
+    #[derive(SerializeState, DeserializeState)]
+    #[serde(remote = "foreign::Span")]
+    struct SpanDef {
+        lo: u32,
+        hi: CounterValue,
+    }
+
+    #[derive(SerializeState, DeserializeState)]
+    #[serde(remote = "foreign::Id")]
+    struct IdDef(#[serde(getter = "foreign::Id::get")] u32);
+
+    impl From<IdDef> for foreign::Id {
+        fn from(def: IdDef) -> Self {
+            foreign::Id::new(def.0)
+        }
+    }
+
+    #[derive(SerializeState, DeserializeState)]
+    #[serde(remote = "foreign::Shape")]
+    enum ShapeDef {
+        Dot,
+        Line(CounterValue, CounterValue),
+    }
+
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    #[serde_state(state = Recorder)]
+    struct Drawing {
+        #[serde(with = "SpanDef")]
+        span: foreign::Span,
+        #[serde(with = "IdDef")]
+        id: foreign::Id,
+        #[serde(with = "ShapeDef")]
+        shape: foreign::Shape,
+    }
+
+    let value = Drawing {
+        span: foreign::Span {
+            lo: 1,
+            hi: CounterValue(2),
+        },
+        id: foreign::Id::new(3),
+        shape: foreign::Shape::Line(CounterValue(4), CounterValue(5)),
+    };
+    assert_json_round_trip(
+        &value,
+        json!({"span": {"lo": 1, "hi": 2}, "id": 3, "shape": {"Line": [4, 5]}}),
+        (3, 3),
+    );
+
+    let state = Recorder::default();
+    let mut deserializer = serde_json::Deserializer::from_str(r#""Dot""#);
+    let shape = ShapeDef::deserialize_state(&state, &mut deserializer).unwrap();
+    assert_eq!(shape, foreign::Shape::Dot);
+
+    let input = json!({"span": {"lo": 1}, "id": 3, "shape": "Dot"});
+    let error = Drawing::deserialize_state(&state, input).unwrap_err();
+    assert_eq!(error.to_string(), "missing field `hi`");
+    let input = json!({"span": {"lo": 1, "hi": 2}, "id": "three", "shape": "Dot"});
+    let error = Drawing::deserialize_state(&state, input).unwrap_err();
+    assert!(error.to_string().contains("expected u32"), "{error}");
+}
+
+#[test]
+fn postcard_named_struct_deserializes_from_seq_and_threads_state() {
+    let value = Example {
+        first: CounterValue(31),
+        second: CounterValue(32),
+    };
+
+    let ser_state = Recorder::default();
+    let bytes = postcard::to_allocvec(&serde_state::__private::wrap_serialize(&value, &ser_state))
+        .expect("postcard serialize named struct");
+    assert_eq!(ser_state.serialized.get(), 2);
+
+    let de_state = Recorder::default();
+    let mut deserializer = postcard::Deserializer::from_bytes(&bytes);
+    let decoded = Example::deserialize_state(&de_state, &mut deserializer)
+        .expect("postcard deserialize named struct");
+    assert_eq!(
+        deserializer.finalize().expect("postcard remainder").len(),
+        0
+    );
+    assert_eq!(decoded, value);
+    assert_eq!(de_state.deserialized.get(), 2);
+}
+
+#[test]
+fn postcard_enum_variants_deserialize_from_numeric_tags() {
+    fn round_trip(value: Action, expected_hits: usize) {
+        let ser_state = Recorder::default();
+        let bytes =
+            postcard::to_allocvec(&serde_state::__private::wrap_serialize(&value, &ser_state))
+                .expect("postcard serialize enum");
+        assert_eq!(ser_state.serialized.get(), expected_hits);
+
+        let de_state = Recorder::default();
+        let mut deserializer = postcard::Deserializer::from_bytes(&bytes);
+        let decoded = Action::deserialize_state(&de_state, &mut deserializer)
+            .expect("postcard deserialize enum");
+        assert_eq!(
+            deserializer.finalize().expect("postcard remainder").len(),
+            0
+        );
+        assert_eq!(decoded, value);
+        assert_eq!(de_state.deserialized.get(), expected_hits);
+    }
+
+    round_trip(Action::Idle, 0);
+    round_trip(Action::Reset(CounterValue(51)), 1);
+    round_trip(Action::Combine(CounterValue(52), CounterValue(53)), 2);
+    round_trip(
+        Action::Record {
+            first: CounterValue(54),
+            second: CounterValue(55),
+        },
+        2,
+    );
+}
+
+#[test]
+fn postcard_serialize_matches_serialize_state_for_auto_serde_container() {
+    let value = AutoSerdeContainer {
+        value: GenericCounterValue(123),
+    };
+    assert_postcard_serde_and_state_equal_with_counts(&value, 1);
+}
+
+#[test]
+fn postcard_serialize_matches_serialize_state_for_vec_of_auto_serde_container() {
+    let values = vec![
+        AutoSerdeContainer {
+            value: GenericCounterValue(7),
+        },
+        AutoSerdeContainer {
+            value: GenericCounterValue(8),
+        },
+    ];
+
+    assert_postcard_serde_and_state_equal_with_counts(&values, 2);
+}
+
+#[test]
+fn postcard_serialize_matches_serialize_state_for_multi_field_struct() {
+    let value = Example {
+        first: CounterValue(31),
+        second: CounterValue(32),
+    };
+
+    assert_postcard_serde_and_state_equal_with_counts(&value, 2);
+}
+
+fn assert_postcard_serde_and_state_equal_with_counts<T>(value: &T, expected_hits: usize)
+where
+    T: Serialize
+        + for<'de> Deserialize<'de>
+        + SerializeState<Recorder>
+        + for<'de> DeserializeState<'de, Recorder>
+        + PartialEq
+        + std::fmt::Debug,
+{
+    let serde_bytes = postcard::to_allocvec(value).expect("postcard serde serialize");
+
+    let ser_state = Recorder::default();
+    let state_bytes =
+        postcard::to_allocvec(&serde_state::__private::wrap_serialize(value, &ser_state))
+            .expect("postcard state serialize");
+    assert_eq!(ser_state.serialized_count(), expected_hits);
+    assert_eq!(serde_bytes, state_bytes);
+
+    let serde_decoded: T = postcard::from_bytes(&state_bytes).expect("postcard serde deserialize");
+    assert_eq!(&serde_decoded, value);
+
+    let de_state = Recorder::default();
+    let mut deserializer = postcard::Deserializer::from_bytes(&serde_bytes);
+    let state_decoded = T::deserialize_state(&de_state, &mut deserializer)
+        .expect("postcard state deserialize");
+    assert_eq!(
+        deserializer.finalize().expect("postcard remainder").len(),
+        0
+    );
+    assert_eq!(de_state.deserialized_count(), expected_hits);
+    assert_eq!(&state_decoded, value);
+}
+
+/// Serializes `value` to JSON, checks the output and reads it back, all with one `Recorder`
+/// whose `(serialized, deserialized)` counts must end up as `expected_counters`.
+fn assert_json_round_trip<T>(
+    value: &T,
+    expected: serde_json::Value,
+    expected_counters: (usize, usize),
+) where
+    T: SerializeState<Recorder>
+        + for<'de> DeserializeState<'de, Recorder>
+        + PartialEq
+        + std::fmt::Debug,
+{
+    let state = Recorder::default();
+    let json_value = serde_json::to_value(serde_state::ser::StateRef::new(value, &state)).unwrap();
+    assert_eq!(json_value, expected);
+    let decoded = T::deserialize_state(&state, json_value).unwrap();
+    assert_eq!(&decoded, value);
+    assert_eq!(
+        (state.serialized.get(), state.deserialized.get()),
+        expected_counters
+    );
 }
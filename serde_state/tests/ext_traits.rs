@@ -0,0 +1,515 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use serde_state::{DeserializeState, SerializeState};
+use std::cell::Cell;
+
+#[derive(Default)]
+struct Recorder {
+    serialized: Cell<usize>,
+    deserialized: Cell<usize>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+struct CounterValue(u32);
+
+impl SerializeState<Recorder> for CounterValue {
+    fn serialize_state<S>(&self, recorder: &Recorder, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        recorder.serialized.set(recorder.serialized.get() + 1);
+        serializer.serialize_u32(self.0)
+    }
+}
+
+impl<'de> DeserializeState<'de, Recorder> for CounterValue {
+    fn deserialize_state<D>(recorder: &Recorder, deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        recorder.deserialized.set(recorder.deserialized.get() + 1);
+        let value = u32::deserialize(deserializer)?;
+        Ok(CounterValue(value))
+    }
+}
+
+#[test]
+fn seq_extension_traits_thread_state() {
+    use serde::ser::SerializeSeq;
+    use serde_state::de::SeqAccessExt;
+    use serde_state::ser::SerializeSeqExt;
+
+    #[derive(Debug, PartialEq)]
+    struct Counters(Vec<CounterValue>);
+
+    impl SerializeState<Recorder> for Counters {
+        fn serialize_state<S>(&self, state: &Recorder, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: serde::Serializer,
+        {
+            let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+            for value in &self.0 {
+                seq.serialize_element_state(value, state)?;
+            }
+            seq.end()
+        }
+    }
+
+    impl<'de> DeserializeState<'de, Recorder> for Counters {
+        fn deserialize_state<D>(state: &Recorder, deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            struct Visitor<'a>(&'a Recorder);
+
+            impl<'de> serde::de::Visitor<'de> for Visitor<'_> {
+                type Value = Counters;
+
+                fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    formatter.write_str("a sequence of counters")
+                }
+
+                fn visit_seq<A>(self, mut seq: A) -> Result<Counters, A::Error>
+                where
+                    A: serde::de::SeqAccess<'de>,
+                {
+                    let mut values = Vec::new();
+                    while let Some(value) = seq.next_element_state(self.0)? {
+                        values.push(value);
+                    }
+                    Ok(Counters(values))
+                }
+            }
+
+            deserializer.deserialize_seq(Visitor(state))
+        }
+    }
+
+    assert_json_round_trip(
+        &Counters(vec![CounterValue(1), CounterValue(2)]),
+        json!([1, 2]),
+        (2, 2),
+    );
+}
+
+#[test]
+fn tuple_extension_traits_thread_state() {
+    use serde::ser::{SerializeTuple, SerializeTupleStruct};
+    use serde_state::de::SeqAccessExt;
+    use serde_state::ser::{SerializeTupleExt, SerializeTupleStructExt};
+
+    // Written as a tuple when `named` is false and as a tuple struct otherwise.
+    #[derive(Debug, PartialEq)]
+    struct Pair {
+        named: bool,
+        values: (CounterValue, CounterValue),
+    }
+
+    impl SerializeState<Recorder> for Pair {
+        fn serialize_state<S>(&self, state: &Recorder, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: serde::Serializer,
+        {
+            let (a, b) = &self.values;
+            if self.named {
+                let mut tuple = serializer.serialize_tuple_struct("Pair", 2)?;
+                tuple.serialize_field_state(a, state)?;
+                tuple.serialize_field_state(b, state)?;
+                tuple.end()
+            } else {
+                let mut tuple = serializer.serialize_tuple(2)?;
+                tuple.serialize_element_state(a, state)?;
+                tuple.serialize_element_state(b, state)?;
+                tuple.end()
+            }
+        }
+    }
+
+    impl<'de> DeserializeState<'de, Recorder> for Pair {
+        fn deserialize_state<D>(state: &Recorder, deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            struct Visitor<'a>(&'a Recorder);
+
+            impl<'de> serde::de::Visitor<'de> for Visitor<'_> {
+                type Value = (CounterValue, CounterValue);
+
+                fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    formatter.write_str("a pair of counters")
+                }
+
+                fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+                where
+                    A: serde::de::SeqAccess<'de>,
+                {
+                    let missing = |index| serde::de::Error::invalid_length(index, &"two counters");
+                    let a = seq.next_element_state(self.0)?.ok_or_else(|| missing(0))?;
+                    let b = seq.next_element_state(self.0)?.ok_or_else(|| missing(1))?;
+                    Ok((a, b))
+                }
+            }
+
+            let values = deserializer.deserialize_tuple(2, Visitor(state))?;
+            Ok(Pair {
+                // JSON writes both forms the same way.
+                named: false,
+                values,
+            })
+        }
+    }
+
+    assert_json_round_trip(
+        &Pair {
+            named: false,
+            values: (CounterValue(1), CounterValue(2)),
+        },
+        json!([1, 2]),
+        (2, 2),
+    );
+
+    let state = Recorder::default();
+    let named = Pair {
+        named: true,
+        values: (CounterValue(3), CounterValue(4)),
+    };
+    let json_value = serde_json::to_value(serde_state::ser::StateRef::new(&named, &state)).unwrap();
+    assert_eq!(json_value, json!([3, 4]));
+    assert_eq!(state.serialized.get(), 2);
+}
+
+#[test]
+fn map_extension_traits_thread_state() {
+    use serde::ser::SerializeMap;
+    use serde_state::de::MapAccessExt;
+    use serde_state::ser::SerializeMapExt;
+
+    // Written key by key when `by_entry` is false and entry by entry otherwise, and read back
+    // the same way.
+    #[derive(Debug, PartialEq)]
+    struct Registry {
+        by_entry: bool,
+        entries: Vec<(CounterValue, CounterValue)>,
+    }
+
+    impl SerializeState<Recorder> for Registry {
+        fn serialize_state<S>(&self, state: &Recorder, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: serde::Serializer,
+        {
+            let mut map = serializer.serialize_map(Some(self.entries.len()))?;
+            for (key, value) in &self.entries {
+                if self.by_entry {
+                    map.serialize_entry_state(key, value, state)?;
+                } else {
+                    map.serialize_key_state(key, state)?;
+                    map.serialize_value_state(value, state)?;
+                }
+            }
+            map.end()
+        }
+    }
+
+    struct Visitor<'a> {
+        state: &'a Recorder,
+        by_entry: bool,
+    }
+
+    impl<'de> serde::de::Visitor<'de> for Visitor<'_> {
+        type Value = Registry;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            formatter.write_str("a map of counters")
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<Registry, A::Error>
+        where
+            A: serde::de::MapAccess<'de>,
+        {
+            let mut entries = Vec::new();
+            if self.by_entry {
+                while let Some(entry) = map.next_entry_state(self.state)? {
+                    entries.push(entry);
+                }
+            } else {
+                while let Some(key) = map.next_key_state(self.state)? {
+                    entries.push((key, map.next_value_state(self.state)?));
+                }
+            }
+            Ok(Registry {
+                by_entry: self.by_entry,
+                entries,
+            })
+        }
+    }
+
+    for by_entry in [false, true] {
+        let value = Registry {
+            by_entry,
+            entries: vec![(CounterValue(1), CounterValue(10))],
+        };
+        let state = Recorder::default();
+        let json_value =
+            serde_json::to_value(serde_state::ser::StateRef::new(&value, &state)).unwrap();
+        assert_eq!(json_value, json!({"1": 10}));
+        let mut deserializer = serde_json::Deserializer::from_str(r#"{"1": 10}"#);
+        let decoded = serde::Deserializer::deserialize_map(
+            &mut deserializer,
+            Visitor {
+                state: &state,
+                by_entry,
+            },
+        )
+        .unwrap();
+        assert_eq!(decoded, value);
+        assert_eq!((state.serialized.get(), state.deserialized.get()), (2, 2));
+    }
+}
+
+#[test]
+fn struct_extension_traits_thread_state() {
+    use serde::ser::SerializeStruct;
+    use serde_state::de::MapAccessExt;
+    use serde_state::ser::SerializeStructExt;
+
+    #[derive(Debug, PartialEq)]
+    struct Span {
+        lo: CounterValue,
+        hi: CounterValue,
+    }
+
+    impl SerializeState<Recorder> for Span {
+        fn serialize_state<S>(&self, state: &Recorder, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: serde::Serializer,
+        {
+            let mut span = serializer.serialize_struct("Span", 2)?;
+            span.serialize_field_state("lo", &self.lo, state)?;
+            span.serialize_field_state("hi", &self.hi, state)?;
+            span.end()
+        }
+    }
+
+    impl<'de> DeserializeState<'de, Recorder> for Span {
+        fn deserialize_state<D>(state: &Recorder, deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            struct Visitor<'a>(&'a Recorder);
+
+            impl<'de> serde::de::Visitor<'de> for Visitor<'_> {
+                type Value = Span;
+
+                fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    formatter.write_str("struct Span")
+                }
+
+                fn visit_map<A>(self, mut map: A) -> Result<Span, A::Error>
+                where
+                    A: serde::de::MapAccess<'de>,
+                {
+                    let (mut lo, mut hi) = (None, None);
+                    while let Some(key) = map.next_key::<String>()? {
+                        match key.as_str() {
+                            "lo" => lo = Some(map.next_value_state(self.0)?),
+                            "hi" => hi = Some(map.next_value_state(self.0)?),
+                            _ => return Err(serde::de::Error::unknown_field(&key, FIELDS)),
+                        }
+                    }
+                    Ok(Span {
+                        lo: lo.ok_or_else(|| serde::de::Error::missing_field("lo"))?,
+                        hi: hi.ok_or_else(|| serde::de::Error::missing_field("hi"))?,
+                    })
+                }
+            }
+
+            const FIELDS: &[&str] = &["lo", "hi"];
+            deserializer.deserialize_struct("Span", FIELDS, Visitor(state))
+        }
+    }
+
+    assert_json_round_trip(
+        &Span {
+            lo: CounterValue(1),
+            hi: CounterValue(2),
+        },
+        json!({"lo": 1, "hi": 2}),
+        (2, 2),
+    );
+}
+
+#[test]
+fn enum_extension_traits_thread_state() {
+    use serde::de::VariantAccess;
+    use serde::ser::{SerializeStructVariant, SerializeTupleVariant};
+    use serde_state::de::{EnumAccessExt, MapAccessExt, SeqAccessExt, VariantAccessExt};
+    use serde_state::ser::{SerializeStructVariantExt, SerializeTupleVariantExt, StateRef};
+
+    #[derive(Debug, PartialEq)]
+    enum Shape {
+        Dot(CounterValue),
+        Line(CounterValue, CounterValue),
+        Labeled { id: CounterValue },
+    }
+
+    const VARIANTS: &[&str] = &["Dot", "Line", "Labeled"];
+
+    // A variant name that is itself read through the state.
+    struct Tag(usize);
+
+    impl<'de> DeserializeState<'de, Recorder> for Tag {
+        fn deserialize_state<D>(state: &Recorder, deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            state.deserialized.set(state.deserialized.get() + 1);
+            let name = String::deserialize(deserializer)?;
+            match VARIANTS.iter().position(|variant| *variant == name) {
+                Some(index) => Ok(Tag(index)),
+                None => Err(serde::de::Error::unknown_variant(&name, VARIANTS)),
+            }
+        }
+    }
+
+    impl SerializeState<Recorder> for Shape {
+        fn serialize_state<S>(&self, state: &Recorder, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: serde::Serializer,
+        {
+            match self {
+                Shape::Dot(value) => serializer.serialize_newtype_variant(
+                    "Shape",
+                    0,
+                    "Dot",
+                    &StateRef::new(value, state),
+                ),
+                Shape::Line(a, b) => {
+                    let mut line = serializer.serialize_tuple_variant("Shape", 1, "Line", 2)?;
+                    line.serialize_field_state(a, state)?;
+                    line.serialize_field_state(b, state)?;
+                    line.end()
+                }
+                Shape::Labeled { id } => {
+                    let mut labeled =
+                        serializer.serialize_struct_variant("Shape", 2, "Labeled", 1)?;
+                    labeled.serialize_field_state("id", id, state)?;
+                    labeled.end()
+                }
+            }
+        }
+    }
+
+    impl<'de> DeserializeState<'de, Recorder> for Shape {
+        fn deserialize_state<D>(state: &Recorder, deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            struct Visitor<'a>(&'a Recorder);
+
+            impl<'de> serde::de::Visitor<'de> for Visitor<'_> {
+                type Value = Shape;
+
+                fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    formatter.write_str("enum Shape")
+                }
+
+                fn visit_enum<A>(self, data: A) -> Result<Shape, A::Error>
+                where
+                    A: serde::de::EnumAccess<'de>,
+                {
+                    let (Tag(index), variant) = data.variant_state(self.0)?;
+                    match index {
+                        0 => Ok(Shape::Dot(variant.newtype_variant_state(self.0)?)),
+                        1 => variant.tuple_variant(2, LineVisitor(self.0)),
+                        _ => variant.struct_variant(&["id"], LabeledVisitor(self.0)),
+                    }
+                }
+            }
+
+            struct LineVisitor<'a>(&'a Recorder);
+
+            impl<'de> serde::de::Visitor<'de> for LineVisitor<'_> {
+                type Value = Shape;
+
+                fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    formatter.write_str("tuple variant Shape::Line")
+                }
+
+                fn visit_seq<A>(self, mut seq: A) -> Result<Shape, A::Error>
+                where
+                    A: serde::de::SeqAccess<'de>,
+                {
+                    let missing = |index| serde::de::Error::invalid_length(index, &"two counters");
+                    let a = seq.next_element_state(self.0)?.ok_or_else(|| missing(0))?;
+                    let b = seq.next_element_state(self.0)?.ok_or_else(|| missing(1))?;
+                    Ok(Shape::Line(a, b))
+                }
+            }
+
+            struct LabeledVisitor<'a>(&'a Recorder);
+
+            impl<'de> serde::de::Visitor<'de> for LabeledVisitor<'_> {
+                type Value = Shape;
+
+                fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    formatter.write_str("struct variant Shape::Labeled")
+                }
+
+                fn visit_map<A>(self, mut map: A) -> Result<Shape, A::Error>
+                where
+                    A: serde::de::MapAccess<'de>,
+                {
+                    let mut id = None;
+                    while let Some(key) = map.next_key::<String>()? {
+                        match key.as_str() {
+                            "id" => id = Some(map.next_value_state(self.0)?),
+                            _ => return Err(serde::de::Error::unknown_field(&key, &["id"])),
+                        }
+                    }
+                    let id = id.ok_or_else(|| serde::de::Error::missing_field("id"))?;
+                    Ok(Shape::Labeled { id })
+                }
+            }
+
+            deserializer.deserialize_enum("Shape", VARIANTS, Visitor(state))
+        }
+    }
+
+    // Deserializing also counts the variant tag.
+    assert_json_round_trip(&Shape::Dot(CounterValue(1)), json!({"Dot": 1}), (1, 2));
+    assert_json_round_trip(
+        &Shape::Line(CounterValue(2), CounterValue(3)),
+        json!({"Line": [2, 3]}),
+        (2, 3),
+    );
+    assert_json_round_trip(
+        &Shape::Labeled {
+            id: CounterValue(4),
+        },
+        json!({"Labeled": {"id": 4}}),
+        (1, 2),
+    );
+}
+
+/// Serializes `value` to JSON, checks the output and reads it back, all with one `Recorder`
+/// whose `(serialized, deserialized)` counts must end up as `expected_counters`.
+fn assert_json_round_trip<T>(
+    value: &T,
+    expected: serde_json::Value,
+    expected_counters: (usize, usize),
+) where
+    T: SerializeState<Recorder>
+        + for<'de> DeserializeState<'de, Recorder>
+        + PartialEq
+        + std::fmt::Debug,
+{
+    let state = Recorder::default();
+    let json_value = serde_json::to_value(serde_state::ser::StateRef::new(value, &state)).unwrap();
+    assert_eq!(json_value, expected);
+    let decoded = T::deserialize_state(&state, json_value).unwrap();
+    assert_eq!(&decoded, value);
+    assert_eq!(
+        (state.serialized.get(), state.deserialized.get()),
+        expected_counters
+    );
+}
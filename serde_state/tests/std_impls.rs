@@ -0,0 +1,453 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use serde_state::{DeserializeState, SerializeState};
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread_local;
+
+#[derive(Default)]
+struct Recorder {
+    serialized: Cell<usize>,
+    deserialized: Cell<usize>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct CounterValue(u32);
+
+impl SerializeState<Recorder> for CounterValue {
+    fn serialize_state<S>(&self, recorder: &Recorder, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        recorder.serialized.set(recorder.serialized.get() + 1);
+        serializer.serialize_u32(self.0)
+    }
+}
+
+impl<'de> DeserializeState<'de, Recorder> for CounterValue {
+    fn deserialize_state<D>(recorder: &Recorder, deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        recorder.deserialized.set(recorder.deserialized.get() + 1);
+        let value = u32::deserialize(deserializer)?;
+        Ok(CounterValue(value))
+    }
+}
+
+#[test]
+fn std_maps_thread_state_into_keys_and_values() {
+    let mut value = BTreeMap::new();
+    value.insert(CounterValue(1), CounterValue(10));
+    value.insert(CounterValue(2), CounterValue(20));
+
+    let state = Recorder::default();
+    let mut buffer = Vec::new();
+    {
+        let mut serializer = serde_json::Serializer::new(&mut buffer);
+        value
+            .serialize_state(&state, &mut serializer)
+            .expect("map serialization");
+    }
+    assert_eq!(state.serialized.get(), 4);
+    let json_value: serde_json::Value = serde_json::from_slice(&buffer).unwrap();
+    assert_eq!(json_value, json!({"1": 10, "2": 20}));
+
+    let state = Recorder::default();
+    let mut deserializer = serde_json::Deserializer::from_slice(&buffer);
+    let decoded =
+        BTreeMap::<CounterValue, CounterValue>::deserialize_state(&state, &mut deserializer)
+            .unwrap();
+    assert_eq!(decoded, value);
+    assert_eq!(state.deserialized.get(), 4);
+
+    let state = Recorder::default();
+    let mut deserializer = serde_json::Deserializer::from_slice(&buffer);
+    let decoded =
+        HashMap::<CounterValue, CounterValue>::deserialize_state(&state, &mut deserializer)
+            .unwrap();
+    assert_eq!(decoded, value.into_iter().collect::<HashMap<_, _>>());
+    assert_eq!(state.deserialized.get(), 4);
+}
+
+#[test]
+fn std_sequences_and_sets_thread_state() {
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    struct Collections {
+        queue: VecDeque<CounterValue>,
+        ordered: BTreeSet<CounterValue>,
+        hashed: HashSet<CounterValue>,
+    }
+
+    let value = Collections {
+        queue: VecDeque::from([CounterValue(1), CounterValue(2)]),
+        ordered: BTreeSet::from([CounterValue(3), CounterValue(4)]),
+        hashed: HashSet::from([CounterValue(5)]),
+    };
+
+    let state = Recorder::default();
+    let mut buffer = Vec::new();
+    {
+        let mut serializer = serde_json::Serializer::new(&mut buffer);
+        value
+            .serialize_state(&state, &mut serializer)
+            .expect("collection serialization");
+    }
+    assert_eq!(state.serialized.get(), 5);
+    let json_value: serde_json::Value = serde_json::from_slice(&buffer).unwrap();
+    assert_eq!(
+        json_value,
+        json!({"queue": [1, 2], "ordered": [3, 4], "hashed": [5]})
+    );
+
+    let state = Recorder::default();
+    let mut deserializer = serde_json::Deserializer::from_slice(&buffer);
+    let decoded = Collections::deserialize_state(&state, &mut deserializer).unwrap();
+    assert_eq!(decoded, value);
+    assert_eq!(state.deserialized.get(), 5);
+}
+
+#[test]
+fn tuples_thread_state_to_every_element() {
+    let value = (
+        CounterValue(1),
+        (CounterValue(2), 3u32, CounterValue(4)),
+        (),
+    );
+
+    let state = Recorder::default();
+    let mut buffer = Vec::new();
+    {
+        let mut serializer = serde_json::Serializer::new(&mut buffer);
+        value
+            .serialize_state(&state, &mut serializer)
+            .expect("tuple serialization");
+    }
+    assert_eq!(state.serialized.get(), 3);
+    let json_value: serde_json::Value = serde_json::from_slice(&buffer).unwrap();
+    assert_eq!(json_value, json!([1, [2, 3, 4], null]));
+
+    let state = Recorder::default();
+    let mut deserializer = serde_json::Deserializer::from_slice(&buffer);
+    let decoded = <(CounterValue, (CounterValue, u32, CounterValue), ())>::deserialize_state(
+        &state,
+        &mut deserializer,
+    )
+    .unwrap();
+    assert_eq!(decoded, value);
+    assert_eq!(state.deserialized.get(), 3);
+
+    let state = Recorder::default();
+    let mut deserializer = serde_json::Deserializer::from_str("[1, 2]");
+    let err =
+        <(CounterValue, CounterValue, CounterValue)>::deserialize_state(&state, &mut deserializer)
+            .unwrap_err();
+    assert!(err.to_string().contains("invalid length 2"), "{err}");
+}
+
+#[test]
+fn arrays_thread_state_and_drop_partial_results() {
+    thread_local! {
+        static LIVE: Cell<isize> = const { Cell::new(0) };
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Tracked(u32);
+
+    impl<'de> DeserializeState<'de, Recorder> for Tracked {
+        fn deserialize_state<D>(recorder: &Recorder, deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            recorder.deserialized.set(recorder.deserialized.get() + 1);
+            let value = u32::deserialize(deserializer)?;
+            LIVE.with(|live| live.set(live.get() + 1));
+            Ok(Tracked(value))
+        }
+    }
+
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            LIVE.with(|live| live.set(live.get() - 1));
+        }
+    }
+
+    let value = [CounterValue(1), CounterValue(2), CounterValue(3)];
+    let state = Recorder::default();
+    let mut buffer = Vec::new();
+    {
+        let mut serializer = serde_json::Serializer::new(&mut buffer);
+        value
+            .serialize_state(&state, &mut serializer)
+            .expect("array serialization");
+    }
+    assert_eq!(state.serialized.get(), 3);
+    let json_value: serde_json::Value = serde_json::from_slice(&buffer).unwrap();
+    assert_eq!(json_value, json!([1, 2, 3]));
+
+    let state = Recorder::default();
+    let mut deserializer = serde_json::Deserializer::from_slice(&buffer);
+    let decoded = <[CounterValue; 3]>::deserialize_state(&state, &mut deserializer).unwrap();
+    assert_eq!(decoded, value);
+    assert_eq!(state.deserialized.get(), 3);
+
+    let state = Recorder::default();
+    let mut deserializer = serde_json::Deserializer::from_str(r#"[1, 2, "three"]"#);
+    assert!(<[Tracked; 3]>::deserialize_state(&state, &mut deserializer).is_err());
+    assert_eq!(state.deserialized.get(), 3);
+    assert_eq!(LIVE.with(|live| live.get()), 0);
+
+    let state = Recorder::default();
+    let mut deserializer = serde_json::Deserializer::from_str("[1, 2]");
+    let err = <[Tracked; 3]>::deserialize_state(&state, &mut deserializer).unwrap_err();
+    assert!(err.to_string().contains("invalid length 2"), "{err}");
+    assert_eq!(LIVE.with(|live| live.get()), 0);
+}
+
+#[test]
+fn smart_pointers_forward_state() {
+    #[derive(SerializeState, DeserializeState)]
+    struct Pointers {
+        shared: Rc<CounterValue>,
+        cell: Rc<RefCell<CounterValue>>,
+        locked: Arc<Mutex<CounterValue>>,
+        rw: RwLock<CounterValue>,
+        cow: Cow<'static, CounterValue>,
+        copied: Cell<u32>,
+    }
+
+    let value = Pointers {
+        shared: Rc::new(CounterValue(1)),
+        cell: Rc::new(RefCell::new(CounterValue(2))),
+        locked: Arc::new(Mutex::new(CounterValue(3))),
+        rw: RwLock::new(CounterValue(4)),
+        cow: Cow::Owned(CounterValue(5)),
+        copied: Cell::new(6),
+    };
+
+    let state = Recorder::default();
+    let mut buffer = Vec::new();
+    {
+        let mut serializer = serde_json::Serializer::new(&mut buffer);
+        value
+            .serialize_state(&state, &mut serializer)
+            .expect("pointer serialization");
+    }
+    assert_eq!(state.serialized.get(), 5);
+    let json_value: serde_json::Value = serde_json::from_slice(&buffer).unwrap();
+    assert_eq!(
+        json_value,
+        json!({"shared": 1, "cell": 2, "locked": 3, "rw": 4, "cow": 5, "copied": 6})
+    );
+
+    let state = Recorder::default();
+    let mut deserializer = serde_json::Deserializer::from_slice(&buffer);
+    let decoded = Pointers::deserialize_state(&state, &mut deserializer).unwrap();
+    assert_eq!(state.deserialized.get(), 5);
+    assert_eq!(*decoded.shared, CounterValue(1));
+    assert_eq!(*decoded.cell.borrow(), CounterValue(2));
+    assert_eq!(*decoded.locked.lock().unwrap(), CounterValue(3));
+    assert_eq!(*decoded.rw.read().unwrap(), CounterValue(4));
+    assert_eq!(*decoded.cow, CounterValue(5));
+    assert_eq!(decoded.copied.get(), 6);
+
+    let _guard = value.cell.borrow_mut();
+    let mut buffer = Vec::new();
+    let mut serializer = serde_json::Serializer::new(&mut buffer);
+    let err = value
+        .serialize_state(&state, &mut serializer)
+        .expect_err("borrowed cell must not serialize");
+    assert!(
+        err.to_string().contains("already mutably borrowed"),
+        "{err}"
+    );
+}
+
+#[test]
+fn slices_and_str_serialize_and_unsized_pointers_deserialize() {
+    #[derive(SerializeState)]
+    struct Borrowed<'a> {
+        counters: &'a [CounterValue],
+        name: &'a str,
+    }
+
+    #[derive(DeserializeState, Debug, PartialEq)]
+    struct Owned {
+        counters: Box<[CounterValue]>,
+        shared: Arc<[CounterValue]>,
+        name: Rc<str>,
+    }
+
+    let counters = [CounterValue(1), CounterValue(2)];
+    let value = Borrowed {
+        counters: &counters,
+        name: "node",
+    };
+    let state = Recorder::default();
+    let mut buffer = Vec::new();
+    {
+        let mut serializer = serde_json::Serializer::new(&mut buffer);
+        value
+            .serialize_state(&state, &mut serializer)
+            .expect("slice serialization");
+    }
+    assert_eq!(state.serialized.get(), 2);
+    let json_value: serde_json::Value = serde_json::from_slice(&buffer).unwrap();
+    assert_eq!(json_value, json!({"counters": [1, 2], "name": "node"}));
+
+    let state = Recorder::default();
+    let json = r#"{"counters": [1, 2], "shared": [3], "name": "node"}"#;
+    let mut deserializer = serde_json::Deserializer::from_str(json);
+    let decoded = Owned::deserialize_state(&state, &mut deserializer).unwrap();
+    assert_eq!(
+        decoded,
+        Owned {
+            counters: Box::new(counters),
+            shared: Arc::new([CounterValue(3)]),
+            name: Rc::from("node"),
+        }
+    );
+    assert_eq!(state.deserialized.get(), 3);
+}
+
+#[test]
+fn std_passthrough_types_and_ranges() {
+    #[derive(SerializeState, DeserializeState, Debug, PartialEq)]
+    struct StdTypes {
+        ratio: f64,
+        id: std::num::NonZeroU32,
+        timeout: std::time::Duration,
+        path: std::path::PathBuf,
+        addr: std::net::IpAddr,
+        wrapped: std::num::Wrapping<CounterValue>,
+        span: std::ops::Range<CounterValue>,
+        inclusive: std::ops::RangeInclusive<u32>,
+        lower: std::ops::Bound<CounterValue>,
+        upper: std::ops::Bound<CounterValue>,
+    }
+
+    let value = StdTypes {
+        ratio: 0.5,
+        id: std::num::NonZeroU32::new(7).unwrap(),
+        timeout: std::time::Duration::from_secs(3),
+        path: "src/lib.rs".into(),
+        addr: std::net::Ipv4Addr::LOCALHOST.into(),
+        wrapped: std::num::Wrapping(CounterValue(1)),
+        span: CounterValue(2)..CounterValue(3),
+        inclusive: 4..=5,
+        lower: std::ops::Bound::Included(CounterValue(6)),
+        upper: std::ops::Bound::Unbounded,
+    };
+
+    let state = Recorder::default();
+    let mut buffer = Vec::new();
+    {
+        let mut serializer = serde_json::Serializer::new(&mut buffer);
+        value
+            .serialize_state(&state, &mut serializer)
+            .expect("std serialization");
+    }
+    assert_eq!(state.serialized.get(), 4);
+    let json_value: serde_json::Value = serde_json::from_slice(&buffer).unwrap();
+    assert_eq!(
+        json_value,
+        json!({
+            "ratio": 0.5,
+            "id": 7,
+            "timeout": {"secs": 3, "nanos": 0},
+            "path": "src/lib.rs",
+            "addr": "127.0.0.1",
+            "wrapped": 1,
+            "span": {"start": 2, "end": 3},
+            "inclusive": {"start": 4, "end": 5},
+            "lower": {"Included": 6},
+            "upper": "Unbounded",
+        })
+    );
+
+    let state = Recorder::default();
+    let mut deserializer = serde_json::Deserializer::from_slice(&buffer);
+    let decoded = StdTypes::deserialize_state(&state, &mut deserializer).unwrap();
+    assert_eq!(decoded, value);
+    assert_eq!(state.deserialized.get(), 4);
+
+    let ser_state = Recorder::default();
+    let bytes = postcard::to_allocvec(&serde_state::__private::wrap_serialize(&value, &ser_state))
+        .expect("postcard serialize std types");
+    let de_state = Recorder::default();
+    let mut deserializer = postcard::Deserializer::from_bytes(&bytes);
+    let decoded = StdTypes::deserialize_state(&de_state, &mut deserializer)
+        .expect("postcard deserialize std types");
+    assert_eq!(decoded, value);
+    assert_eq!(de_state.deserialized.get(), 4);
+
+    let mut deserializer = serde_json::Deserializer::from_str(r#"{"start": 1, "stop": 2}"#);
+    let err = std::ops::Range::<u32>::deserialize_state(&(), &mut deserializer).unwrap_err();
+    assert!(err.to_string().contains("unknown field `stop`"), "{err}");
+}
+
+#[test]
+fn result_threads_state_into_either_variant() {
+    fn round_trip(value: Result<CounterValue, (CounterValue, u32)>, expected: serde_json::Value) {
+        let state = Recorder::default();
+        let mut buffer = Vec::new();
+        {
+            let mut serializer = serde_json::Serializer::new(&mut buffer);
+            value
+                .serialize_state(&state, &mut serializer)
+                .expect("result serialization");
+        }
+        assert_eq!(state.serialized.get(), 1);
+        let json_value: serde_json::Value = serde_json::from_slice(&buffer).unwrap();
+        assert_eq!(json_value, expected);
+
+        let state = Recorder::default();
+        let mut deserializer = serde_json::Deserializer::from_slice(&buffer);
+        let decoded = Result::<CounterValue, (CounterValue, u32)>::deserialize_state(
+            &state,
+            &mut deserializer,
+        )
+        .unwrap();
+        assert_eq!(decoded, value);
+        assert_eq!(state.deserialized.get(), 1);
+    }
+
+    round_trip(Ok(CounterValue(1)), json!({"Ok": 1}));
+    round_trip(Err((CounterValue(2), 3)), json!({"Err": [2, 3]}));
+}
+
+#[test]
+fn atomics_and_borrowed_slices_pass_through() {
+    use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+    let state = Recorder::default();
+    let json_value = serde_json::to_value(serde_state::ser::StateRef::new(
+        &(AtomicBool::new(true), AtomicU32::new(7)),
+        &state,
+    ))
+    .unwrap();
+    assert_eq!(json_value, json!([true, 7]));
+
+    let mut deserializer = serde_json::Deserializer::from_str("[false, 8]");
+    let (flag, count) =
+        <(AtomicBool, AtomicU32)>::deserialize_state(&state, &mut deserializer).unwrap();
+    assert!(!flag.load(Ordering::Relaxed));
+    assert_eq!(count.load(Ordering::Relaxed), 8);
+
+    let input = r#""borrowed""#;
+    let mut deserializer = serde_json::Deserializer::from_str(input);
+    let name = <&str>::deserialize_state(&state, &mut deserializer).unwrap();
+    assert_eq!(name, "borrowed");
+    assert!(input.as_bytes().as_ptr_range().contains(&name.as_ptr()));
+
+    let bytes = postcard::to_allocvec(&b"raw"[..]).unwrap();
+    let mut deserializer = postcard::Deserializer::from_bytes(&bytes);
+    let raw = <&[u8]>::deserialize_state(&state, &mut deserializer).unwrap();
+    assert_eq!(raw, b"raw");
+
+    let mut deserializer = serde_json::Deserializer::from_str(r#""esc\aped""#);
+    assert!(<&str>::deserialize_state(&state, &mut deserializer).is_err());
+}
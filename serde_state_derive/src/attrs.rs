@@ -1,4 +1,6 @@
-use syn::{Attribute, Ident, LitStr, Path};
+use syn::meta::ParseNestedMeta;
+use syn::punctuated::Punctuated;
+use syn::{parse_quote, Attribute, LitStr, Path, Token, WherePredicate};
 
 #[derive(Clone, Copy, PartialEq, Eq, Default)]
 pub enum ItemMode {
@@ -7,28 +9,98 @@ pub enum ItemMode {
     Stateless,
 }
 
+/// Where the value of a field that is missing from the input comes from.
+#[derive(Clone)]
+pub enum DefaultValue {
+    /// `#[serde(default)]`: `Default::default()`.
+    Default,
+    /// `#[serde(default = "path")]`: `path()`.
+    Path(Path),
+    /// `#[serde_state(default_with_state = "path")]`: `path(state)`.
+    State(Path),
+}
+
+/// A function used in place of a field type's own impls.
+#[derive(Clone)]
+pub enum WithFn {
+    /// `serialize_with`/`deserialize_with`: serde's signatures, without the state.
+    Stateless(Path),
+    /// `serialize_state_with`/`deserialize_state_with`, or a `with` module's functions: the
+    /// state is passed along with the value or deserializer.
+    Stateful(Path),
+}
+
+/// A value that may differ between serialization and deserialization, as in
+/// `rename(serialize = "..", deserialize = "..")`. Either half may be missing.
+#[derive(Clone)]
+pub struct SerDe<T> {
+    pub serialize: Option<T>,
+    pub deserialize: Option<T>,
+}
+
+impl<T> Default for SerDe<T> {
+    fn default() -> Self {
+        SerDe {
+            serialize: None,
+            deserialize: None,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct FieldAttrs {
-    pub rename: Option<String>,
-    pub skip: bool,
+    pub rename: SerDe<String>,
+    pub aliases: Vec<String>,
+    /// `#[serde(skip_serializing)]`, or half of `#[serde(skip)]`.
+    pub skip_serializing: bool,
+    /// `#[serde(skip_deserializing)]`, or half of `#[serde(skip)]`.
+    pub skip_deserializing: bool,
+    /// `#[serde(skip_serializing_if = "path")]`: omitted when `path(&value)` is true.
+    pub skip_serializing_if: Option<Path>,
     pub mode: ItemMode,
-    pub with: Option<Path>,
+    pub serialize_with: Option<WithFn>,
+    pub deserialize_with: Option<WithFn>,
+    pub flatten: bool,
+    pub default: Option<DefaultValue>,
+    /// `#[serde(bound = "..")]`: used instead of the bound inferred for the field's type.
+    pub bound: SerDe<Vec<WherePredicate>>,
+    /// `#[serde(getter = "path")]`: how a remote type's private field is read, `path(&value)`.
+    pub getter: Option<Path>,
 }
 
 impl Default for FieldAttrs {
     fn default() -> Self {
         FieldAttrs {
-            rename: None,
-            skip: false,
+            rename: SerDe::default(),
+            aliases: Vec::new(),
+            skip_serializing: false,
+            skip_deserializing: false,
+            skip_serializing_if: None,
             mode: ItemMode::Stateful,
-            with: None,
+            serialize_with: None,
+            deserialize_with: None,
+            flatten: false,
+            default: None,
+            bound: SerDe::default(),
+            getter: None,
         }
     }
 }
 
 impl FieldAttrs {
-    pub fn key(&self, ident: &Ident) -> String {
-        self.rename.clone().unwrap_or_else(|| ident.to_string())
+    /// The skip attribute set on the field, spelled as written, for error messages.
+    pub fn skip_name(&self) -> Option<&'static str> {
+        if self.skip_serializing && self.skip_deserializing {
+            Some("skip")
+        } else if self.skip_serializing {
+            Some("skip_serializing")
+        } else if self.skip_deserializing {
+            Some("skip_deserializing")
+        } else if self.skip_serializing_if.is_some() {
+            Some("skip_serializing_if")
+        } else {
+            None
+        }
     }
 }
 
@@ -41,17 +113,57 @@ pub fn parse_field_attrs(attrs: &[Attribute], default_mode: ItemMode) -> syn::Re
         if attr.path().is_ident("serde") {
             attr.parse_nested_meta(|meta| {
                 if meta.path.is_ident("rename") {
+                    result.rename = parse_rename(&meta)?;
+                    return Ok(());
+                }
+                if meta.path.is_ident("alias") {
                     let value: LitStr = meta.value()?.parse()?;
-                    result.rename = Some(value.value());
+                    result.aliases.push(value.value());
                     return Ok(());
                 }
                 if meta.path.is_ident("skip") {
-                    result.skip = true;
+                    result.skip_serializing = true;
+                    result.skip_deserializing = true;
                     return Ok(());
                 }
-                if meta.path.is_ident("with") {
+                if meta.path.is_ident("skip_serializing") {
+                    result.skip_serializing = true;
+                    return Ok(());
+                }
+                if meta.path.is_ident("skip_deserializing") {
+                    result.skip_deserializing = true;
+                    return Ok(());
+                }
+                if meta.path.is_ident("skip_serializing_if") {
                     let value: LitStr = meta.value()?.parse()?;
-                    result.with = Some(value.parse()?);
+                    result.skip_serializing_if = Some(value.parse()?);
+                    return Ok(());
+                }
+                if meta.path.is_ident("default") {
+                    if result.default.is_some() {
+                        return Err(meta.error("duplicate `default` attribute"));
+                    }
+                    result.default = Some(parse_default(&meta)?);
+                    return Ok(());
+                }
+                if meta.path.is_ident("flatten") {
+                    result.flatten = true;
+                    return Ok(());
+                }
+                if meta.path.is_ident("bound") {
+                    result.bound = parse_bound(&meta)?;
+                    return Ok(());
+                }
+                if meta.path.is_ident("getter") {
+                    let value: LitStr = meta.value()?.parse()?;
+                    result.getter = Some(value.parse()?);
+                    return Ok(());
+                }
+                if parse_with(
+                    &meta,
+                    &mut result.serialize_with,
+                    &mut result.deserialize_with,
+                )? {
                     return Ok(());
                 }
                 Err(meta.error("unsupported serde attribute"))
@@ -66,6 +178,21 @@ pub fn parse_field_attrs(attrs: &[Attribute], default_mode: ItemMode) -> syn::Re
                     result.mode = ItemMode::Stateful;
                     return Ok(());
                 }
+                if meta.path.is_ident("default_with_state") {
+                    if result.default.is_some() {
+                        return Err(meta.error("duplicate `default` attribute"));
+                    }
+                    let value: LitStr = meta.value()?.parse()?;
+                    result.default = Some(DefaultValue::State(value.parse()?));
+                    return Ok(());
+                }
+                if parse_state_with(
+                    &meta,
+                    &mut result.serialize_with,
+                    &mut result.deserialize_with,
+                )? {
+                    return Ok(());
+                }
                 Ok(())
             })?;
         }
@@ -73,9 +200,148 @@ pub fn parse_field_attrs(attrs: &[Attribute], default_mode: ItemMode) -> syn::Re
     Ok(result)
 }
 
-#[derive(Clone, Copy)]
+/// Parses serde's `with`, `serialize_with` and `deserialize_with`, returning whether `meta` was one
+/// of them.
+fn parse_with(
+    meta: &ParseNestedMeta,
+    serialize_with: &mut Option<WithFn>,
+    deserialize_with: &mut Option<WithFn>,
+) -> syn::Result<bool> {
+    if meta.path.is_ident("with") {
+        let value: LitStr = meta.value()?.parse()?;
+        let module: Path = value.parse()?;
+        set_with(meta, serialize_with, || {
+            WithFn::Stateful(parse_quote!(#module::serialize_state))
+        })?;
+        set_with(meta, deserialize_with, || {
+            WithFn::Stateful(parse_quote!(#module::deserialize_state))
+        })?;
+        return Ok(true);
+    }
+    if meta.path.is_ident("serialize_with") {
+        let value: LitStr = meta.value()?.parse()?;
+        let path = value.parse()?;
+        set_with(meta, serialize_with, || WithFn::Stateless(path))?;
+        return Ok(true);
+    }
+    if meta.path.is_ident("deserialize_with") {
+        let value: LitStr = meta.value()?.parse()?;
+        let path = value.parse()?;
+        set_with(meta, deserialize_with, || WithFn::Stateless(path))?;
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+/// Parses `serialize_state_with` and `deserialize_state_with`, returning whether `meta` was one of
+/// them.
+fn parse_state_with(
+    meta: &ParseNestedMeta,
+    serialize_with: &mut Option<WithFn>,
+    deserialize_with: &mut Option<WithFn>,
+) -> syn::Result<bool> {
+    if meta.path.is_ident("serialize_state_with") {
+        let value: LitStr = meta.value()?.parse()?;
+        let path = value.parse()?;
+        set_with(meta, serialize_with, || WithFn::Stateful(path))?;
+        return Ok(true);
+    }
+    if meta.path.is_ident("deserialize_state_with") {
+        let value: LitStr = meta.value()?.parse()?;
+        let path = value.parse()?;
+        set_with(meta, deserialize_with, || WithFn::Stateful(path))?;
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+/// Records a field's or variant's (de)serialization function, of which there can only be one per
+/// direction.
+fn set_with(
+    meta: &ParseNestedMeta,
+    slot: &mut Option<WithFn>,
+    with: impl FnOnce() -> WithFn,
+) -> syn::Result<()> {
+    if slot.is_some() {
+        return Err(
+            meta.error("only one serialization and one deserialization function can be given")
+        );
+    }
+    *slot = Some(with());
+    Ok(())
+}
+
+/// Parses `default` or `default = "path"`.
+pub fn parse_default(meta: &ParseNestedMeta) -> syn::Result<DefaultValue> {
+    if meta.input.peek(Token![=]) {
+        let value: LitStr = meta.value()?.parse()?;
+        Ok(DefaultValue::Path(value.parse()?))
+    } else {
+        Ok(DefaultValue::Default)
+    }
+}
+
+/// Parses `name = "value"` or `name(serialize = "..", deserialize = "..")`.
+pub fn parse_ser_de(meta: &ParseNestedMeta) -> syn::Result<SerDe<LitStr>> {
+    if meta.input.peek(Token![=]) {
+        let value: LitStr = meta.value()?.parse()?;
+        return Ok(SerDe {
+            serialize: Some(value.clone()),
+            deserialize: Some(value),
+        });
+    }
+    let mut result = SerDe::default();
+    meta.parse_nested_meta(|meta| {
+        let slot = if meta.path.is_ident("serialize") {
+            &mut result.serialize
+        } else if meta.path.is_ident("deserialize") {
+            &mut result.deserialize
+        } else {
+            return Err(meta.error("expected `serialize` or `deserialize`"));
+        };
+        if slot.is_some() {
+            return Err(meta.error("duplicate attribute"));
+        }
+        *slot = Some(meta.value()?.parse()?);
+        Ok(())
+    })?;
+    Ok(result)
+}
+
+/// Parses `bound = "T: Trait, .."` or `bound(serialize = "..", deserialize = "..")`.
+pub fn parse_bound(meta: &ParseNestedMeta) -> syn::Result<SerDe<Vec<WherePredicate>>> {
+    let bounds = parse_ser_de(meta)?;
+    let parse = |bound: Option<LitStr>| {
+        bound
+            .map(|bound| {
+                bound
+                    .parse_with(Punctuated::<WherePredicate, Token![,]>::parse_terminated)
+                    .map(|predicates| predicates.into_iter().collect())
+            })
+            .transpose()
+    };
+    Ok(SerDe {
+        serialize: parse(bounds.serialize)?,
+        deserialize: parse(bounds.deserialize)?,
+    })
+}
+
+fn parse_rename(meta: &ParseNestedMeta) -> syn::Result<SerDe<String>> {
+    let names = parse_ser_de(meta)?;
+    Ok(SerDe {
+        serialize: names.serialize.map(|name| name.value()),
+        deserialize: names.deserialize.map(|name| name.value()),
+    })
+}
+
+#[derive(Clone)]
 pub struct VariantAttrs {
     pub mode: ItemMode,
+    pub untagged: bool,
+    pub rename: SerDe<String>,
+    pub aliases: Vec<String>,
+    pub serialize_with: Option<WithFn>,
+    pub deserialize_with: Option<WithFn>,
 }
 
 impl VariantAttrs {
@@ -88,6 +354,11 @@ impl Default for VariantAttrs {
     fn default() -> Self {
         VariantAttrs {
             mode: ItemMode::Stateful,
+            untagged: false,
+            rename: SerDe::default(),
+            aliases: Vec::new(),
+            serialize_with: None,
+            deserialize_with: None,
         }
     }
 }
@@ -96,9 +367,52 @@ pub fn parse_variant_attrs(
     attrs: &[Attribute],
     default_mode: ItemMode,
 ) -> syn::Result<VariantAttrs> {
-    let mut result = VariantAttrs { mode: default_mode };
+    let mut result = VariantAttrs {
+        mode: default_mode,
+        ..VariantAttrs::default()
+    };
     for attr in attrs {
-        if attr.path().is_ident("serde_state") {
+        if attr.path().is_ident("serde") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("untagged") {
+                    result.untagged = true;
+                    return Ok(());
+                }
+                if meta.path.is_ident("rename") {
+                    result.rename = parse_rename(&meta)?;
+                    return Ok(());
+                }
+                if meta.path.is_ident("alias") {
+                    let value: LitStr = meta.value()?.parse()?;
+                    result.aliases.push(value.value());
+                    return Ok(());
+                }
+                if parse_with(
+                    &meta,
+                    &mut result.serialize_with,
+                    &mut result.deserialize_with,
+                )? {
+                    return Ok(());
+                }
+                // Ignoring these would change what gets written or accepted.
+                for unsupported in [
+                    "skip",
+                    "skip_serializing",
+                    "skip_deserializing",
+                    "other",
+                    "bound",
+                ] {
+                    if meta.path.is_ident(unsupported) {
+                        return Err(meta.error(format!(
+                            "#[serde({})] is not supported on variants",
+                            unsupported
+                        )));
+                    }
+                }
+                // Other serde attributes on variants are ignored.
+                skip_meta(&meta)
+            })?;
+        } else if attr.path().is_ident("serde_state") {
             attr.parse_nested_meta(|meta| {
                 if meta.path.is_ident("stateless") {
                     result.mode = ItemMode::Stateless;
@@ -108,9 +422,26 @@ pub fn parse_variant_attrs(
                     result.mode = ItemMode::Stateful;
                     return Ok(());
                 }
+                if parse_state_with(
+                    &meta,
+                    &mut result.serialize_with,
+                    &mut result.deserialize_with,
+                )? {
+                    return Ok(());
+                }
                 Ok(())
             })?;
         }
     }
     Ok(result)
 }
+
+/// Consumes the value of an attribute that is not handled, if it has one.
+fn skip_meta(meta: &ParseNestedMeta) -> syn::Result<()> {
+    if meta.input.peek(Token![=]) {
+        meta.value()?.parse::<syn::Expr>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        meta.input.parse::<proc_macro2::TokenTree>()?;
+    }
+    Ok(())
+}
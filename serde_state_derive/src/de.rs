@@ -1,14 +1,16 @@
 use crate::{
-    attrs::ItemMode,
-    dummy,
+    attrs::{DefaultValue, ItemMode, WithFn},
+    dummy, pretend,
     type_decl::{
-        EnumDecl, FieldDecl, FieldsDecl, FieldsStyle, StructDecl, TypeData, TypeDecl, VariantDecl,
+        EnumDecl, FieldDecl, FieldsDecl, FieldsStyle, FromProxy, StructDecl, TagType, TypeData,
+        TypeDecl, VariantDecl,
     },
 };
 use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
+use syn::ext::IdentExt;
 use syn::spanned::Spanned;
-use syn::{Data, DeriveInput, GenericParam, Generics, Type, parse_quote};
+use syn::{Data, DeriveInput, GenericParam, Generics, Type, WherePredicate, parse_quote};
 
 pub fn expand_derive_deserialize(input: &DeriveInput) -> syn::Result<TokenStream> {
     if let Data::Union(u) = &input.data {
@@ -19,9 +21,42 @@ pub fn expand_derive_deserialize(input: &DeriveInput) -> syn::Result<TokenStream
     }
 
     let decl = TypeDecl::from_derive_input(input)?;
-    let impl_block = match &decl.data {
-        TypeData::Struct(data) => derive_struct(&decl, data)?,
-        TypeData::Enum(data) => derive_enum(&decl, data)?,
+    let parts = match (&decl.attrs.from, &decl.data) {
+        (Some(from), _) => derive_from(&decl, from),
+        (None, TypeData::Struct(data)) => derive_struct(&decl, data)?,
+        (None, TypeData::Enum(data)) => derive_enum(&decl, data)?,
+    };
+    let impl_block = match &decl.attrs.remote {
+        Some(remote) => remote_deserialize_fn(&decl, remote, &parts),
+        None => {
+            let DeserializeImpl {
+                generics,
+                where_clause,
+                body,
+            } = &parts;
+            let (impl_generics, _, _) = generics.split_for_impl();
+            let (_, ty_generics, _) = decl.generics.split_for_impl();
+            let where_clause_tokens = quote_where_clause(where_clause);
+            let state_tokens = state_type_tokens(&decl);
+            let ident = decl.ident;
+            let default_deser_impl = default_deserialize_impl(&decl, ident);
+            quote! {
+                #[automatically_derived]
+                impl #impl_generics _serde_state::DeserializeState<'de, #state_tokens> for #ident #ty_generics #where_clause_tokens {
+                    fn deserialize_state<__D>(
+                        __state: &#state_tokens,
+                        __deserializer: __D,
+                    ) -> ::core::result::Result<Self, __D::Error>
+                    where
+                        __D: _serde::Deserializer<'de>,
+                    {
+                        #body
+                    }
+                }
+
+                #default_deser_impl
+            }
+        }
     };
 
     Ok(dummy::wrap_in_const(
@@ -30,36 +65,235 @@ pub fn expand_derive_deserialize(input: &DeriveInput) -> syn::Result<TokenStream
     ))
 }
 
-fn derive_struct(decl: &TypeDecl, data: &StructDecl) -> syn::Result<TokenStream> {
+/// What goes into `deserialize_state`: the impl's generics, including `'de` and the state
+/// parameter, its where clause and the body of the method.
+struct DeserializeImpl {
+    generics: Generics,
+    where_clause: Option<syn::WhereClause>,
+    body: TokenStream,
+}
+
+/// For `#[serde(remote = "Remote")]`, emits `Def::deserialize_state(state, deserializer)`
+/// returning a `Remote`, for use with `#[serde(with = "Def")]`. The body goes into a private trait
+/// implemented for the remote type, in a scope where `Def` names it, so that it builds a `Remote`
+/// directly. With getters the fields may be private, so `Def` is built and converted with `Into`.
+fn remote_deserialize_fn(
+    decl: &TypeDecl,
+    remote: &syn::Path,
+    parts: &DeserializeImpl,
+) -> TokenStream {
+    let DeserializeImpl {
+        generics,
+        where_clause,
+        body,
+    } = parts;
+    let ident = decl.ident;
+    let vis = decl.vis;
+    let (impl_generics, _, _) = generics.split_for_impl();
+    let (decl_impl_generics, ty_generics, decl_where_clause) = decl.generics.split_for_impl();
+    let where_clause_tokens = quote_where_clause(where_clause);
+    let predicates = where_clause.iter().flat_map(|clause| &clause.predicates);
+    let state_tokens = state_type_tokens(decl);
+    let state_param = match decl.attrs.state {
+        Some(_) => None,
+        None => {
+            let bound = state_bound_clause(decl.attrs.state_bound.as_ref());
+            Some(quote!(__State: ?Sized #bound,))
+        }
+    };
+    let has_getters = match &decl.data {
+        TypeData::Struct(data) => data
+            .fields
+            .fields
+            .iter()
+            .any(|field| field.attrs.getter.is_some()),
+        TypeData::Enum(_) => false,
+    };
+    let (alias, call) = if has_getters {
+        (
+            None,
+            quote! {
+                <Self as __DeserializeStateRemote<'de, #state_tokens>>::deserialize_state(
+                    __state,
+                    __deserializer,
+                )
+                .map(::core::convert::Into::into)
+            },
+        )
+    } else {
+        (
+            Some(remote_alias(decl, remote)),
+            quote! {
+                <#remote #ty_generics as __DeserializeStateRemote<'de, #state_tokens>>::deserialize_state(
+                    __state,
+                    __deserializer,
+                )
+            },
+        )
+    };
+
+    let pretend = pretend::pretend_used(decl);
+
+    quote! {
+        trait __DeserializeStateRemote<'de, __State: ?Sized>: Sized {
+            fn deserialize_state<__D>(
+                __state: &__State,
+                __deserializer: __D,
+            ) -> ::core::result::Result<Self, __D::Error>
+            where
+                __D: _serde::Deserializer<'de>;
+        }
+
+        const _: () = {
+            #alias
+
+            impl #impl_generics __DeserializeStateRemote<'de, #state_tokens> for #ident #ty_generics #where_clause_tokens {
+                fn deserialize_state<__D>(
+                    __state: &#state_tokens,
+                    __deserializer: __D,
+                ) -> ::core::result::Result<Self, __D::Error>
+                where
+                    __D: _serde::Deserializer<'de>,
+                {
+                    #body
+                }
+            }
+        };
+
+        #[automatically_derived]
+        impl #decl_impl_generics #ident #ty_generics #decl_where_clause {
+            #vis fn deserialize_state<'de, #state_param __D>(
+                __state: &#state_tokens,
+                __deserializer: __D,
+            ) -> ::core::result::Result<#remote #ty_generics, __D::Error>
+            where
+                #(#predicates,)*
+                __D: _serde::Deserializer<'de>,
+            {
+                #pretend
+                #call
+            }
+        }
+    }
+}
+
+/// `type Def<T> = Remote<T>;`, shadowing the local definition. Tuple and unit structs are built
+/// with braces, as in `Def { 0: value }`, since an alias can't be called as a constructor.
+fn remote_alias(decl: &TypeDecl, remote: &syn::Path) -> TokenStream {
+    let ident = decl.ident;
+    let (_, ty_generics, _) = decl.generics.split_for_impl();
+    let params = decl.generics.params.iter().map(|param| match param {
+        GenericParam::Lifetime(param) => {
+            let lifetime = &param.lifetime;
+            quote!(#lifetime)
+        }
+        GenericParam::Type(param) => {
+            let ident = &param.ident;
+            quote!(#ident)
+        }
+        GenericParam::Const(param) => {
+            let ident = &param.ident;
+            let ty = &param.ty;
+            quote!(const #ident: #ty)
+        }
+    });
+    quote! {
+        type #ident <#(#params),*> = #remote #ty_generics;
+    }
+}
+
+/// Deserializes the proxy from `#[serde(from)]`, `#[serde(try_from)]`,
+/// `#[serde_state(from_state)]` or `try_from_state` and converts it, bounded only by the proxy's
+/// impl.
+fn derive_from(decl: &TypeDecl, from: &FromProxy) -> DeserializeImpl {
+    let generics = add_state_param(
+        decl.generics,
+        decl.attrs.state.is_none(),
+        decl.attrs.state_bound.as_ref(),
+    );
+    let mut where_clause = decl.generics.where_clause.clone();
+    let state_tokens = state_type_tokens(decl);
+    match (&decl.attrs.bound.deserialize, from) {
+        (Some(bound), _) => add_where_predicates(&mut where_clause, bound),
+        (
+            None,
+            FromProxy::From(ty)
+            | FromProxy::TryFrom(ty)
+            | FromProxy::State(_, Some(ty))
+            | FromProxy::TryState(_, Some(ty)),
+        ) => add_where_predicates(
+            &mut where_clause,
+            &[parse_quote!(#ty: _serde_state::DeserializeState<'de, #state_tokens>)],
+        ),
+        // The proxy type is only known from the function's signature, and the state is concrete.
+        (None, FromProxy::State(_, None) | FromProxy::TryState(_, None)) => {}
+    }
+    let body = match from {
+        FromProxy::From(ty) => quote! {
+            let __proxy = <#ty as _serde_state::DeserializeState<'de, #state_tokens>>::deserialize_state(
+                __state,
+                __deserializer,
+            )?;
+            ::core::result::Result::Ok(<Self as ::core::convert::From<#ty>>::from(__proxy))
+        },
+        FromProxy::TryFrom(ty) => quote! {
+            let __proxy = <#ty as _serde_state::DeserializeState<'de, #state_tokens>>::deserialize_state(
+                __state,
+                __deserializer,
+            )?;
+            <Self as ::core::convert::TryFrom<#ty>>::try_from(__proxy)
+                .map_err(_serde::de::Error::custom)
+        },
+        FromProxy::State(path, ty) => {
+            let ty = ty.as_ref().map(|ty| quote!(: #ty));
+            quote! {
+                let __proxy #ty = _serde_state::DeserializeState::deserialize_state(__state, __deserializer)?;
+                ::core::result::Result::Ok(#path(__proxy, __state))
+            }
+        }
+        FromProxy::TryState(path, ty) => {
+            let ty = ty.as_ref().map(|ty| quote!(: #ty));
+            quote! {
+                let __proxy #ty = _serde_state::DeserializeState::deserialize_state(__state, __deserializer)?;
+                #path(__proxy, __state).map_err(_serde::de::Error::custom)
+            }
+        }
+    };
+    DeserializeImpl {
+        generics,
+        where_clause,
+        body,
+    }
+}
+
+fn derive_struct(decl: &TypeDecl, data: &StructDecl) -> syn::Result<DeserializeImpl> {
     let has_explicit_state = decl.attrs.state.is_some();
-    let has_state_bound = decl.attrs.state_bound.is_some();
     let uses_generic_state = !has_explicit_state;
-    let infer_bounds = !has_explicit_state && !has_state_bound;
     let impl_generics_with_state = add_state_param(
         decl.generics,
         uses_generic_state,
         decl.attrs.state_bound.as_ref(),
     );
-    let (impl_generics_ref, _, _) = impl_generics_with_state.split_for_impl();
-    let impl_generics = quote!(#impl_generics_ref);
     let (_, ty_generics_ref, _) = decl.generics.split_for_impl();
     let ty_generics = quote!(#ty_generics_ref);
     let mut where_clause = decl.generics.where_clause.clone();
     let state_tokens = state_type_tokens(decl);
     let field_types = collect_field_types_from_fields(&data.fields);
-    let explicit_state = decl.attrs.state.as_ref();
-    if infer_bounds {
-        add_deserialize_bounds_from_types(&mut where_clause, &field_types, &state_tokens);
-    } else {
-        add_deserialize_bounds_from_type_params(
+    add_deserialize_bounds(&mut where_clause, decl, &field_types, &state_tokens);
+    if flattened_option_rollback(&data.fields) {
+        add_rollback_bound(&mut where_clause, &state_tokens);
+    }
+    if decl.attrs.bound.deserialize.is_none() {
+        add_default_bounds(
+            &data.fields,
+            decl.attrs.default.is_some(),
             &mut where_clause,
-            decl.generics,
-            &state_tokens,
-            decl.attrs.mode,
         );
+        if let Some(DefaultValue::Default) = decl.attrs.default {
+            let ident = decl.ident;
+            push_default_bound(&mut where_clause, &parse_quote!(#ident #ty_generics));
+        }
     }
-    add_default_bounds_for_skipped(&data.fields, &mut where_clause);
-    let where_clause_tokens = quote_where_clause(&where_clause);
     let ident = decl.ident;
 
     let body = if decl.attrs.transparent {
@@ -68,95 +302,67 @@ fn derive_struct(decl: &TypeDecl, data: &StructDecl) -> syn::Result<TokenStream>
         deserialize_struct_body(
             ident,
             &data.fields,
+            decl.attrs.default.as_ref(),
             &state_tokens,
-            explicit_state,
             decl.generics,
             uses_generic_state,
             decl.attrs.state_bound.as_ref(),
             &where_clause,
         )
     };
-    let default_deser_impl = default_deserialize_impl(decl, ident);
-
-    Ok(quote! {
-        #[automatically_derived]
-        impl #impl_generics _serde_state::DeserializeState<'de, #state_tokens> for #ident #ty_generics #where_clause_tokens {
-            fn deserialize_state<__D>(
-                __state: &#state_tokens,
-                __deserializer: __D,
-            ) -> ::core::result::Result<Self, __D::Error>
-            where
-                __D: _serde::Deserializer<'de>,
-            {
-                #body
-            }
-        }
-
-        #default_deser_impl
+    Ok(DeserializeImpl {
+        generics: impl_generics_with_state,
+        where_clause,
+        body,
     })
 }
 
-fn derive_enum(decl: &TypeDecl, data: &EnumDecl) -> syn::Result<TokenStream> {
+fn derive_enum(decl: &TypeDecl, data: &EnumDecl) -> syn::Result<DeserializeImpl> {
     let has_explicit_state = decl.attrs.state.is_some();
-    let has_state_bound = decl.attrs.state_bound.is_some();
     let uses_generic_state = !has_explicit_state;
-    let infer_bounds = !has_explicit_state && !has_state_bound;
     let impl_generics_with_state = add_state_param(
         decl.generics,
         uses_generic_state,
         decl.attrs.state_bound.as_ref(),
     );
-    let (impl_generics_ref, _, _) = impl_generics_with_state.split_for_impl();
-    let impl_generics = quote!(#impl_generics_ref);
-    let (_, ty_generics_ref, _) = decl.generics.split_for_impl();
-    let ty_generics = quote!(#ty_generics_ref);
     let mut where_clause = decl.generics.where_clause.clone();
     let state_tokens = state_type_tokens(decl);
     let field_types = collect_field_types_from_enum(data);
-    let explicit_state = decl.attrs.state.as_ref();
-    if infer_bounds {
-        add_deserialize_bounds_from_types(&mut where_clause, &field_types, &state_tokens);
-    } else {
-        add_deserialize_bounds_from_type_params(
-            &mut where_clause,
-            decl.generics,
-            &state_tokens,
-            decl.attrs.mode,
-        );
+    add_deserialize_bounds(&mut where_clause, decl, &field_types, &state_tokens);
+    if decl.attrs.bound.deserialize.is_none() {
+        for variant in &data.variants {
+            add_default_bounds(&variant.fields, false, &mut where_clause);
+        }
     }
-    for variant in &data.variants {
-        add_default_bounds_for_skipped(&variant.fields, &mut where_clause);
+    let has_untagged =
+        matches!(decl.attrs.tag, TagType::Untagged) || !data.split_untagged().1.is_empty();
+    let rollback =
+        has_untagged && !decl.attrs.no_rollback && data.variants.iter().any(variant_reads_state);
+    if rollback
+        || data
+            .variants
+            .iter()
+            .any(|variant| flattened_option_rollback(&variant.fields))
+    {
+        add_rollback_bound(&mut where_clause, &state_tokens);
     }
-    let where_clause_tokens = quote_where_clause(&where_clause);
     let ident = decl.ident;
 
     let body = deserialize_enum_body(
         ident,
         data,
+        &decl.attrs.tag,
+        rollback,
         &state_tokens,
-        explicit_state,
         decl.generics,
         uses_generic_state,
         decl.attrs.state_bound.as_ref(),
         &where_clause,
     );
-    let default_deser_impl = default_deserialize_impl(decl, ident);
-
-    Ok(quote! {
-        #[automatically_derived]
-        impl #impl_generics _serde_state::DeserializeState<'de, #state_tokens> for #ident #ty_generics #where_clause_tokens {
-            fn deserialize_state<__D>(
-                __state: &#state_tokens,
-                __deserializer: __D,
-            ) -> ::core::result::Result<Self, __D::Error>
-            where
-                __D: _serde::Deserializer<'de>,
-            {
-                #body
-            }
-        }
-
-        #default_deser_impl
+    Ok(DeserializeImpl {
+        generics: impl_generics_with_state,
+        where_clause,
+        body,
     })
 }
 
@@ -170,15 +376,16 @@ fn deserialize_transparent(
             let field = &fields.fields[0];
             let field_ident = field.ident().unwrap();
             let ty = field.ty();
-            if let Some(with) = &field.attrs.with {
+            if let Some(with) = &field.attrs.deserialize_with {
+                let call = deserialize_with_call(with, quote!(__state));
                 Ok(quote! {
-                    let #field_ident: #ty = #with::deserialize_state(__state, __deserializer)?;
+                    let #field_ident: #ty = #call?;
                     ::core::result::Result::Ok(#ident { #field_ident: #field_ident })
                 })
             } else {
                 Ok(match field.mode() {
                     ItemMode::Stateful => quote! {
-                        let __seed = _serde_state::__private::wrap_deserialize_seed::<#ty, #state_tokens>(__state);
+                        let __seed = _serde_state::de::StateSeed::<#ty, #state_tokens>::new(__state);
                         let #field_ident = _serde::de::DeserializeSeed::deserialize(__seed, __deserializer)?;
                         ::core::result::Result::Ok(#ident { #field_ident: #field_ident })
                     },
@@ -192,21 +399,22 @@ fn deserialize_transparent(
         FieldsStyle::Unnamed if fields.fields.len() == 1 => {
             let field = &fields.fields[0];
             let ty = field.ty();
-            if let Some(with) = &field.attrs.with {
+            if let Some(with) = &field.attrs.deserialize_with {
+                let call = deserialize_with_call(with, quote!(__state));
                 Ok(quote! {
-                    let __value: #ty = #with::deserialize_state(__state, __deserializer)?;
-                    ::core::result::Result::Ok(#ident(__value))
+                    let __value: #ty = #call?;
+                    ::core::result::Result::Ok(#ident { 0: __value })
                 })
             } else {
                 Ok(match field.mode() {
                     ItemMode::Stateful => quote! {
-                        let __seed = _serde_state::__private::wrap_deserialize_seed::<#ty, #state_tokens>(__state);
+                        let __seed = _serde_state::de::StateSeed::<#ty, #state_tokens>::new(__state);
                         let __value = _serde::de::DeserializeSeed::deserialize(__seed, __deserializer)?;
-                        ::core::result::Result::Ok(#ident(__value))
+                        ::core::result::Result::Ok(#ident { 0: __value })
                     },
                     ItemMode::Stateless => quote! {
                         let __value: #ty = _serde::Deserialize::deserialize(__deserializer)?;
-                        ::core::result::Result::Ok(#ident(__value))
+                        ::core::result::Result::Ok(#ident { 0: __value })
                     },
                 })
             }
@@ -221,8 +429,8 @@ fn deserialize_transparent(
 fn deserialize_struct_body(
     ident: &syn::Ident,
     fields: &FieldsDecl<'_>,
+    container_default: Option<&DefaultValue>,
     state_tokens: &TokenStream,
-    explicit_state: Option<&Type>,
     generics: &Generics,
     include_state_param: bool,
     state_bound: Option<&Type>,
@@ -232,8 +440,10 @@ fn deserialize_struct_body(
         FieldsStyle::Named => deserialize_named_struct(
             ident,
             &fields.fields,
+            fields.deny_unknown_fields,
+            fields.no_rollback,
+            container_default,
             state_tokens,
-            explicit_state,
             generics,
             include_state_param,
             state_bound,
@@ -243,7 +453,6 @@ fn deserialize_struct_body(
             ident,
             &fields.fields,
             state_tokens,
-            explicit_state,
             generics,
             include_state_param,
             state_bound,
@@ -263,12 +472,121 @@ fn deserialize_identifier_or_u32(deserializer: TokenStream, visitor: TokenStream
     }
 }
 
+/// How a field identifier visitor handles a name no field claims: the default skips its
+/// value, while `#[serde(deny_unknown_fields)]` reports it like serde_derive does.
+struct UnknownFieldArms {
+    /// The `__Ignore` variant of the field enum, if unknown names are skipped.
+    variant: TokenStream,
+    /// The fallback of `visit_str`.
+    visit_str: TokenStream,
+    /// The fallback of `visit_u64`.
+    visit_u64: TokenStream,
+    /// The arm of `visit_map` that skips the value of an ignored key.
+    map_arm: TokenStream,
+}
+
+fn unknown_field_arms(
+    field_enum: &TokenStream,
+    fields_const: &TokenStream,
+    len: usize,
+    deny_unknown_fields: bool,
+) -> UnknownFieldArms {
+    if deny_unknown_fields {
+        let expecting = format!("field index 0 <= i < {}", len);
+        UnknownFieldArms {
+            variant: quote!(),
+            visit_str: quote! {
+                ::core::result::Result::Err(_serde::de::Error::unknown_field(value, #fields_const))
+            },
+            visit_u64: quote! {
+                ::core::result::Result::Err(_serde::de::Error::invalid_value(
+                    _serde::de::Unexpected::Unsigned(value),
+                    &#expecting,
+                ))
+            },
+            map_arm: quote!(),
+        }
+    } else {
+        UnknownFieldArms {
+            variant: quote!(__Ignore),
+            visit_str: quote!(::core::result::Result::Ok(#field_enum::__Ignore)),
+            visit_u64: quote!(::core::result::Result::Ok(#field_enum::__Ignore)),
+            map_arm: quote! {
+                #field_enum::__Ignore => {
+                    let _ = _serde::de::MapAccess::next_value::<_serde::de::IgnoredAny>(&mut __map)?;
+                }
+            },
+        }
+    }
+}
+
+/// The value of a field that is missing from the input, or `None` if the field is required.
+fn missing_field_value(field: &FieldDecl<'_>, container_default: bool) -> Option<TokenStream> {
+    let ident = field.ident().unwrap();
+    match &field.attrs.default {
+        Some(DefaultValue::Default) => Some(quote!(::core::default::Default::default())),
+        Some(DefaultValue::Path(path)) => Some(quote!(#path())),
+        Some(DefaultValue::State(path)) => Some(quote!(#path(state))),
+        None if container_default => Some(quote!(__default.#ident)),
+        None if field.attrs.skip_deserializing => Some(quote!(::core::default::Default::default())),
+        None => None,
+    }
+}
+
+/// Builds the container's `#[serde(default)]` value, which supplies the missing fields.
+fn container_default_init(default: Option<&DefaultValue>) -> TokenStream {
+    match default {
+        Some(DefaultValue::Path(path)) => quote!(let __default: Self::Value = #path();),
+        Some(_) => quote!(let __default: Self::Value = ::core::default::Default::default();),
+        None => TokenStream::new(),
+    }
+}
+
+/// Unwraps a field's `Option` local once the map has been read, falling back to its default.
+///
+/// Fields without a default are deserialized from a `MissingFieldDeserializer`, which makes
+/// `Option` fields `None` and reports any other field as missing, like serde_derive does.
+fn build_field(
+    field: &FieldDecl<'_>,
+    container_default: bool,
+    state_tokens: &TokenStream,
+    container: &ContainerGenerics<'_>,
+) -> TokenStream {
+    let ident = field.ident().unwrap();
+    let missing = missing_field_value(field, container_default);
+    if field.attrs.skip_deserializing {
+        return quote! {
+            let #ident = #missing;
+        };
+    }
+    let missing = missing.unwrap_or_else(|| {
+        let name = &field.name.deserialize;
+        if field.attrs.deserialize_with.is_some() {
+            return quote! {
+                return ::core::result::Result::Err(_serde::de::Error::missing_field(#name))
+            };
+        }
+        let value = deserialize_newtype_content(field, state_tokens, container);
+        quote! {{
+            let __deserializer =
+                _serde_state::__private::de::MissingFieldDeserializer::<__M::Error>::new(#name);
+            #value
+        }}
+    });
+    quote! {
+        let #ident = match #ident {
+            ::core::option::Option::Some(value) => value,
+            ::core::option::Option::None => #missing,
+        };
+    }
+}
+
 fn seq_read_fields_body(
     fields: &[FieldDecl<'_>],
     included: &[&FieldDecl<'_>],
+    container_default: Option<&DefaultValue>,
     state_tokens: &TokenStream,
-    explicit_state: Option<&Type>,
-    state_bound: Option<&Type>,
+    container: &ContainerGenerics<'_>,
     construct: TokenStream,
 ) -> TokenStream {
     let included_len = included.len();
@@ -276,44 +594,47 @@ fn seq_read_fields_body(
         let ident = field.ident().unwrap();
         let ty = field.ty();
         let idx = seq_index;
-        if field.attrs.with.is_some() {
-            let seed = with_deserialize_seed(field, explicit_state, state_bound);
+        let missing = match missing_field_value(field, container_default.is_some()) {
+            Some(value) => value,
+            None => quote! {
+                return ::core::result::Result::Err(_serde::de::Error::invalid_length(#idx, &self))
+            },
+        };
+        if field.attrs.deserialize_with.is_some() {
+            let seed = with_deserialize_seed(field, state_tokens, container);
             quote! {
                 let __seed = #seed;
                 let #ident = match _serde::de::SeqAccess::next_element_seed(&mut __seq, __seed)? {
                     ::core::option::Option::Some(value) => value,
-                    ::core::option::Option::None =>
-                        return ::core::result::Result::Err(_serde::de::Error::invalid_length(#idx, &self)),
+                    ::core::option::Option::None => #missing,
                 };
             }
         } else {
             match field.mode() {
                 ItemMode::Stateful => quote! {
-                    let __seed = _serde_state::__private::wrap_deserialize_seed::<#ty, #state_tokens>(state);
+                    let __seed = _serde_state::de::StateSeed::<#ty, #state_tokens>::new(state);
                     let #ident = match _serde::de::SeqAccess::next_element_seed(&mut __seq, __seed)? {
                         ::core::option::Option::Some(value) => value,
-                        ::core::option::Option::None =>
-                            return ::core::result::Result::Err(_serde::de::Error::invalid_length(#idx, &self)),
+                        ::core::option::Option::None => #missing,
                     };
                 },
                 ItemMode::Stateless => quote! {
                     let #ident = match _serde::de::SeqAccess::next_element::<#ty>(&mut __seq)? {
                         ::core::option::Option::Some(value) => value,
-                        ::core::option::Option::None =>
-                            return ::core::result::Result::Err(_serde::de::Error::invalid_length(#idx, &self)),
+                        ::core::option::Option::None => #missing,
                     };
                 },
             }
         }
     });
-    let init_skipped = fields.iter().filter(|field| field.attrs.skip).map(|field| {
-        let ident = field.ident().unwrap();
-        quote! {
-            let #ident = ::core::default::Default::default();
-        }
-    });
+    let init_skipped = fields
+        .iter()
+        .filter(|field| field.attrs.skip_deserializing)
+        .map(|field| build_field(field, container_default.is_some(), state_tokens, container));
+    let default_init = container_default_init(container_default);
     quote! {
         let state = self.state;
+        #default_init
         #(#read_included)*
         if let ::core::option::Option::Some(_) =
             _serde::de::SeqAccess::next_element::<_serde::de::IgnoredAny>(&mut __seq)?
@@ -328,24 +649,72 @@ fn seq_read_fields_body(
 fn deserialize_named_struct(
     ident: &syn::Ident,
     fields: &[FieldDecl<'_>],
+    deny_unknown_fields: bool,
+    no_rollback: bool,
+    container_default: Option<&DefaultValue>,
     state_tokens: &TokenStream,
-    explicit_state: Option<&Type>,
     generics: &Generics,
     include_state_param: bool,
     state_bound: Option<&Type>,
     where_clause: &Option<syn::WhereClause>,
 ) -> TokenStream {
-    let included: Vec<_> = fields.iter().filter(|field| !field.attrs.skip).collect();
+    let container = ContainerGenerics {
+        ident,
+        generics,
+        include_state_param,
+        state_bound,
+        where_clause,
+    };
+    if fields.iter().any(|field| field.attrs.flatten) {
+        let construct = {
+            let pairs = fields.iter().map(|field| {
+                let ident = field.ident().unwrap();
+                quote!(#ident: #ident)
+            });
+            quote!(#ident { #(#pairs),* })
+        };
+        let visitor = flattened_fields_visitor(
+            ident,
+            fields,
+            deny_unknown_fields,
+            no_rollback,
+            container_default,
+            construct,
+            &format!("struct {}", ident),
+            &format_ident!("__Visitor"),
+            state_tokens,
+            generics,
+            include_state_param,
+            state_bound,
+            where_clause,
+        );
+        return quote! {
+            #visitor
+
+            _serde::Deserializer::deserialize_map(
+                __deserializer,
+                __Visitor {
+                    state: __state,
+                    _marker: ::core::marker::PhantomData,
+                },
+            )
+        };
+    }
+
+    let included: Vec<_> = fields
+        .iter()
+        .filter(|field| !field.attrs.skip_deserializing)
+        .collect();
 
-    let field_names: Vec<String> = included
+    let field_names: Vec<&str> = included
         .iter()
-        .map(|field| field.attrs.key(field.ident().unwrap()))
+        .flat_map(|field| field.name.deserialize_names())
         .collect();
 
     let field_variants: Vec<_> = included
         .iter()
         .map(|field| {
-            let name = field.ident().unwrap().to_string();
+            let name = field.ident().unwrap().unraw();
             format_ident!("__field_{}", name)
         })
         .collect();
@@ -357,22 +726,34 @@ fn deserialize_named_struct(
         }
     };
 
+    let UnknownFieldArms {
+        variant: ignore_variant,
+        visit_str: unknown_str,
+        visit_u64: unknown_index,
+        map_arm: ignore_arm,
+    } = unknown_field_arms(
+        &quote!(__Field),
+        &quote!(__FIELDS),
+        field_variants.len(),
+        deny_unknown_fields,
+    );
     let field_enum = {
         let variants = field_variants.iter();
         quote! {
             #[allow(non_camel_case_types)]
-            enum __Field { #(#variants,)* __Ignore }
+            enum __Field { #(#variants,)* #ignore_variant }
         }
     };
 
     let field_visitor = {
         let deserialize_field =
             deserialize_identifier_or_u32(quote!(deserializer), quote!(__FieldVisitor));
-        let match_arms = field_names
+        let match_arms = included
             .iter()
             .zip(field_variants.iter())
-            .map(|(name, variant)| {
-                quote! { #name => ::core::result::Result::Ok(__Field::#variant) }
+            .map(|(field, variant)| {
+                let names = field.name.deserialize_names();
+                quote! { #(#names)|* => ::core::result::Result::Ok(__Field::#variant) }
             });
         let index_match_arms = field_variants.iter().enumerate().map(|(index, variant)| {
             let index = index as u64;
@@ -393,7 +774,7 @@ fn deserialize_named_struct(
                 {
                     match value {
                         #(#match_arms,)*
-                        _ => ::core::result::Result::Ok(__Field::__Ignore),
+                        _ => #unknown_str,
                     }
                 }
 
@@ -403,7 +784,7 @@ fn deserialize_named_struct(
                 {
                     match value {
                         #(#index_match_arms,)*
-                        _ => ::core::result::Result::Ok(__Field::__Ignore),
+                        _ => #unknown_index,
                     }
                 }
 
@@ -428,7 +809,7 @@ fn deserialize_named_struct(
 
     let init_locals = fields.iter().map(|field| {
         let ident = field.ident().unwrap();
-        if field.attrs.skip {
+        if field.attrs.skip_deserializing {
             quote!()
         } else {
             quote!(let mut #ident = ::core::option::Option::None;)
@@ -446,9 +827,9 @@ fn deserialize_named_struct(
     let seq_read_fields = seq_read_fields_body(
         fields,
         &included,
+        container_default,
         state_tokens,
-        explicit_state,
-        state_bound,
+        &container,
         construct.clone(),
     );
 
@@ -457,31 +838,9 @@ fn deserialize_named_struct(
         .zip(field_variants.iter())
         .map(|(field, variant)| {
             let ident = field.ident().unwrap();
-            let name = field.attrs.key(ident);
-            let ty = field.ty();
-            let assignment = if field.attrs.with.is_some() {
-                let seed = with_deserialize_seed(field, explicit_state, state_bound);
-                quote! {
-                    let __seed = #seed;
-                    #ident = ::core::option::Option::Some(
-                        _serde::de::MapAccess::next_value_seed(&mut __map, __seed)?,
-                    );
-                }
-            } else {
-                match field.mode() {
-                    ItemMode::Stateful => quote! {
-                        let __seed = _serde_state::__private::wrap_deserialize_seed::<#ty, #state_tokens>(state);
-                        #ident = ::core::option::Option::Some(
-                            _serde::de::MapAccess::next_value_seed(&mut __map, __seed)?,
-                        );
-                    },
-                    ItemMode::Stateless => quote! {
-                        #ident = ::core::option::Option::Some(
-                            _serde::de::MapAccess::next_value::<#ty>(&mut __map)?,
-                        );
-                    },
-                }
-            };
+            let name = &field.name.deserialize;
+            let assignment =
+                map_value_assignment(field, state_tokens, &container);
             quote! {
                 __Field::#variant => {
                     if #ident.is_some() {
@@ -492,24 +851,11 @@ fn deserialize_named_struct(
             }
         });
 
-    let build_fields = fields.iter().map(|field| {
-        let ident = field.ident().unwrap();
-        if field.attrs.skip {
-            quote! {
-                let #ident = ::core::default::Default::default();
-            }
-        } else {
-            let name = field.attrs.key(ident);
-            quote! {
-                let #ident = match #ident {
-                    ::core::option::Option::Some(value) => value,
-                    ::core::option::Option::None =>
-                        return ::core::result::Result::Err(_serde::de::Error::missing_field(#name)),
-                };
-            }
-        }
-    });
+    let build_fields = fields
+        .iter()
+        .map(|field| build_field(field, container_default.is_some(), state_tokens, &container));
 
+    let default_init = container_default_init(container_default);
     let (visitor_struct_generics, _) =
         visitor_struct_generics_tokens(generics, include_state_param, state_bound);
     let (visitor_impl_generics, visitor_impl_type_generics) =
@@ -539,15 +885,14 @@ fn deserialize_named_struct(
                 __M: _serde::de::MapAccess<'de>,
             {
                 let state = self.state;
+                #default_init
                 #(#init_locals)*
                 while let ::core::option::Option::Some(__key) =
                     _serde::de::MapAccess::next_key::<__Field>(&mut __map)?
                 {
                     match __key {
                         #(#match_arms)*
-                        __Field::__Ignore => {
-                            let _ = _serde::de::MapAccess::next_value::<_serde::de::IgnoredAny>(&mut __map)?;
-                        }
+                        #ignore_arm
                     }
                 }
                 #(#build_fields)*
@@ -584,36 +929,254 @@ fn deserialize_named_struct(
     }
 }
 
-fn deserialize_unnamed_struct(
+/// Reads the value of the current map entry into the field's `Option` local.
+fn map_value_assignment(
+    field: &FieldDecl<'_>,
+    state_tokens: &TokenStream,
+    container: &ContainerGenerics<'_>,
+) -> TokenStream {
+    let ident = field.ident().unwrap();
+    let ty = field.ty();
+    if field.attrs.deserialize_with.is_some() {
+        let seed = with_deserialize_seed(field, state_tokens, container);
+        quote! {
+            let __seed = #seed;
+            #ident = ::core::option::Option::Some(
+                _serde::de::MapAccess::next_value_seed(&mut __map, __seed)?,
+            );
+        }
+    } else {
+        match field.mode() {
+            ItemMode::Stateful => quote! {
+                let __seed = _serde_state::de::StateSeed::<#ty, #state_tokens>::new(state);
+                #ident = ::core::option::Option::Some(
+                    _serde::de::MapAccess::next_value_seed(&mut __map, __seed)?,
+                );
+            },
+            ItemMode::Stateless => quote! {
+                #ident = ::core::option::Option::Some(
+                    _serde::de::MapAccess::next_value::<#ty>(&mut __map)?,
+                );
+            },
+        }
+    }
+}
+
+/// Emits a visitor for named fields that include `#[serde(flatten)]` ones. Entries that no
+/// regular field claims are buffered and then handed to each flattened field in turn.
+fn flattened_fields_visitor(
     ident: &syn::Ident,
     fields: &[FieldDecl<'_>],
+    deny_unknown_fields: bool,
+    no_rollback: bool,
+    container_default: Option<&DefaultValue>,
+    construct: TokenStream,
+    expecting: &str,
+    visitor_ident: &syn::Ident,
     state_tokens: &TokenStream,
-    explicit_state: Option<&Type>,
     generics: &Generics,
     include_state_param: bool,
     state_bound: Option<&Type>,
     where_clause: &Option<syn::WhereClause>,
 ) -> TokenStream {
-    match fields.len() {
-        0 => deserialize_unit_struct(ident),
-        1 => {
-            let field = &fields[0];
-            deserialize_newtype_struct(
-                ident,
-                field,
-                state_tokens,
-                explicit_state,
-                generics,
-                include_state_param,
-                state_bound,
-                where_clause,
+    let container = ContainerGenerics {
+        ident,
+        generics,
+        include_state_param,
+        state_bound,
+        where_clause,
+    };
+    let regular: Vec<_> = fields
+        .iter()
+        .filter(|field| !field.attrs.skip_deserializing && !field.attrs.flatten)
+        .collect();
+    let init_locals = regular.iter().map(|field| {
+        let ident = field.ident().unwrap();
+        quote!(let mut #ident = ::core::option::Option::None;)
+    });
+    let match_arms = regular.iter().map(|field| {
+        let ident = field.ident().unwrap();
+        let name = &field.name.deserialize;
+        let names = field.name.deserialize_names();
+        let assignment = map_value_assignment(field, state_tokens, &container);
+        quote! {
+            ::core::option::Option::Some(#(#names)|*) => {
+                if #ident.is_some() {
+                    return ::core::result::Result::Err(_serde::de::Error::duplicate_field(#name));
+                }
+                #assignment
+            }
+        }
+    });
+    let build_fields = fields.iter().map(|field| {
+        let ident = field.ident().unwrap();
+        if let Some(inner) = flattened_option_type(field) {
+            // Like serde_derive, a flattened `Option` is `None` when its value can't be built
+            // from the leftover entries, which it only takes when it succeeds.
+            let value = match field.mode() {
+                ItemMode::Stateful => quote! {
+                    <#inner as _serde_state::DeserializeState<'de, #state_tokens>>::deserialize_state(
+                        state,
+                        __deserializer,
+                    )
+                },
+                ItemMode::Stateless => quote! {
+                    <#inner as _serde::Deserialize<'de>>::deserialize(__deserializer)
+                },
+            };
+            let (snapshot, restore) = if !no_rollback && field_reads_state(field) {
+                (
+                    quote!(let __snapshot = _serde_state::de::Rollback::snapshot(state);),
+                    quote!(_serde_state::de::Rollback::restore(state, __snapshot);),
+                )
+            } else {
+                (quote!(), quote!())
+            };
+            quote! {
+                let #ident = {
+                    #snapshot
+                    let mut __attempt = __collect.clone();
+                    let __deserializer =
+                        _serde_state::__private::de::FlatMapDeserializer::<__M::Error>::new(
+                            &mut __attempt,
+                        );
+                    match #value {
+                        ::core::result::Result::Ok(__value) => {
+                            __collect = __attempt;
+                            ::core::option::Option::Some(__value)
+                        }
+                        ::core::result::Result::Err(_) => {
+                            #restore
+                            ::core::option::Option::None
+                        }
+                    }
+                };
+            }
+        } else if field.attrs.flatten {
+            let value =
+                deserialize_newtype_content(field, state_tokens, &container);
+            quote! {
+                let #ident = {
+                    let __deserializer =
+                        _serde_state::__private::de::FlatMapDeserializer::<__M::Error>::new(
+                            &mut __collect,
+                        );
+                    #value
+                };
+            }
+        } else {
+            build_field(
+                field,
+                container_default.is_some(),
+                state_tokens,
+                &container,
+            )
+        }
+    });
+
+    // Entries that neither a field nor a flattened struct claimed. A flattened map leaves its
+    // entries in place, so it never combines with `deny_unknown_fields`, as in serde_derive.
+    let reject_leftovers = if deny_unknown_fields {
+        let expected = regular
+            .iter()
+            .flat_map(|field| field.name.deserialize_names());
+        quote! {
+            if let ::core::option::Option::Some((__key, _)) = __collect.into_iter().flatten().next() {
+                return ::core::result::Result::Err(match __key.as_str() {
+                    ::core::option::Option::Some(__key) => _serde::de::Error::unknown_field(__key, &[#(#expected),*]),
+                    ::core::option::Option::None => _serde::de::Error::custom("unexpected map key"),
+                });
+            }
+        }
+    } else {
+        quote!()
+    };
+
+    let default_init = container_default_init(container_default);
+    let (visitor_struct_generics, _) =
+        visitor_struct_generics_tokens(generics, include_state_param, state_bound);
+    let (visitor_impl_generics, visitor_impl_type_generics) =
+        visitor_impl_generics_tokens(generics, include_state_param, state_bound);
+    let (_, ty_generics, _) = generics.split_for_impl();
+    let phantom_type = phantom_type(ident, generics);
+    let visitor_where_clause = quote_where_clause(where_clause);
+
+    quote! {
+        #[allow(non_camel_case_types)]
+        struct #visitor_ident #visitor_struct_generics {
+            state: &'state #state_tokens,
+            _marker: ::core::marker::PhantomData<#phantom_type>,
+        }
+
+        impl #visitor_impl_generics _serde::de::Visitor<'de> for #visitor_ident #visitor_impl_type_generics #visitor_where_clause {
+            type Value = #ident #ty_generics;
+
+            fn expecting(&self, formatter: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                formatter.write_str(#expecting)
+            }
+
+            fn visit_map<__M>(self, mut __map: __M) -> ::core::result::Result<Self::Value, __M::Error>
+            where
+                __M: _serde::de::MapAccess<'de>,
+            {
+                let state = self.state;
+                #default_init
+                #(#init_locals)*
+                let mut __collect = ::std::vec::Vec::<
+                    ::core::option::Option<(
+                        _serde_state::__private::de::Content<'de>,
+                        _serde_state::__private::de::Content<'de>,
+                    )>,
+                >::new();
+                while let ::core::option::Option::Some(__key) = _serde::de::MapAccess::next_key::<
+                    _serde_state::__private::de::Content<'de>,
+                >(&mut __map)?
+                {
+                    match __key.as_str() {
+                        #(#match_arms)*
+                        _ => {
+                            let __value = _serde::de::MapAccess::next_value::<
+                                _serde_state::__private::de::Content<'de>,
+                            >(&mut __map)?;
+                            __collect.push(::core::option::Option::Some((__key, __value)));
+                        }
+                    }
+                }
+                #(#build_fields)*
+                #reject_leftovers
+                ::core::result::Result::Ok(#construct)
+            }
+        }
+    }
+}
+
+fn deserialize_unnamed_struct(
+    ident: &syn::Ident,
+    fields: &[FieldDecl<'_>],
+    state_tokens: &TokenStream,
+    generics: &Generics,
+    include_state_param: bool,
+    state_bound: Option<&Type>,
+    where_clause: &Option<syn::WhereClause>,
+) -> TokenStream {
+    match fields.len() {
+        0 => deserialize_unit_struct(ident),
+        1 => {
+            let field = &fields[0];
+            deserialize_newtype_struct(
+                ident,
+                field,
+                state_tokens,
+                generics,
+                include_state_param,
+                state_bound,
+                where_clause,
             )
         }
         _ => deserialize_tuple_struct(
             ident,
             fields,
             state_tokens,
-            explicit_state,
             generics,
             include_state_param,
             state_bound,
@@ -626,12 +1189,18 @@ fn deserialize_newtype_struct(
     ident: &syn::Ident,
     field: &FieldDecl<'_>,
     state_tokens: &TokenStream,
-    explicit_state: Option<&Type>,
     generics: &Generics,
     include_state_param: bool,
     state_bound: Option<&Type>,
     where_clause: &Option<syn::WhereClause>,
 ) -> TokenStream {
+    let container = ContainerGenerics {
+        ident,
+        generics,
+        include_state_param,
+        state_bound,
+        where_clause,
+    };
     let field_ty = field.ty();
     let (visitor_struct_generics, _) =
         visitor_struct_generics_tokens(generics, include_state_param, state_bound);
@@ -641,29 +1210,30 @@ fn deserialize_newtype_struct(
     let phantom_type = phantom_type(ident, generics);
     let field_mode = field.mode();
 
-    let newtype_body = if let Some(with) = &field.attrs.with {
+    let newtype_body = if let Some(with) = &field.attrs.deserialize_with {
+        let call = deserialize_with_call(with, quote!(state));
         quote! {
             let state = self.state;
-            let __value: #field_ty = #with::deserialize_state(state, __deserializer)?;
-            ::core::result::Result::Ok(#ident(__value))
+            let __value: #field_ty = #call?;
+            ::core::result::Result::Ok(#ident { 0: __value })
         }
     } else {
         match field_mode {
             ItemMode::Stateful => quote! {
                 let state = self.state;
-                let __seed = _serde_state::__private::wrap_deserialize_seed::<#field_ty, #state_tokens>(state);
+                let __seed = _serde_state::de::StateSeed::<#field_ty, #state_tokens>::new(state);
                 let __value = _serde::de::DeserializeSeed::deserialize(__seed, __deserializer)?;
-                ::core::result::Result::Ok(#ident(__value))
+                ::core::result::Result::Ok(#ident { 0: __value })
             },
             ItemMode::Stateless => quote! {
                 let __value: #field_ty = _serde::Deserialize::deserialize(__deserializer)?;
-                ::core::result::Result::Ok(#ident(__value))
+                ::core::result::Result::Ok(#ident { 0: __value })
             },
         }
     };
 
-    let seq_body = if field.attrs.with.is_some() {
-        let seed = with_deserialize_seed(field, explicit_state, state_bound);
+    let seq_body = if field.attrs.deserialize_with.is_some() {
+        let seed = with_deserialize_seed(field, state_tokens, &container);
         quote! {
             let state = self.state;
             let __seed = #seed;
@@ -675,13 +1245,13 @@ fn deserialize_newtype_struct(
             if _serde::de::SeqAccess::next_element::<_serde::de::IgnoredAny>(&mut __seq)?.is_some() {
                 return ::core::result::Result::Err(_serde::de::Error::invalid_length(1, &self));
             }
-            ::core::result::Result::Ok(#ident(__value))
+            ::core::result::Result::Ok(#ident { 0: __value })
         }
     } else {
         match field_mode {
             ItemMode::Stateful => quote! {
                 let state = self.state;
-                let __seed = _serde_state::__private::wrap_deserialize_seed::<#field_ty, #state_tokens>(state);
+                let __seed = _serde_state::de::StateSeed::<#field_ty, #state_tokens>::new(state);
                 let __value = match _serde::de::SeqAccess::next_element_seed(&mut __seq, __seed)? {
                     ::core::option::Option::Some(value) => value,
                     ::core::option::Option::None =>
@@ -690,7 +1260,7 @@ fn deserialize_newtype_struct(
                 if _serde::de::SeqAccess::next_element::<_serde::de::IgnoredAny>(&mut __seq)?.is_some() {
                     return ::core::result::Result::Err(_serde::de::Error::invalid_length(1, &self));
                 }
-                ::core::result::Result::Ok(#ident(__value))
+                ::core::result::Result::Ok(#ident { 0: __value })
             },
             ItemMode::Stateless => quote! {
                 let __value = match _serde::de::SeqAccess::next_element::<#field_ty>(&mut __seq)? {
@@ -701,7 +1271,7 @@ fn deserialize_newtype_struct(
                 if _serde::de::SeqAccess::next_element::<_serde::de::IgnoredAny>(&mut __seq)?.is_some() {
                     return ::core::result::Result::Err(_serde::de::Error::invalid_length(1, &self));
                 }
-                ::core::result::Result::Ok(#ident(__value))
+                ::core::result::Result::Ok(#ident { 0: __value })
             },
         }
     };
@@ -768,52 +1338,26 @@ fn deserialize_tuple_struct(
     ident: &syn::Ident,
     fields: &[FieldDecl<'_>],
     state_tokens: &TokenStream,
-    explicit_state: Option<&Type>,
     generics: &Generics,
     include_state_param: bool,
     state_bound: Option<&Type>,
     where_clause: &Option<syn::WhereClause>,
 ) -> TokenStream {
-    let len = fields.len();
-    let bindings: Vec<_> = (0..len).map(|i| format_ident!("__field_{}", i)).collect();
-    let read_fields = fields.iter().enumerate().map(|(index, field)| {
-        let binding = &bindings[index];
-        let ty = field.ty();
-        let idx = index;
-        if field.attrs.with.is_some() {
-            let seed = with_deserialize_seed(field, explicit_state, state_bound);
-            quote! {
-                let __seed = #seed;
-                let #binding = match _serde::de::SeqAccess::next_element_seed(&mut __seq, __seed)? {
-                    ::core::option::Option::Some(value) => value,
-                    ::core::option::Option::None =>
-                        return ::core::result::Result::Err(_serde::de::Error::invalid_length(#idx, &self)),
-                };
-            }
-        } else {
-            match field.mode() {
-                ItemMode::Stateful => quote! {
-                    let #binding = match _serde::de::SeqAccess::next_element_seed(
-                        &mut __seq,
-                        _serde_state::__private::wrap_deserialize_seed::<#ty, #state_tokens>(state),
-                    )? {
-                        ::core::option::Option::Some(value) => value,
-                        ::core::option::Option::None =>
-                            return ::core::result::Result::Err(_serde::de::Error::invalid_length(#idx, &self)),
-                    };
-                },
-                ItemMode::Stateless => quote! {
-                    let #binding = match _serde::de::SeqAccess::next_element::<#ty>(&mut __seq)? {
-                        ::core::option::Option::Some(value) => value,
-                        ::core::option::Option::None =>
-                            return ::core::result::Result::Err(_serde::de::Error::invalid_length(#idx, &self)),
-                    };
-                },
-            }
-        }
-    });
+    let container = ContainerGenerics {
+        ident,
+        generics,
+        include_state_param,
+        state_bound,
+        where_clause,
+    };
+    let len = tuple_len(fields);
+    let bindings: Vec<_> = (0..fields.len())
+        .map(|i| format_ident!("__field_{}", i))
+        .collect();
+    let read_fields = seq_read_tuple_fields(fields, &bindings, state_tokens, &container);
 
-    let construct = quote!(#ident(#(#bindings),*));
+    let indices = (0..fields.len()).map(syn::Index::from);
+    let construct = quote!(#ident { #(#indices: #bindings),* });
     let (visitor_struct_generics, _) =
         visitor_struct_generics_tokens(generics, include_state_param, state_bound);
     let (visitor_impl_generics, visitor_impl_type_generics) =
@@ -887,7 +1431,7 @@ fn deserialize_unit_struct(ident: &syn::Ident) -> TokenStream {
             where
                 E: _serde::de::Error,
             {
-                ::core::result::Result::Ok(#ident)
+                ::core::result::Result::Ok(#ident {})
             }
         }
 
@@ -902,19 +1446,119 @@ fn deserialize_unit_struct(ident: &syn::Ident) -> TokenStream {
 fn deserialize_enum_body(
     ident: &syn::Ident,
     data: &EnumDecl<'_>,
+    tag: &TagType,
+    rollback: bool,
+    state_tokens: &TokenStream,
+    generics: &Generics,
+    include_state_param: bool,
+    state_bound: Option<&Type>,
+    where_clause: &Option<syn::WhereClause>,
+) -> TokenStream {
+    let (tagged, untagged) = match tag {
+        TagType::Untagged => (&[][..], &data.variants[..]),
+        _ => data.split_untagged(),
+    };
+    if untagged.is_empty() {
+        return deserialize_tagged_enum(
+            ident,
+            tagged,
+            tag,
+            state_tokens,
+            generics,
+            include_state_param,
+            state_bound,
+            where_clause,
+        );
+    }
+
+    // Each attempt reads from its own copy of the buffered content, and rolls the state back if
+    // it fails so the next attempt starts from the same state.
+    let attempt = |body: TokenStream| {
+        let (snapshot, restore) = if rollback {
+            (
+                quote!(let __snapshot = _serde_state::de::Rollback::snapshot(state);),
+                quote!(_serde_state::de::Rollback::restore(state, __snapshot)),
+            )
+        } else {
+            (quote!(), quote!({}))
+        };
+        quote! {
+            #snapshot
+            let __deserializer =
+                _serde_state::__private::de::ContentDeserializer::<__D::Error>::new(__content.clone());
+            match (|| -> ::core::result::Result<Self, __D::Error> { #body })() {
+                ::core::result::Result::Ok(__value) => return ::core::result::Result::Ok(__value),
+                ::core::result::Result::Err(_) => #restore,
+            }
+        }
+    };
+
+    let tagged_attempt = if tagged.is_empty() {
+        TokenStream::new()
+    } else {
+        attempt(deserialize_tagged_enum(
+            ident,
+            tagged,
+            tag,
+            state_tokens,
+            generics,
+            include_state_param,
+            state_bound,
+            where_clause,
+        ))
+    };
+    let mut helper_tokens = Vec::new();
+    let untagged_attempts: Vec<_> = untagged
+        .iter()
+        .enumerate()
+        .map(|(index, variant)| {
+            let value = deserialize_variant_content(
+                ident,
+                variant,
+                true,
+                state_tokens,
+                generics,
+                include_state_param,
+                state_bound,
+                tagged.len() + index,
+                &mut helper_tokens,
+                where_clause,
+            );
+            attempt(quote! {
+                let __value = #value;
+                ::core::result::Result::Ok(__value)
+            })
+        })
+        .collect();
+    let message = format!("data did not match any variant of untagged enum {}", ident);
+
+    quote! {
+        #(#helper_tokens)*
+
+        let __content =
+            <_serde_state::__private::de::Content<'de> as _serde::Deserialize<'de>>::deserialize(__deserializer)?;
+        let state = __state;
+        #tagged_attempt
+        #(#untagged_attempts)*
+        ::core::result::Result::Err(<__D::Error as _serde::de::Error>::custom(#message))
+    }
+}
+
+fn deserialize_tagged_enum(
+    ident: &syn::Ident,
+    variants: &[VariantDecl<'_>],
+    tag: &TagType,
     state_tokens: &TokenStream,
-    explicit_state: Option<&Type>,
     generics: &Generics,
     include_state_param: bool,
     state_bound: Option<&Type>,
     where_clause: &Option<syn::WhereClause>,
 ) -> TokenStream {
-    let variant_names: Vec<_> = data
-        .variants
+    let variant_names: Vec<_> = variants
         .iter()
-        .map(|variant| variant.ident.to_string())
+        .flat_map(|variant| variant.name.deserialize_names())
         .collect();
-    let variant_idents: Vec<_> = data.variants.iter().map(|variant| variant.ident).collect();
+    let variant_idents: Vec<_> = variants.iter().map(|variant| variant.ident).collect();
 
     let const_variants = {
         let names = variant_names.iter();
@@ -927,6 +1571,7 @@ fn deserialize_enum_body(
         let variants = variant_idents.iter();
         quote! {
             #[allow(non_camel_case_types)]
+            #[derive(Clone, Copy)]
             enum __Variant { #(#variants),* }
         }
     };
@@ -934,12 +1579,11 @@ fn deserialize_enum_body(
     let variant_visitor = {
         let deserialize_variant =
             deserialize_identifier_or_u32(quote!(deserializer), quote!(__VariantVisitor));
-        let match_arms = variant_names
-            .iter()
-            .zip(variant_idents.iter())
-            .map(|(name, ident)| {
-                quote! { #name => ::core::result::Result::Ok(__Variant::#ident) }
-            });
+        let match_arms = variants.iter().map(|variant| {
+            let names = variant.name.deserialize_names();
+            let ident = variant.ident;
+            quote! { #(#names)|* => ::core::result::Result::Ok(__Variant::#ident) }
+        });
         let index_match_arms = variant_idents.iter().enumerate().map(|(index, ident)| {
             let index = index as u64;
             quote! { #index => ::core::result::Result::Ok(__Variant::#ident) }
@@ -995,13 +1639,71 @@ fn deserialize_enum_body(
         }
     };
 
+    if let TagType::Adjacent { tag, content } = tag {
+        let body = deserialize_adjacently_tagged_enum(
+            ident,
+            variants,
+            tag,
+            content,
+            state_tokens,
+            generics,
+            include_state_param,
+            state_bound,
+            where_clause,
+        );
+        return quote! {
+            #const_variants
+            #variant_enum
+            #variant_visitor
+            #body
+        };
+    }
+
+    if let TagType::Internal { tag } = tag {
+        let mut helper_tokens = Vec::new();
+        let variant_match_arms: Vec<_> = variants
+            .iter()
+            .enumerate()
+            .map(|(index, variant)| {
+                deserialize_internally_tagged_variant_arm(
+                    ident,
+                    variant,
+                    state_tokens,
+                    generics,
+                    include_state_param,
+                    state_bound,
+                    index,
+                    &mut helper_tokens,
+                    where_clause,
+                )
+            })
+            .collect();
+        let expecting = format!("internally tagged enum {}", ident);
+        return quote! {
+            #const_variants
+            #variant_enum
+            #variant_visitor
+            #(#helper_tokens)*
+
+            let (__tag, __content) = _serde::Deserializer::deserialize_any(
+                __deserializer,
+                _serde_state::__private::de::TaggedContentVisitor::<__Variant>::new(#tag, #expecting),
+            )?;
+            let __deserializer =
+                _serde_state::__private::de::ContentDeserializer::<__D::Error>::new(__content);
+            let state = __state;
+            match __tag {
+                #(#variant_match_arms)*
+            }
+        };
+    }
+
     let mut helper_tokens = Vec::new();
-    let variant_match_arms = data.variants.iter().enumerate().map(|(index, variant)| {
+    let variant_match_arms = variants.iter().enumerate().map(|(index, variant)| {
         deserialize_enum_variant_arm(
             ident,
             variant,
             state_tokens,
-            explicit_state,
             generics,
             include_state_param,
             state_bound,
@@ -1074,7 +1776,6 @@ fn deserialize_enum_variant_arm(
     ident: &syn::Ident,
     variant: &VariantDecl<'_>,
     state_tokens: &TokenStream,
-    explicit_state: Option<&Type>,
     generics: &Generics,
     include_state_param: bool,
     state_bound: Option<&Type>,
@@ -1082,7 +1783,27 @@ fn deserialize_enum_variant_arm(
     helpers: &mut Vec<TokenStream>,
     where_clause: &Option<syn::WhereClause>,
 ) -> TokenStream {
+    let container = ContainerGenerics {
+        ident,
+        generics,
+        include_state_param,
+        state_bound,
+        where_clause,
+    };
     let variant_ident = variant.ident;
+    if let Some(with) = &variant.deserialize_with {
+        let value = deserialize_variant_with(
+            ident,
+            variant,
+            with,
+            |seed| quote!(_serde::de::VariantAccess::newtype_variant_seed(__variant, #seed)),
+            state_tokens,
+            &container,
+        );
+        return quote! {
+            (__Variant::#variant_ident, __variant) => ::core::result::Result::Ok(#value),
+        };
+    }
     match variant.fields.style {
         FieldsStyle::Unit => {
             quote! {
@@ -1095,8 +1816,8 @@ fn deserialize_enum_variant_arm(
         FieldsStyle::Unnamed if variant.fields.fields.len() == 1 => {
             let field = &variant.fields.fields[0];
             let ty = field.ty();
-            if field.attrs.with.is_some() {
-                let seed = with_deserialize_seed(field, explicit_state, state_bound);
+            if field.attrs.deserialize_with.is_some() {
+                let seed = with_deserialize_seed(field, state_tokens, &container);
                 quote! {
                     (__Variant::#variant_ident, __variant) => {
                         let __seed = #seed;
@@ -1108,7 +1829,7 @@ fn deserialize_enum_variant_arm(
                 match field.mode() {
                     ItemMode::Stateful => quote! {
                         (__Variant::#variant_ident, __variant) => {
-                            let __seed = _serde_state::__private::wrap_deserialize_seed::<#ty, #state_tokens>(state);
+                            let __seed = _serde_state::de::StateSeed::<#ty, #state_tokens>::new(state);
                             let __value = _serde::de::VariantAccess::newtype_variant_seed(__variant, __seed)?;
                             ::core::result::Result::Ok(#ident::#variant_ident(__value))
                         }
@@ -1129,14 +1850,13 @@ fn deserialize_enum_variant_arm(
                 variant_ident,
                 &variant.fields.fields,
                 state_tokens,
-                explicit_state,
                 generics,
                 include_state_param,
                 state_bound,
                 &visitor_ident,
                 where_clause,
             ));
-            let len = variant.fields.fields.len();
+            let len = tuple_len(&variant.fields.fields);
             quote! {
                 (__Variant::#variant_ident, __variant) => {
                     _serde::de::VariantAccess::tuple_variant(
@@ -1157,13 +1877,15 @@ fn deserialize_enum_variant_arm(
                 ident,
                 variant_ident,
                 &variant.fields.fields,
+                variant.fields.deny_unknown_fields,
+                variant.fields.no_rollback,
                 state_tokens,
-                explicit_state,
                 generics,
                 include_state_param,
                 state_bound,
                 &visitor_ident,
                 &field_array_ident,
+                true,
                 where_clause,
             ));
             quote! {
@@ -1182,58 +1904,548 @@ fn deserialize_enum_variant_arm(
     }
 }
 
-fn tuple_variant_visitor(
-    ident: &syn::Ident,
-    variant_ident: &syn::Ident,
+fn deserialize_internally_tagged_variant_arm(
+    ident: &syn::Ident,
+    variant: &VariantDecl<'_>,
+    state_tokens: &TokenStream,
+    generics: &Generics,
+    include_state_param: bool,
+    state_bound: Option<&Type>,
+    index: usize,
+    helpers: &mut Vec<TokenStream>,
+    where_clause: &Option<syn::WhereClause>,
+) -> TokenStream {
+    let container = ContainerGenerics {
+        ident,
+        generics,
+        include_state_param,
+        state_bound,
+        where_clause,
+    };
+    let variant_ident = variant.ident;
+    if let Some(with) = &variant.deserialize_with {
+        let value = deserialize_variant_with(
+            ident,
+            variant,
+            with,
+            |seed| quote!(_serde::de::DeserializeSeed::deserialize(#seed, __deserializer)),
+            state_tokens,
+            &container,
+        );
+        return quote! {
+            __Variant::#variant_ident => ::core::result::Result::Ok(#value),
+        };
+    }
+    match variant.fields.style {
+        FieldsStyle::Unit => {
+            let type_name = ident.to_string();
+            let variant_name = &variant.name.deserialize;
+            quote! {
+                __Variant::#variant_ident => {
+                    _serde::Deserializer::deserialize_any(
+                        __deserializer,
+                        _serde_state::__private::de::InternallyTaggedUnitVisitor::new(
+                            #type_name,
+                            #variant_name,
+                        ),
+                    )?;
+                    ::core::result::Result::Ok(#ident::#variant_ident)
+                }
+            }
+        }
+        FieldsStyle::Unnamed => {
+            let value =
+                deserialize_newtype_content(&variant.fields.fields[0], state_tokens, &container);
+            quote! {
+                __Variant::#variant_ident => {
+                    let __value = #value;
+                    ::core::result::Result::Ok(#ident::#variant_ident(__value))
+                }
+            }
+        }
+        FieldsStyle::Named => {
+            let visitor_ident = format_ident!("__Variant{}_StructVisitor", index);
+            let field_array_ident = format_ident!("__VARIANT_FIELDS_{}", index);
+            helpers.push(struct_variant_helpers(
+                ident,
+                variant_ident,
+                &variant.fields.fields,
+                variant.fields.deny_unknown_fields,
+                variant.fields.no_rollback,
+                state_tokens,
+                generics,
+                include_state_param,
+                state_bound,
+                &visitor_ident,
+                &field_array_ident,
+                true,
+                where_clause,
+            ));
+            quote! {
+                __Variant::#variant_ident => {
+                    _serde::Deserializer::deserialize_any(
+                        __deserializer,
+                        #visitor_ident {
+                            state,
+                            _marker: ::core::marker::PhantomData,
+                        },
+                    )
+                }
+            }
+        }
+    }
+}
+
+/// Deserializes a single field directly from `__deserializer`, with state if it is stateful.
+fn deserialize_newtype_content(
+    field: &FieldDecl<'_>,
+    state_tokens: &TokenStream,
+    container: &ContainerGenerics<'_>,
+) -> TokenStream {
+    let ty = field.ty();
+    if field.attrs.deserialize_with.is_some() {
+        let seed = with_deserialize_seed(field, state_tokens, container);
+        quote! {
+            _serde::de::DeserializeSeed::deserialize(#seed, __deserializer)?
+        }
+    } else {
+        match field.mode() {
+            ItemMode::Stateful => quote! {
+                <#ty as _serde_state::DeserializeState<'de, #state_tokens>>::deserialize_state(
+                    state,
+                    __deserializer,
+                )?
+            },
+            ItemMode::Stateless => quote! {
+                <#ty as _serde::Deserialize<'de>>::deserialize(__deserializer)?
+            },
+        }
+    }
+}
+
+/// Deserializes the fields of a variant directly from `__deserializer`, as the content of an
+/// adjacently tagged or untagged enum.
+fn deserialize_variant_content(
+    ident: &syn::Ident,
+    variant: &VariantDecl<'_>,
+    untagged: bool,
+    state_tokens: &TokenStream,
+    generics: &Generics,
+    include_state_param: bool,
+    state_bound: Option<&Type>,
+    index: usize,
+    helpers: &mut Vec<TokenStream>,
+    where_clause: &Option<syn::WhereClause>,
+) -> TokenStream {
+    let container = ContainerGenerics {
+        ident,
+        generics,
+        include_state_param,
+        state_bound,
+        where_clause,
+    };
+    let variant_ident = variant.ident;
+    if let Some(with) = &variant.deserialize_with {
+        return deserialize_variant_with(
+            ident,
+            variant,
+            with,
+            |seed| quote!(_serde::de::DeserializeSeed::deserialize(#seed, __deserializer)),
+            state_tokens,
+            &container,
+        );
+    }
+    match variant.fields.style {
+        FieldsStyle::Unit => {
+            let type_name = ident.to_string();
+            let variant_name = &variant.name.deserialize;
+            quote! {{
+                _serde::Deserializer::deserialize_any(
+                    __deserializer,
+                    _serde_state::__private::de::UntaggedUnitVisitor::new(
+                        #type_name,
+                        #variant_name,
+                    ),
+                )?;
+                #ident::#variant_ident
+            }}
+        }
+        FieldsStyle::Unnamed if variant.fields.fields.len() == 1 => {
+            let value =
+                deserialize_newtype_content(&variant.fields.fields[0], state_tokens, &container);
+            quote!(#ident::#variant_ident(#value))
+        }
+        FieldsStyle::Unnamed => {
+            let visitor_ident = format_ident!("__Variant{}_TupleVisitor", index);
+            helpers.push(tuple_variant_visitor(
+                ident,
+                variant_ident,
+                &variant.fields.fields,
+                state_tokens,
+                generics,
+                include_state_param,
+                state_bound,
+                &visitor_ident,
+                where_clause,
+            ));
+            let len = tuple_len(&variant.fields.fields);
+            quote! {
+                _serde::Deserializer::deserialize_tuple(
+                    __deserializer,
+                    #len,
+                    #visitor_ident {
+                        state,
+                        _marker: ::core::marker::PhantomData,
+                    },
+                )?
+            }
+        }
+        FieldsStyle::Named => {
+            let visitor_ident = format_ident!("__Variant{}_StructVisitor", index);
+            let field_array_ident = format_ident!("__VARIANT_FIELDS_{}", index);
+            helpers.push(struct_variant_helpers(
+                ident,
+                variant_ident,
+                &variant.fields.fields,
+                variant.fields.deny_unknown_fields,
+                variant.fields.no_rollback,
+                state_tokens,
+                generics,
+                include_state_param,
+                state_bound,
+                &visitor_ident,
+                &field_array_ident,
+                !untagged,
+                where_clause,
+            ));
+            let variant_name = &variant.name.deserialize;
+            quote! {
+                _serde::Deserializer::deserialize_struct(
+                    __deserializer,
+                    #variant_name,
+                    #field_array_ident,
+                    #visitor_ident {
+                        state,
+                        _marker: ::core::marker::PhantomData,
+                    },
+                )?
+            }
+        }
+    }
+}
+
+fn deserialize_adjacently_tagged_enum(
+    ident: &syn::Ident,
+    variants: &[VariantDecl<'_>],
+    tag: &str,
+    content: &str,
+    state_tokens: &TokenStream,
+    generics: &Generics,
+    include_state_param: bool,
+    state_bound: Option<&Type>,
+    where_clause: &Option<syn::WhereClause>,
+) -> TokenStream {
+    let mut helper_tokens = Vec::new();
+    let seed_arms: Vec<_> = variants
+        .iter()
+        .enumerate()
+        .map(|(index, variant)| {
+            let variant_ident = variant.ident;
+            let value = deserialize_variant_content(
+                ident,
+                variant,
+                false,
+                state_tokens,
+                generics,
+                include_state_param,
+                state_bound,
+                index,
+                &mut helper_tokens,
+                where_clause,
+            );
+            quote! {
+                __Variant::#variant_ident => ::core::result::Result::Ok(#value),
+            }
+        })
+        .collect();
+
+    // Unit variants may leave out the content entirely, unless a function reads it.
+    let unit_arms: Vec<_> = variants
+        .iter()
+        .filter(|variant| {
+            matches!(variant.fields.style, FieldsStyle::Unit) && variant.deserialize_with.is_none()
+        })
+        .map(|variant| {
+            let variant_ident = variant.ident;
+            quote! {
+                __Variant::#variant_ident => return ::core::result::Result::Ok(#ident::#variant_ident),
+            }
+        })
+        .collect();
+    let missing_content = quote! {
+        match __variant {
+            #(#unit_arms)*
+            #[allow(unreachable_patterns)]
+            _ => ::core::result::Result::Err(_serde::de::Error::missing_field(#content)),
+        }
+    };
+
+    let (visitor_struct_generics, _) =
+        visitor_struct_generics_tokens(generics, include_state_param, state_bound);
+    let (visitor_impl_generics, visitor_impl_type_generics) =
+        visitor_impl_generics_tokens(generics, include_state_param, state_bound);
+    let (_, ty_generics, _) = generics.split_for_impl();
+    let phantom_type = phantom_type(ident, generics);
+    let visitor_where_clause = quote_where_clause(where_clause);
+    let expecting = format!("adjacently tagged enum {}", ident);
+
+    quote! {
+        #(#helper_tokens)*
+
+        struct __AdjacentlyTaggedSeed #visitor_struct_generics {
+            variant: __Variant,
+            state: &'state #state_tokens,
+            _marker: ::core::marker::PhantomData<#phantom_type>,
+        }
+
+        impl #visitor_impl_generics _serde::de::DeserializeSeed<'de>
+            for __AdjacentlyTaggedSeed #visitor_impl_type_generics #visitor_where_clause
+        {
+            type Value = #ident #ty_generics;
+
+            fn deserialize<__D>(
+                self,
+                __deserializer: __D,
+            ) -> ::core::result::Result<Self::Value, __D::Error>
+            where
+                __D: _serde::Deserializer<'de>,
+            {
+                let state = self.state;
+                match self.variant {
+                    #(#seed_arms)*
+                }
+            }
+        }
+
+        struct __Visitor #visitor_struct_generics {
+            state: &'state #state_tokens,
+            _marker: ::core::marker::PhantomData<#phantom_type>,
+        }
+
+        impl #visitor_impl_generics _serde::de::Visitor<'de> for __Visitor #visitor_impl_type_generics #visitor_where_clause {
+            type Value = #ident #ty_generics;
+
+            fn expecting(&self, formatter: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                formatter.write_str(#expecting)
+            }
+
+            fn visit_map<__M>(
+                self,
+                mut __map: __M,
+            ) -> ::core::result::Result<Self::Value, __M::Error>
+            where
+                __M: _serde::de::MapAccess<'de>,
+            {
+                let state = self.state;
+                let mut __tag: ::core::option::Option<__Variant> = ::core::option::Option::None;
+                let mut __value: ::core::option::Option<Self::Value> = ::core::option::Option::None;
+                let mut __content: ::core::option::Option<_serde_state::__private::de::Content<'de>> =
+                    ::core::option::Option::None;
+                while let ::core::option::Option::Some(__key) = _serde::de::MapAccess::next_key_seed(
+                    &mut __map,
+                    _serde_state::__private::de::TagContentOtherFieldVisitor::new(#tag, #content),
+                )? {
+                    match __key {
+                        _serde_state::__private::de::TagContentOtherField::Tag => {
+                            if __tag.is_some() {
+                                return ::core::result::Result::Err(_serde::de::Error::duplicate_field(#tag));
+                            }
+                            __tag = ::core::option::Option::Some(
+                                _serde::de::MapAccess::next_value::<__Variant>(&mut __map)?,
+                            );
+                        }
+                        _serde_state::__private::de::TagContentOtherField::Content => {
+                            if __value.is_some() || __content.is_some() {
+                                return ::core::result::Result::Err(_serde::de::Error::duplicate_field(#content));
+                            }
+                            match __tag {
+                                // The tag came first, so the content can be read in place.
+                                ::core::option::Option::Some(__variant) => {
+                                    __value = ::core::option::Option::Some(
+                                        _serde::de::MapAccess::next_value_seed(
+                                            &mut __map,
+                                            __AdjacentlyTaggedSeed {
+                                                variant: __variant,
+                                                state,
+                                                _marker: ::core::marker::PhantomData,
+                                            },
+                                        )?,
+                                    );
+                                }
+                                // Otherwise buffer it until the tag shows up.
+                                ::core::option::Option::None => {
+                                    __content = ::core::option::Option::Some(
+                                        _serde::de::MapAccess::next_value(&mut __map)?,
+                                    );
+                                }
+                            }
+                        }
+                        _serde_state::__private::de::TagContentOtherField::Other => {
+                            let _ = _serde::de::MapAccess::next_value::<_serde::de::IgnoredAny>(&mut __map)?;
+                        }
+                    }
+                }
+                let __variant = match __tag {
+                    ::core::option::Option::Some(__variant) => __variant,
+                    ::core::option::Option::None =>
+                        return ::core::result::Result::Err(_serde::de::Error::missing_field(#tag)),
+                };
+                match (__value, __content) {
+                    (::core::option::Option::Some(__value), _) => ::core::result::Result::Ok(__value),
+                    (::core::option::Option::None, ::core::option::Option::Some(__content)) => {
+                        _serde::de::DeserializeSeed::deserialize(
+                            __AdjacentlyTaggedSeed {
+                                variant: __variant,
+                                state,
+                                _marker: ::core::marker::PhantomData,
+                            },
+                            _serde_state::__private::de::ContentDeserializer::<__M::Error>::new(__content),
+                        )
+                    }
+                    (::core::option::Option::None, ::core::option::Option::None) => #missing_content,
+                }
+            }
+
+            fn visit_seq<__A>(
+                self,
+                mut __seq: __A,
+            ) -> ::core::result::Result<Self::Value, __A::Error>
+            where
+                __A: _serde::de::SeqAccess<'de>,
+            {
+                let state = self.state;
+                let __variant = match _serde::de::SeqAccess::next_element::<__Variant>(&mut __seq)? {
+                    ::core::option::Option::Some(__variant) => __variant,
+                    ::core::option::Option::None =>
+                        return ::core::result::Result::Err(_serde::de::Error::invalid_length(0, &self)),
+                };
+                // As in serde_derive, a sequence must hold the content even for unit variants.
+                match _serde::de::SeqAccess::next_element_seed(
+                    &mut __seq,
+                    __AdjacentlyTaggedSeed {
+                        variant: __variant,
+                        state,
+                        _marker: ::core::marker::PhantomData,
+                    },
+                )? {
+                    ::core::option::Option::Some(__value) => ::core::result::Result::Ok(__value),
+                    ::core::option::Option::None =>
+                        ::core::result::Result::Err(_serde::de::Error::invalid_length(1, &self)),
+                }
+            }
+        }
+
+        _serde::Deserializer::deserialize_struct(
+            __deserializer,
+            stringify!(#ident),
+            &[#tag, #content],
+            __Visitor {
+                state: __state,
+                _marker: ::core::marker::PhantomData,
+            },
+        )
+    }
+}
+
+/// The number of elements a tuple struct or variant reads, leaving out skipped fields.
+fn tuple_len(fields: &[FieldDecl<'_>]) -> usize {
+    fields
+        .iter()
+        .filter(|field| !field.attrs.skip_deserializing)
+        .count()
+}
+
+/// Reads the fields of a tuple struct or variant from `__seq` into `bindings`. Skipped fields
+/// take their default without using up an element.
+fn seq_read_tuple_fields(
     fields: &[FieldDecl<'_>],
+    bindings: &[syn::Ident],
     state_tokens: &TokenStream,
-    explicit_state: Option<&Type>,
-    generics: &Generics,
-    include_state_param: bool,
-    state_bound: Option<&Type>,
-    visitor_ident: &syn::Ident,
-    where_clause: &Option<syn::WhereClause>,
-) -> TokenStream {
-    let len = fields.len();
-    let bindings: Vec<_> = (0..len)
-        .map(|i| format_ident!("__variant_field_{}", i))
-        .collect();
-    let read_fields = fields.iter().enumerate().map(|(index, field)| {
-        let binding = &bindings[index];
-        let ty = field.ty();
-        let idx = index;
-        if field.attrs.with.is_some() {
-            let seed = with_deserialize_seed(field, explicit_state, state_bound);
-            quote! {
-                let __seed = #seed;
-                let #binding = match _serde::de::SeqAccess::next_element_seed(&mut __seq, __seed)? {
-                    ::core::option::Option::Some(value) => value,
-                    ::core::option::Option::None =>
-                        return ::core::result::Result::Err(_serde::de::Error::invalid_length(#idx, &self)),
+    container: &ContainerGenerics<'_>,
+) -> Vec<TokenStream> {
+    let mut idx = 0usize;
+    fields
+        .iter()
+        .zip(bindings)
+        .map(|(field, binding)| {
+            if field.attrs.skip_deserializing {
+                let value = match &field.attrs.default {
+                    Some(DefaultValue::Path(path)) => quote!(#path()),
+                    Some(DefaultValue::State(path)) => quote!(#path(state)),
+                    Some(DefaultValue::Default) | None => {
+                        quote!(::core::default::Default::default())
+                    }
+                };
+                return quote!(let #binding = #value;);
+            }
+            let ty = field.ty();
+            let missing = quote! {
+                return ::core::result::Result::Err(_serde::de::Error::invalid_length(#idx, &self))
+            };
+            idx += 1;
+            if field.attrs.deserialize_with.is_some() {
+                let seed = with_deserialize_seed(field, state_tokens, container);
+                return quote! {
+                    let __seed = #seed;
+                    let #binding = match _serde::de::SeqAccess::next_element_seed(&mut __seq, __seed)? {
+                        ::core::option::Option::Some(value) => value,
+                        ::core::option::Option::None => #missing,
+                    };
                 };
             }
-        } else {
             match field.mode() {
                 ItemMode::Stateful => quote! {
                     let #binding = match _serde::de::SeqAccess::next_element_seed(
                         &mut __seq,
-                        _serde_state::__private::wrap_deserialize_seed::<#ty, #state_tokens>(state),
+                        _serde_state::de::StateSeed::<#ty, #state_tokens>::new(state),
                     )? {
                         ::core::option::Option::Some(value) => value,
-                        ::core::option::Option::None =>
-                            return ::core::result::Result::Err(_serde::de::Error::invalid_length(#idx, &self)),
+                        ::core::option::Option::None => #missing,
                     };
                 },
                 ItemMode::Stateless => quote! {
                     let #binding = match _serde::de::SeqAccess::next_element::<#ty>(&mut __seq)? {
                         ::core::option::Option::Some(value) => value,
-                        ::core::option::Option::None =>
-                            return ::core::result::Result::Err(_serde::de::Error::invalid_length(#idx, &self)),
+                        ::core::option::Option::None => #missing,
                     };
                 },
             }
-        }
-    });
+        })
+        .collect()
+}
+
+fn tuple_variant_visitor(
+    ident: &syn::Ident,
+    variant_ident: &syn::Ident,
+    fields: &[FieldDecl<'_>],
+    state_tokens: &TokenStream,
+    generics: &Generics,
+    include_state_param: bool,
+    state_bound: Option<&Type>,
+    visitor_ident: &syn::Ident,
+    where_clause: &Option<syn::WhereClause>,
+) -> TokenStream {
+    let container = ContainerGenerics {
+        ident,
+        generics,
+        include_state_param,
+        state_bound,
+        where_clause,
+    };
+    let bindings: Vec<_> = (0..fields.len())
+        .map(|i| format_ident!("__variant_field_{}", i))
+        .collect();
+    let read_fields = seq_read_tuple_fields(fields, &bindings, state_tokens, &container);
     let construct = quote!(#ident::#variant_ident(#(#bindings),*));
 
     let (visitor_struct_generics, _) =
@@ -1289,25 +2501,64 @@ fn struct_variant_helpers(
     ident: &syn::Ident,
     variant_ident: &syn::Ident,
     fields: &[FieldDecl<'_>],
+    deny_unknown_fields: bool,
+    no_rollback: bool,
     state_tokens: &TokenStream,
-    explicit_state: Option<&Type>,
     generics: &Generics,
     include_state_param: bool,
     state_bound: Option<&Type>,
     visitor_ident: &syn::Ident,
     field_array_ident: &syn::Ident,
+    accept_seq: bool,
     where_clause: &Option<syn::WhereClause>,
 ) -> TokenStream {
-    let included: Vec<_> = fields.iter().filter(|field| !field.attrs.skip).collect();
+    let container = ContainerGenerics {
+        ident,
+        generics,
+        include_state_param,
+        state_bound,
+        where_clause,
+    };
+    let included: Vec<_> = fields
+        .iter()
+        .filter(|field| !field.attrs.skip_deserializing)
+        .collect();
     let field_idents: Vec<_> = fields.iter().map(|field| field.ident().unwrap()).collect();
-    let field_names: Vec<String> = included
+
+    if fields.iter().any(|field| field.attrs.flatten) {
+        let names = included
+            .iter()
+            .filter(|field| !field.attrs.flatten)
+            .flat_map(|field| field.name.deserialize_names());
+        let pairs = field_idents.iter().map(|ident| quote!(#ident: #ident));
+        let visitor = flattened_fields_visitor(
+            ident,
+            fields,
+            deny_unknown_fields,
+            no_rollback,
+            None,
+            quote!(#ident::#variant_ident { #(#pairs),* }),
+            &format!("struct variant {}::{}", ident, variant_ident),
+            visitor_ident,
+            state_tokens,
+            generics,
+            include_state_param,
+            state_bound,
+            where_clause,
+        );
+        return quote! {
+            const #field_array_ident: &'static [&'static str] = &[#(#names),*];
+            #visitor
+        };
+    }
+    let field_names: Vec<&str> = included
         .iter()
-        .map(|field| field.attrs.key(field.ident().unwrap()))
+        .flat_map(|field| field.name.deserialize_names())
         .collect();
     let field_variants: Vec<_> = included
         .iter()
         .map(|field| {
-            let name = field.ident().unwrap().to_string();
+            let name = field.ident().unwrap().unraw();
             format_ident!("__variant_field_{}", name)
         })
         .collect();
@@ -1320,11 +2571,22 @@ fn struct_variant_helpers(
     };
 
     let field_enum_ident = format_ident!("__VariantFieldEnum_{}", variant_ident);
+    let UnknownFieldArms {
+        variant: ignore_variant,
+        visit_str: unknown_str,
+        visit_u64: unknown_index,
+        map_arm: ignore_arm,
+    } = unknown_field_arms(
+        &quote!(#field_enum_ident),
+        &quote!(#field_array_ident),
+        field_variants.len(),
+        deny_unknown_fields,
+    );
     let field_enum = {
         let variants = field_variants.iter();
         quote! {
             #[allow(non_camel_case_types)]
-            enum #field_enum_ident { #(#variants,)* __Ignore }
+            enum #field_enum_ident { #(#variants,)* #ignore_variant }
         }
     };
 
@@ -1332,11 +2594,12 @@ fn struct_variant_helpers(
     let field_visitor = {
         let deserialize_field =
             deserialize_identifier_or_u32(quote!(deserializer), quote!(#field_visitor_ident));
-        let match_arms = field_names
+        let match_arms = included
             .iter()
             .zip(field_variants.iter())
-            .map(|(name, variant)| {
-                quote! { #name => ::core::result::Result::Ok(#field_enum_ident::#variant) }
+            .map(|(field, variant)| {
+                let names = field.name.deserialize_names();
+                quote! { #(#names)|* => ::core::result::Result::Ok(#field_enum_ident::#variant) }
             });
         let index_match_arms = field_variants.iter().enumerate().map(|(index, variant)| {
             let index = index as u64;
@@ -1358,7 +2621,7 @@ fn struct_variant_helpers(
                 {
                     match value {
                         #(#match_arms,)*
-                        _ => ::core::result::Result::Ok(#field_enum_ident::__Ignore),
+                        _ => #unknown_str,
                     }
                 }
 
@@ -1368,7 +2631,7 @@ fn struct_variant_helpers(
                 {
                     match value {
                         #(#index_match_arms,)*
-                        _ => ::core::result::Result::Ok(#field_enum_ident::__Ignore),
+                        _ => #unknown_index,
                     }
                 }
 
@@ -1393,7 +2656,7 @@ fn struct_variant_helpers(
 
     let init_locals = fields.iter().map(|field| {
         let ident = field.ident().unwrap();
-        if field.attrs.skip {
+        if field.attrs.skip_deserializing {
             quote!()
         } else {
             quote!(let mut #ident = ::core::option::Option::None;)
@@ -1405,45 +2668,37 @@ fn struct_variant_helpers(
         quote!(#ident::#variant_ident { #(#pairs),* })
     };
 
-    let seq_read_fields = seq_read_fields_body(
-        fields,
-        &included,
-        state_tokens,
-        explicit_state,
-        state_bound,
-        construct.clone(),
-    );
+    // Like serde_derive, untagged struct variants are only read from maps, so they don't take a
+    // sequence meant for a later tuple variant.
+    let visit_seq = if accept_seq {
+        let seq_read_fields = seq_read_fields_body(
+            fields,
+            &included,
+            None,
+            state_tokens,
+            &container,
+            construct.clone(),
+        );
+        quote! {
+            fn visit_seq<__A>(self, mut __seq: __A) -> ::core::result::Result<Self::Value, __A::Error>
+            where
+                __A: _serde::de::SeqAccess<'de>,
+            {
+                #seq_read_fields
+            }
+        }
+    } else {
+        quote!()
+    };
 
     let match_arms = included
         .iter()
         .zip(field_variants.iter())
         .map(|(field, variant)| {
             let ident = field.ident().unwrap();
-            let ty = field.ty();
-            let field_name = field.attrs.key(ident);
-            let assignment = if field.attrs.with.is_some() {
-                let seed = with_deserialize_seed(field, explicit_state, state_bound);
-                quote! {
-                    let __seed = #seed;
-                    #ident = ::core::option::Option::Some(
-                        _serde::de::MapAccess::next_value_seed(&mut __map, __seed)?,
-                    );
-                }
-            } else {
-                match field.mode() {
-                    ItemMode::Stateful => quote! {
-                        let __seed = _serde_state::__private::wrap_deserialize_seed::<#ty, #state_tokens>(state);
-                        #ident = ::core::option::Option::Some(
-                            _serde::de::MapAccess::next_value_seed(&mut __map, __seed)?,
-                        );
-                    },
-                    ItemMode::Stateless => quote! {
-                        #ident = ::core::option::Option::Some(
-                            _serde::de::MapAccess::next_value::<#ty>(&mut __map)?,
-                        );
-                    },
-                }
-            };
+            let field_name = &field.name.deserialize;
+            let assignment =
+                map_value_assignment(field, state_tokens, &container);
             quote! {
                 #field_enum_ident::#variant => {
                     if #ident.is_some() {
@@ -1454,23 +2709,9 @@ fn struct_variant_helpers(
             }
         });
 
-    let build_fields = fields.iter().map(|field| {
-        let ident = field.ident().unwrap();
-        if field.attrs.skip {
-            quote! {
-                let #ident = ::core::default::Default::default();
-            }
-        } else {
-            let name = field.attrs.key(ident);
-            quote! {
-                let #ident = match #ident {
-                    ::core::option::Option::Some(value) => value,
-                    ::core::option::Option::None =>
-                        return ::core::result::Result::Err(_serde::de::Error::missing_field(#name)),
-                };
-            }
-        }
-    });
+    let build_fields = fields
+        .iter()
+        .map(|field| build_field(field, false, state_tokens, &container));
 
     let (visitor_struct_generics, _) =
         visitor_struct_generics_tokens(generics, include_state_param, state_bound);
@@ -1511,22 +2752,14 @@ fn struct_variant_helpers(
                 {
                     match key {
                         #(#match_arms)*
-                        #field_enum_ident::__Ignore => {
-                            let _ =
-                                _serde::de::MapAccess::next_value::<_serde::de::IgnoredAny>(&mut __map)?;
-                        }
+                        #ignore_arm
                     }
                 }
                 #(#build_fields)*
                 ::core::result::Result::Ok(#construct)
             }
 
-            fn visit_seq<__A>(self, mut __seq: __A) -> ::core::result::Result<Self::Value, __A::Error>
-            where
-                __A: _serde::de::SeqAccess<'de>,
-            {
-                #seq_read_fields
-            }
+            #visit_seq
         }
     };
 
@@ -1539,70 +2772,135 @@ fn struct_variant_helpers(
     }
 }
 
+/// Calls a field's deserialization function on `__deserializer`, passing `state` if it takes it.
+fn deserialize_with_call(with: &WithFn, state: TokenStream) -> TokenStream {
+    match with {
+        WithFn::Stateless(path) => quote!(#path(__deserializer)),
+        WithFn::Stateful(path) => quote!(#path(#state, __deserializer)),
+    }
+}
+
 fn with_deserialize_seed(
     field: &FieldDecl<'_>,
-    explicit_state: Option<&Type>,
-    state_bound: Option<&Type>,
+    state_tokens: &TokenStream,
+    container: &ContainerGenerics<'_>,
 ) -> TokenStream {
     let ty = field.ty();
     let with = field
         .attrs
-        .with
+        .deserialize_with
         .as_ref()
-        .expect("with_deserialize_seed used without `with`");
-    match explicit_state {
-        Some(state_ty) => quote! {
-            {
-                struct __SerdeStateWithSeed<'state> {
-                    state: &'state #state_ty,
-                }
+        .expect("with_deserialize_seed used without a deserialization function");
+    deserialize_with_seed(with, quote!(#ty), state_tokens, container)
+}
 
-                impl<'de, 'state> _serde::de::DeserializeSeed<'de>
-                    for __SerdeStateWithSeed<'state>
-                {
-                    type Value = #ty;
+/// Deserializes the fields of a variant with `#[serde(deserialize_with)]`, read from `read` given
+/// the seed calling the function, into the variant. The function returns `()` for a unit variant,
+/// the field itself for a single field and a tuple of the fields otherwise.
+fn deserialize_variant_with(
+    ident: &syn::Ident,
+    variant: &VariantDecl<'_>,
+    with: &WithFn,
+    read: impl FnOnce(TokenStream) -> TokenStream,
+    state_tokens: &TokenStream,
+    container: &ContainerGenerics<'_>,
+) -> TokenStream {
+    let variant_ident = variant.ident;
+    let fields = &variant.fields.fields;
+    let types = fields.iter().map(|field| field.ty());
+    let ty = if fields.len() == 1 {
+        quote!(#(#types)*)
+    } else {
+        quote!((#(#types),*))
+    };
+    let values: Vec<_> = if fields.len() == 1 {
+        vec![quote!(__value)]
+    } else {
+        (0..fields.len())
+            .map(|i| {
+                let i = syn::Index::from(i);
+                quote!(__value.#i)
+            })
+            .collect()
+    };
+    let read = read(deserialize_with_seed(with, ty, state_tokens, container));
+    match variant.fields.style {
+        FieldsStyle::Unit => quote!({
+            let () = #read?;
+            #ident::#variant_ident
+        }),
+        FieldsStyle::Unnamed => quote!({
+            let __value = #read?;
+            #ident::#variant_ident(#(#values),*)
+        }),
+        FieldsStyle::Named => {
+            let field_idents = fields.iter().map(|field| field.ident().unwrap());
+            quote!({
+                let __value = #read?;
+                #ident::#variant_ident { #(#field_idents: #values),* }
+            })
+        }
+    }
+}
 
-                    fn deserialize<__D>(
-                        self,
-                        __deserializer: __D,
-                    ) -> ::core::result::Result<Self::Value, __D::Error>
-                    where
-                        __D: _serde::Deserializer<'de>,
-                    {
-                        #with::deserialize_state(self.state, __deserializer)
-                    }
-                }
+/// The container's generics, which the helper structs declared inside its impl repeat so that
+/// their types can mention the container's type parameters.
+struct ContainerGenerics<'a> {
+    ident: &'a syn::Ident,
+    generics: &'a Generics,
+    include_state_param: bool,
+    state_bound: Option<&'a Type>,
+    where_clause: &'a Option<syn::WhereClause>,
+}
 
-                __SerdeStateWithSeed { state }
+/// A `DeserializeSeed` producing a `ty` through the deserialization function `with`.
+fn deserialize_with_seed(
+    with: &WithFn,
+    ty: TokenStream,
+    state_tokens: &TokenStream,
+    container: &ContainerGenerics<'_>,
+) -> TokenStream {
+    let ContainerGenerics {
+        ident,
+        generics,
+        include_state_param,
+        state_bound,
+        where_clause,
+    } = *container;
+    let (seed_struct_generics, _) =
+        visitor_struct_generics_tokens(generics, include_state_param, state_bound);
+    let (seed_impl_generics, seed_impl_type_generics) =
+        visitor_impl_generics_tokens(generics, include_state_param, state_bound);
+    let phantom_type = phantom_type(ident, generics);
+    let seed_where_clause = quote_where_clause(where_clause);
+    let call = deserialize_with_call(with, quote!(self.state));
+    quote! {
+        {
+            struct __SerdeWithSeed #seed_struct_generics {
+                state: &'state #state_tokens,
+                _marker: ::core::marker::PhantomData<#phantom_type>,
             }
-        },
-        None => {
-            let bound = state_bound_clause(state_bound);
-            quote! {
-                {
-                    struct __SerdeStateWithSeed<'state, State: ?Sized #bound> {
-                        state: &'state State,
-                    }
 
-                    impl<'de, 'state, State: ?Sized #bound> _serde::de::DeserializeSeed<'de>
-                        for __SerdeStateWithSeed<'state, State>
-                    {
-                        type Value = #ty;
-
-                        fn deserialize<__D>(
-                            self,
-                            __deserializer: __D,
-                        ) -> ::core::result::Result<Self::Value, __D::Error>
-                        where
-                            __D: _serde::Deserializer<'de>,
-                        {
-                            #with::deserialize_state(self.state, __deserializer)
-                        }
-                    }
+            impl #seed_impl_generics _serde::de::DeserializeSeed<'de>
+                for __SerdeWithSeed #seed_impl_type_generics #seed_where_clause
+            {
+                type Value = #ty;
 
-                    __SerdeStateWithSeed { state }
+                fn deserialize<__D>(
+                    self,
+                    __deserializer: __D,
+                ) -> ::core::result::Result<Self::Value, __D::Error>
+                where
+                    __D: _serde::Deserializer<'de>,
+                {
+                    #call
                 }
             }
+
+            __SerdeWithSeed {
+                state,
+                _marker: ::core::marker::PhantomData,
+            }
         }
     }
 }
@@ -1635,11 +2933,17 @@ fn add_state_param(
 struct FieldType<'a> {
     ty: &'a Type,
     mode: ItemMode,
+    /// From `#[serde(bound)]`, replacing the bound inferred from `ty` and `mode`.
+    bound: Option<&'a [WherePredicate]>,
 }
 
 impl<'a> FieldType<'a> {
     fn new(ty: &'a Type, mode: ItemMode) -> Self {
-        FieldType { ty, mode }
+        FieldType {
+            ty,
+            mode,
+            bound: None,
+        }
     }
 }
 
@@ -1648,7 +2952,15 @@ fn collect_field_types_from_fields<'a>(fields: &'a FieldsDecl<'a>) -> Vec<FieldT
         .fields
         .iter()
         .filter_map(|field| {
-            if field.attrs.skip {
+            if let Some(bound) = &field.attrs.bound.deserialize {
+                return Some(FieldType {
+                    bound: Some(bound),
+                    ..FieldType::new(field.ty(), field.mode())
+                });
+            }
+            if field.attrs.skip_deserializing
+                || matches!(field.attrs.deserialize_with, Some(WithFn::Stateless(_)))
+            {
                 return None;
             }
             Some(FieldType::new(field.ty(), field.mode()))
@@ -1659,11 +2971,53 @@ fn collect_field_types_from_fields<'a>(fields: &'a FieldsDecl<'a>) -> Vec<FieldT
 fn collect_field_types_from_enum<'a>(data: &'a EnumDecl<'a>) -> Vec<FieldType<'a>> {
     let mut result = Vec::new();
     for variant in &data.variants {
+        if matches!(variant.deserialize_with, Some(WithFn::Stateless(_))) {
+            continue;
+        }
         result.extend(collect_field_types_from_fields(&variant.fields));
     }
     result
 }
 
+/// Adds the container's `#[serde(bound)]` if it has one, and the inferred bounds otherwise: on the
+/// field types with perfect derive, or on the type parameters once the state is given.
+fn add_deserialize_bounds(
+    where_clause: &mut Option<syn::WhereClause>,
+    decl: &TypeDecl,
+    field_types: &[FieldType<'_>],
+    state_ty: &TokenStream,
+) {
+    if let Some(bound) = &decl.attrs.bound.deserialize {
+        add_where_predicates(where_clause, bound);
+    } else if decl.attrs.state.is_none() && decl.attrs.state_bound.is_none() {
+        add_deserialize_bounds_from_types(where_clause, field_types, state_ty);
+    } else {
+        add_deserialize_bounds_from_type_params(
+            where_clause,
+            decl.generics,
+            state_ty,
+            decl.attrs.mode,
+        );
+        for field in field_types {
+            add_where_predicates(where_clause, field.bound.unwrap_or_default());
+        }
+    }
+}
+
+fn add_where_predicates(
+    where_clause: &mut Option<syn::WhereClause>,
+    predicates: &[WherePredicate],
+) {
+    if predicates.is_empty() {
+        return;
+    }
+    let clause = where_clause.get_or_insert_with(|| syn::WhereClause {
+        where_token: Default::default(),
+        predicates: Default::default(),
+    });
+    clause.predicates.extend(predicates.iter().cloned());
+}
+
 fn add_deserialize_bounds_from_types(
     where_clause: &mut Option<syn::WhereClause>,
     field_types: &[FieldType<'_>],
@@ -1679,6 +3033,10 @@ fn add_deserialize_bounds_from_types(
     });
 
     for field in field_types {
+        if let Some(bound) = field.bound {
+            clause.predicates.extend(bound.iter().cloned());
+            continue;
+        }
         let ty = field.ty;
         match field.mode {
             ItemMode::Stateful => clause
@@ -1788,12 +3146,19 @@ fn phantom_type(ident: &syn::Ident, generics: &Generics) -> TokenStream {
     quote!(#ident #ty_generics)
 }
 
-fn add_default_bounds_for_skipped(
+/// Requires `Default` for the fields whose missing value comes from `Default::default()`.
+fn add_default_bounds(
     fields: &FieldsDecl<'_>,
+    container_default: bool,
     where_clause: &mut Option<syn::WhereClause>,
 ) {
     for field in &fields.fields {
-        if field.attrs.skip {
+        let uses_default = match field.attrs.default {
+            Some(DefaultValue::Default) => true,
+            Some(_) => false,
+            None => field.attrs.skip_deserializing && !container_default,
+        };
+        if uses_default {
             push_default_bound(where_clause, field.ty());
         }
     }
@@ -1809,6 +3174,73 @@ fn push_default_bound(where_clause: &mut Option<syn::WhereClause>, ty: &Type) {
         .push(parse_quote!(#ty: ::core::default::Default));
 }
 
+/// Whether deserializing the variant can reach the state, so that a failed untagged attempt
+/// may have changed it.
+fn variant_reads_state(variant: &VariantDecl<'_>) -> bool {
+    match &variant.deserialize_with {
+        Some(WithFn::Stateless(_)) => false,
+        Some(WithFn::Stateful(_)) => true,
+        None => variant.fields.fields.iter().any(field_reads_state),
+    }
+}
+
+fn field_reads_state(field: &FieldDecl<'_>) -> bool {
+    if let Some(DefaultValue::State(_)) = &field.attrs.default {
+        return true;
+    }
+    if field.attrs.skip_deserializing {
+        return false;
+    }
+    match &field.attrs.deserialize_with {
+        Some(WithFn::Stateless(_)) => false,
+        Some(WithFn::Stateful(_)) => true,
+        None => field.mode() == ItemMode::Stateful,
+    }
+}
+
+/// The `T` of a flattened `Option<T>` field that is read through its own impls.
+fn flattened_option_type<'a>(field: &FieldDecl<'a>) -> Option<&'a Type> {
+    if !field.attrs.flatten || field.attrs.deserialize_with.is_some() {
+        return None;
+    }
+    let Type::Path(path) = field.ty() else {
+        return None;
+    };
+    if path.qself.is_some() {
+        return None;
+    }
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first() {
+        Some(syn::GenericArgument::Type(inner)) if args.args.len() == 1 => Some(inner),
+        _ => None,
+    }
+}
+
+/// Whether a flattened `Option` field may have to undo a failed attempt.
+fn flattened_option_rollback(fields: &FieldsDecl<'_>) -> bool {
+    !fields.no_rollback
+        && fields
+            .fields
+            .iter()
+            .any(|field| flattened_option_type(field).is_some() && field_reads_state(field))
+}
+
+fn add_rollback_bound(where_clause: &mut Option<syn::WhereClause>, state_ty: &TokenStream) {
+    let clause = where_clause.get_or_insert_with(|| syn::WhereClause {
+        where_token: Default::default(),
+        predicates: Default::default(),
+    });
+    clause
+        .predicates
+        .push(parse_quote!(#state_ty: _serde_state::de::Rollback));
+}
+
 fn add_default_state_bound(where_clause: &mut Option<syn::WhereClause>, state_ty: &Type) {
     let clause = where_clause.get_or_insert_with(|| syn::WhereClause {
         where_token: Default::default(),
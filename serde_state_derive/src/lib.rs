@@ -1,3 +1,5 @@
+#![allow(clippy::too_many_arguments)]
+
 extern crate proc_macro;
 
 use proc_macro::TokenStream;
@@ -5,9 +7,12 @@ use syn::parse_macro_input;
 use syn::DeriveInput;
 
 mod attrs;
+mod case;
 mod de;
 mod dummy;
+mod pretend;
 mod ser;
+mod stateless;
 mod type_decl;
 
 #[proc_macro_derive(SerializeState, attributes(serde, serde_state))]
@@ -25,3 +30,13 @@ pub fn derive_deserialize(input: TokenStream) -> TokenStream {
         .unwrap_or_else(syn::Error::into_compile_error)
         .into()
 }
+
+/// Implements `SerializeState` and `DeserializeState` for any state by delegating to the type's
+/// plain `Serialize`/`Deserialize` impls.
+#[proc_macro_derive(StatelessState)]
+pub fn derive_stateless(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    stateless::expand_derive_stateless(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -0,0 +1,50 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::Member;
+
+use crate::type_decl::{FieldsDecl, TypeData, TypeDecl};
+
+/// Code that reads every field and builds every variant of a remote definition.
+///
+/// The generated impls only ever operate on the remote type, so without this the local
+/// definition would trigger `dead_code` warnings.
+pub fn pretend_used(decl: &TypeDecl) -> TokenStream {
+    let arms = match &decl.data {
+        TypeData::Struct(data) => vec![pretend_arm(quote!(Self), &data.fields)],
+        TypeData::Enum(data) => data
+            .variants
+            .iter()
+            .map(|variant| {
+                let ident = variant.ident;
+                pretend_arm(quote!(Self::#ident), &variant.fields)
+            })
+            .collect(),
+    };
+    quote! {
+        if let ::core::option::Option::Some(__value) = ::core::option::Option::None::<Self> {
+            match __value {
+                #(#arms)*
+            }
+        }
+    }
+}
+
+fn pretend_arm(path: TokenStream, fields: &FieldsDecl) -> TokenStream {
+    let members: Vec<Member> = fields
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| match field.ident() {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::from(index),
+        })
+        .collect();
+    let bindings: Vec<_> = (0..members.len())
+        .map(|index| format_ident!("__v{}", index))
+        .collect();
+    quote! {
+        #path { #(#members: #bindings),* } => {
+            let _ = #path { #(#members: #bindings),* };
+        }
+    }
+}
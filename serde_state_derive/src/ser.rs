@@ -1,14 +1,15 @@
 use crate::{
-    attrs::ItemMode,
-    dummy,
+    attrs::{ItemMode, WithFn},
+    dummy, pretend,
     type_decl::{
-        EnumDecl, FieldDecl, FieldsDecl, FieldsStyle, StructDecl, TypeData, TypeDecl, VariantDecl,
+        EnumDecl, FieldDecl, FieldsDecl, FieldsStyle, IntoProxy, StructDecl, TagType, TypeData,
+        TypeDecl, VariantDecl,
     },
 };
 use proc_macro2::TokenStream;
-use quote::{format_ident, quote};
+use quote::{format_ident, quote, ToTokens};
 use syn::spanned::Spanned;
-use syn::{parse_quote, Data, DeriveInput, Generics, Type};
+use syn::{parse_quote, Data, DeriveInput, GenericParam, Generics, Type, WherePredicate};
 
 pub fn expand_derive_serialize(input: &DeriveInput) -> syn::Result<TokenStream> {
     if let Data::Union(u) = &input.data {
@@ -19,9 +20,43 @@ pub fn expand_derive_serialize(input: &DeriveInput) -> syn::Result<TokenStream>
     }
 
     let decl = TypeDecl::from_derive_input(input)?;
-    let impl_block = match &decl.data {
-        TypeData::Struct(data) => derive_struct(&decl, data)?,
-        TypeData::Enum(data) => derive_enum(&decl, data)?,
+    let parts = match (&decl.attrs.into, &decl.data) {
+        (Some(into), _) => derive_into(&decl, into),
+        (None, TypeData::Struct(data)) => derive_struct(&decl, data)?,
+        (None, TypeData::Enum(data)) => derive_enum(&decl, data)?,
+    };
+    let impl_block = match &decl.attrs.remote {
+        Some(remote) => remote_serialize_fn(&decl, remote, &parts),
+        None => {
+            let SerializeImpl {
+                generics,
+                where_clause,
+                body,
+            } = &parts;
+            let (impl_generics, _, _) = generics.split_for_impl();
+            let (_, ty_generics, _) = decl.generics.split_for_impl();
+            let where_clause_tokens = quote_where_clause(where_clause);
+            let state_tokens = state_type_tokens(&decl);
+            let ident = decl.ident;
+            let default_serde_impl = default_serde_impl(&decl, ident);
+            quote! {
+                #[automatically_derived]
+                impl #impl_generics _serde_state::SerializeState<#state_tokens> for #ident #ty_generics #where_clause_tokens {
+                    fn serialize_state<__S>(
+                        &self,
+                        __state: &#state_tokens,
+                        __serializer: __S,
+                    ) -> ::core::result::Result<__S::Ok, __S::Error>
+                    where
+                        __S: _serde::Serializer,
+                    {
+                        #body
+                    }
+                }
+
+                #default_serde_impl
+            }
+        }
     };
 
     Ok(dummy::wrap_in_const(
@@ -30,120 +65,226 @@ pub fn expand_derive_serialize(input: &DeriveInput) -> syn::Result<TokenStream>
     ))
 }
 
-fn derive_struct(decl: &TypeDecl, data: &StructDecl) -> syn::Result<TokenStream> {
-    let has_explicit_state = decl.attrs.state.is_some();
-    let has_state_bound = decl.attrs.state_bound.is_some();
-    let uses_generic_state = !has_explicit_state;
-    let infer_bounds = !has_explicit_state && !has_state_bound;
-    let impl_generics_storage = add_state_param(
-        decl.generics,
-        uses_generic_state,
-        decl.attrs.state_bound.as_ref(),
-    );
-    let (impl_generics_ref, _, _) = impl_generics_storage.split_for_impl();
-    let impl_generics = quote!(#impl_generics_ref);
-    let (_, ty_generics_ref, _) = decl.generics.split_for_impl();
-    let ty_generics = quote!(#ty_generics_ref);
-    let mut where_clause = decl.generics.where_clause.clone();
+/// What goes into `serialize_state`: the impl's generics, including the state parameter, its
+/// where clause and the body of the method.
+struct SerializeImpl {
+    generics: Generics,
+    where_clause: Option<syn::WhereClause>,
+    body: TokenStream,
+}
+
+/// For `#[serde(remote = "Remote")]`, emits `Def::serialize_state(&Remote, state, serializer)`
+/// for use with `#[serde(with = "Def")]`. The body is generated for `self: &Def` as usual, so it
+/// goes into a private trait implemented for the remote type, in a scope where `Def` names it.
+fn remote_serialize_fn(decl: &TypeDecl, remote: &syn::Path, parts: &SerializeImpl) -> TokenStream {
+    let SerializeImpl {
+        generics,
+        where_clause,
+        body,
+    } = parts;
+    let ident = decl.ident;
+    let vis = decl.vis;
+    let (impl_generics, _, _) = generics.split_for_impl();
+    let (decl_impl_generics, ty_generics, decl_where_clause) = decl.generics.split_for_impl();
+    let where_clause_tokens = quote_where_clause(where_clause);
+    let predicates = where_clause.iter().flat_map(|clause| &clause.predicates);
     let state_tokens = state_type_tokens(decl);
-    let field_types = collect_field_types_from_fields(&data.fields);
-    if infer_bounds {
-        add_serialize_bounds_from_types(&mut where_clause, &field_types, &state_tokens);
-    } else {
-        add_serialize_bounds_from_type_params(
-            &mut where_clause,
-            decl.generics,
-            &state_tokens,
-            decl.attrs.mode,
-        );
-    }
-    let where_clause_tokens = match &where_clause {
-        Some(clause) => quote!(#clause),
-        None => TokenStream::new(),
+    let state_param = match decl.attrs.state {
+        Some(_) => None,
+        None => {
+            let bound = state_bound_clause(decl.attrs.state_bound.as_ref());
+            Some(quote!(__State: ?Sized #bound,))
+        }
     };
-    let ident = decl.ident;
+    let alias = remote_alias(decl, remote);
+    let pretend = pretend::pretend_used(decl);
 
-    let state_bound = decl.attrs.state_bound.as_ref();
-    let explicit_state = decl.attrs.state.as_ref();
-    let body = if decl.attrs.transparent {
-        serialize_transparent(&data.fields, explicit_state, state_bound)?
-    } else {
-        serialize_struct_body(ident, &data.fields, explicit_state, state_bound)?
-    };
+    quote! {
+        trait __SerializeStateRemote<__State: ?Sized> {
+            fn serialize_state<__S>(
+                &self,
+                __state: &__State,
+                __serializer: __S,
+            ) -> ::core::result::Result<__S::Ok, __S::Error>
+            where
+                __S: _serde::Serializer;
+        }
 
-    let default_serde_impl = default_serde_impl(decl, ident);
+        const _: () = {
+            #alias
+
+            impl #impl_generics __SerializeStateRemote<#state_tokens> for #ident #ty_generics #where_clause_tokens {
+                fn serialize_state<__S>(
+                    &self,
+                    __state: &#state_tokens,
+                    __serializer: __S,
+                ) -> ::core::result::Result<__S::Ok, __S::Error>
+                where
+                    __S: _serde::Serializer,
+                {
+                    #body
+                }
+            }
+        };
 
-    Ok(quote! {
         #[automatically_derived]
-        impl #impl_generics _serde_state::SerializeState<#state_tokens> for #ident #ty_generics #where_clause_tokens {
-            fn serialize_state<__S>(
-                &self,
+        impl #decl_impl_generics #ident #ty_generics #decl_where_clause {
+            #vis fn serialize_state<#state_param __S>(
+                __self: &#remote #ty_generics,
                 __state: &#state_tokens,
                 __serializer: __S,
             ) -> ::core::result::Result<__S::Ok, __S::Error>
             where
+                #(#predicates,)*
                 __S: _serde::Serializer,
             {
-                #body
+                #pretend
+                __SerializeStateRemote::serialize_state(__self, __state, __serializer)
             }
         }
+    }
+}
 
-        #default_serde_impl
-    })
+/// `type Def<T> = Remote<T>;`, shadowing the local definition.
+fn remote_alias(decl: &TypeDecl, remote: &syn::Path) -> TokenStream {
+    let ident = decl.ident;
+    let (_, ty_generics, _) = decl.generics.split_for_impl();
+    let params = decl.generics.params.iter().map(|param| match param {
+        GenericParam::Lifetime(param) => {
+            let lifetime = &param.lifetime;
+            quote!(#lifetime)
+        }
+        GenericParam::Type(param) => {
+            let ident = &param.ident;
+            quote!(#ident)
+        }
+        GenericParam::Const(param) => {
+            let ident = &param.ident;
+            let ty = &param.ty;
+            quote!(const #ident: #ty)
+        }
+    });
+    quote! {
+        type #ident <#(#params),*> = #remote #ty_generics;
+    }
 }
 
-fn derive_enum(decl: &TypeDecl, data: &EnumDecl) -> syn::Result<TokenStream> {
-    let has_explicit_state = decl.attrs.state.is_some();
-    let has_state_bound = decl.attrs.state_bound.is_some();
-    let uses_generic_state = !has_explicit_state;
-    let infer_bounds = !has_explicit_state && !has_state_bound;
-    let impl_generics_storage = add_state_param(
+/// Serializes the proxy from `#[serde(into)]` or `#[serde_state(into_state)]` in place of the
+/// value, bounded only by the proxy's impl.
+fn derive_into(decl: &TypeDecl, into: &IntoProxy) -> SerializeImpl {
+    let generics = add_state_param(
         decl.generics,
-        uses_generic_state,
+        decl.attrs.state.is_none(),
         decl.attrs.state_bound.as_ref(),
     );
-    let (impl_generics_ref, _, _) = impl_generics_storage.split_for_impl();
-    let impl_generics = quote!(#impl_generics_ref);
-    let (_, ty_generics_ref, _) = decl.generics.split_for_impl();
-    let ty_generics = quote!(#ty_generics_ref);
     let mut where_clause = decl.generics.where_clause.clone();
     let state_tokens = state_type_tokens(decl);
-    let field_types = collect_field_types_from_enum(data);
-    if infer_bounds {
-        add_serialize_bounds_from_types(&mut where_clause, &field_types, &state_tokens);
-    } else {
-        add_serialize_bounds_from_type_params(
+    match (&decl.attrs.bound.serialize, into) {
+        (Some(bound), _) => add_where_predicates(&mut where_clause, bound),
+        (None, IntoProxy::Into(ty) | IntoProxy::State(_, Some(ty))) => add_where_predicates(
             &mut where_clause,
-            decl.generics,
-            &state_tokens,
-            decl.attrs.mode,
-        );
+            &[parse_quote!(#ty: _serde_state::SerializeState<#state_tokens>)],
+        ),
+        // The proxy type is only known from the function's signature, and the state is concrete.
+        (None, IntoProxy::State(_, None)) => {}
     }
-    let where_clause_tokens = match &where_clause {
-        Some(clause) => quote!(#clause),
-        None => TokenStream::new(),
+    let proxy = match into {
+        IntoProxy::Into(ty) => quote! {
+            <Self as ::core::convert::Into<#ty>>::into(::core::clone::Clone::clone(self))
+        },
+        IntoProxy::State(path, _) => quote!(#path(self, __state)),
+    };
+    let proxy_ty = match into {
+        IntoProxy::State(_, Some(ty)) => Some(quote!(: #ty)),
+        _ => None,
     };
+
+    SerializeImpl {
+        generics,
+        where_clause,
+        body: quote! {
+            let __proxy #proxy_ty = #proxy;
+            _serde_state::SerializeState::serialize_state(&__proxy, __state, __serializer)
+        },
+    }
+}
+
+fn derive_struct(decl: &TypeDecl, data: &StructDecl) -> syn::Result<SerializeImpl> {
+    let generics = add_state_param(
+        decl.generics,
+        decl.attrs.state.is_none(),
+        decl.attrs.state_bound.as_ref(),
+    );
+    let mut where_clause = decl.generics.where_clause.clone();
+    let state_tokens = state_type_tokens(decl);
+    let field_types = collect_field_types_from_fields(&data.fields);
+    add_serialize_bounds(&mut where_clause, decl, &field_types, &state_tokens);
     let ident = decl.ident;
-    let explicit_state = decl.attrs.state.as_ref();
-    let body = serialize_enum_body(ident, data, explicit_state, decl.attrs.state_bound.as_ref())?;
-    let default_serde_impl = default_serde_impl(decl, ident);
 
-    Ok(quote! {
-        #[automatically_derived]
-        impl #impl_generics _serde_state::SerializeState<#state_tokens> for #ident #ty_generics #where_clause_tokens {
-            fn serialize_state<__S>(
-                &self,
-                __state: &#state_tokens,
-                __serializer: __S,
-            ) -> ::core::result::Result<__S::Ok, __S::Error>
-            where
-                __S: _serde::Serializer,
-            {
-                #body
-            }
-        }
+    let container = ContainerGenerics {
+        ident,
+        generics: decl.generics,
+        impl_generics: &generics,
+        where_clause: &where_clause,
+        state_tokens: &state_tokens,
+    };
+    let body = if decl.attrs.transparent {
+        serialize_transparent(&data.fields, &container)?
+    } else {
+        serialize_struct_body(ident, &data.fields, &container)?
+    };
+
+    Ok(SerializeImpl {
+        generics,
+        where_clause,
+        body,
+    })
+}
+
+fn derive_enum(decl: &TypeDecl, data: &EnumDecl) -> syn::Result<SerializeImpl> {
+    let generics = add_state_param(
+        decl.generics,
+        decl.attrs.state.is_none(),
+        decl.attrs.state_bound.as_ref(),
+    );
+    let mut where_clause = decl.generics.where_clause.clone();
+    let state_tokens = state_type_tokens(decl);
+    let field_types = collect_field_types_from_enum(data);
+    add_serialize_bounds(&mut where_clause, decl, &field_types, &state_tokens);
+    let ident = decl.ident;
+    let container = ContainerGenerics {
+        ident,
+        generics: decl.generics,
+        impl_generics: &generics,
+        where_clause: &where_clause,
+        state_tokens: &state_tokens,
+    };
+    let body = serialize_enum_body(ident, data, &decl.attrs.tag, &container)?;
+    let has_content = data
+        .variants
+        .iter()
+        .any(|variant| !matches!(variant.fields.style, FieldsStyle::Unit));
+    let has_flattened_variant = data
+        .variants
+        .iter()
+        .any(|variant| !variant.untagged && variant.fields.has_flatten());
+    let needs_content_wrapper = match &decl.attrs.tag {
+        TagType::Adjacent { .. } => has_content,
+        TagType::External => has_flattened_variant,
+        _ => false,
+    };
+    let content_wrapper = match needs_content_wrapper {
+        true => variant_content_wrapper(decl, data, &generics, &where_clause, &state_tokens),
+        false => TokenStream::new(),
+    };
 
-        #default_serde_impl
+    Ok(SerializeImpl {
+        generics,
+        where_clause,
+        body: quote! {
+            #content_wrapper
+            #body
+        },
     })
 }
 
@@ -156,15 +297,14 @@ fn state_bound_clause(bound: Option<&Type>) -> TokenStream {
 
 fn serialize_transparent(
     fields: &FieldsDecl<'_>,
-    explicit_state: Option<&Type>,
-    state_bound: Option<&Type>,
+    container: &ContainerGenerics<'_>,
 ) -> syn::Result<TokenStream> {
     match fields.style {
         FieldsStyle::Named if fields.fields.len() == 1 => {
             let field = &fields.fields[0];
             let ident = field.ident().unwrap();
-            let call =
-                serialize_field_expr(field, quote!(&self.#ident), explicit_state, state_bound);
+            let value = self_field(field, quote!(#ident));
+            let call = serialize_field_expr(field, value, container);
             Ok(quote! {
                 _serde::Serialize::serialize(#call, __serializer)
             })
@@ -172,8 +312,8 @@ fn serialize_transparent(
         FieldsStyle::Unnamed if fields.fields.len() == 1 => {
             let index = syn::Index::from(0);
             let field = &fields.fields[0];
-            let call =
-                serialize_field_expr(field, quote!(&self.#index), explicit_state, state_bound);
+            let value = self_field(field, quote!(#index));
+            let call = serialize_field_expr(field, value, container);
             Ok(quote! {
                 _serde::Serialize::serialize(#call, __serializer)
             })
@@ -188,16 +328,11 @@ fn serialize_transparent(
 fn serialize_struct_body(
     ident: &syn::Ident,
     fields: &FieldsDecl<'_>,
-    explicit_state: Option<&Type>,
-    state_bound: Option<&Type>,
+    container: &ContainerGenerics<'_>,
 ) -> syn::Result<TokenStream> {
     Ok(match fields.style {
-        FieldsStyle::Named => {
-            serialize_named_fields(ident, &fields.fields, explicit_state, state_bound)?
-        }
-        FieldsStyle::Unnamed => {
-            serialize_unnamed_fields(ident, &fields.fields, explicit_state, state_bound)
-        }
+        FieldsStyle::Named => serialize_named_fields(ident, &fields.fields, container)?,
+        FieldsStyle::Unnamed => serialize_unnamed_fields(ident, &fields.fields, container),
         FieldsStyle::Unit => serialize_unit_struct(ident),
     })
 }
@@ -205,31 +340,29 @@ fn serialize_struct_body(
 fn serialize_named_fields(
     ident: &syn::Ident,
     fields: &[FieldDecl<'_>],
-    explicit_state: Option<&Type>,
-    state_bound: Option<&Type>,
+    container: &ContainerGenerics<'_>,
 ) -> syn::Result<TokenStream> {
+    if fields.iter().any(|field| field.attrs.flatten) {
+        return Ok(serialize_flattened_fields(
+            fields,
+            None,
+            |field| {
+                let ident = field.ident().unwrap();
+                self_field(field, quote!(#ident))
+            },
+            container,
+        ));
+    }
     let type_name = ident.to_string();
-    let len = fields.iter().filter(|field| !field.attrs.skip).count();
-    let serialize_fields = fields
-        .iter()
-        .filter(|field| !field.attrs.skip)
-        .map(|field| {
-            let field_ident = field.ident().unwrap();
-            let key = field.attrs.key(field_ident);
-            let call = serialize_field_expr(
-                field,
-                quote!(&self.#field_ident),
-                explicit_state,
-                state_bound,
-            );
-            quote! {
-                _serde::ser::SerializeStruct::serialize_field(
-                    &mut __serde_state,
-                    #key,
-                    #call,
-                )?;
-            }
-        });
+    let (len, serialize_fields) = serialize_struct_fields(
+        fields,
+        quote!(_serde::ser::SerializeStruct),
+        |field| {
+            let ident = field.ident().unwrap();
+            self_field(field, quote!(#ident))
+        },
+        container,
+    );
 
     Ok(quote! {
         let mut __serde_state = _serde::Serializer::serialize_struct(__serializer, #type_name, #len)?;
@@ -241,19 +374,14 @@ fn serialize_named_fields(
 fn serialize_unnamed_fields(
     ident: &syn::Ident,
     fields: &[FieldDecl<'_>],
-    explicit_state: Option<&Type>,
-    state_bound: Option<&Type>,
+    container: &ContainerGenerics<'_>,
 ) -> TokenStream {
     match fields.len() {
         0 => serialize_unit_struct(ident),
         1 => {
             let index = syn::Index::from(0);
-            let call = serialize_field_expr(
-                &fields[0],
-                quote!(&self.#index),
-                explicit_state,
-                state_bound,
-            );
+            let value = self_field(&fields[0], quote!(#index));
+            let call = serialize_field_expr(&fields[0], value, container);
             quote! {
                 _serde::Serializer::serialize_newtype_struct(
                     __serializer,
@@ -262,18 +390,23 @@ fn serialize_unnamed_fields(
                 )
             }
         }
-        len => {
-            let serialize_fields = fields.iter().enumerate().map(|(i, field)| {
-                let index = syn::Index::from(i);
-                let call =
-                    serialize_field_expr(field, quote!(&self.#index), explicit_state, state_bound);
-                quote! {
-                    _serde::ser::SerializeTupleStruct::serialize_field(
-                        &mut __serde_state,
-                        #call,
-                    )?;
-                }
-            });
+        _ => {
+            let len = serialized_tuple_len(fields);
+            let serialize_fields = fields
+                .iter()
+                .enumerate()
+                .filter(|(_, field)| !field.attrs.skip_serializing)
+                .map(|(i, field)| {
+                    let index = syn::Index::from(i);
+                    let value = self_field(field, quote!(#index));
+                    let call = serialize_field_expr(field, value, container);
+                    quote! {
+                        _serde::ser::SerializeTupleStruct::serialize_field(
+                            &mut __serde_state,
+                            #call,
+                        )?;
+                    }
+                });
             quote! {
                 let mut __serde_state = _serde::Serializer::serialize_tuple_struct(
                     __serializer,
@@ -287,6 +420,139 @@ fn serialize_unnamed_fields(
     }
 }
 
+/// The number of elements a tuple struct or variant writes, leaving out skipped fields.
+fn serialized_tuple_len(fields: &[FieldDecl<'_>]) -> usize {
+    fields
+        .iter()
+        .filter(|field| !field.attrs.skip_serializing)
+        .count()
+}
+
+/// Binds the fields of a tuple variant, leaving the skipped ones unbound.
+fn tuple_patterns(fields: &[FieldDecl<'_>], bindings: &[syn::Ident]) -> Vec<TokenStream> {
+    fields
+        .iter()
+        .zip(bindings)
+        .map(|(field, binding)| {
+            if field.attrs.skip_serializing {
+                quote!(_)
+            } else {
+                quote!(ref #binding)
+            }
+        })
+        .collect()
+}
+
+/// Serializes named fields as the entries of a map, which lets `#[serde(flatten)]` fields add
+/// entries of their own. `tag` is an internal tag and variant name to write first.
+fn serialize_flattened_fields(
+    fields: &[FieldDecl<'_>],
+    tag: Option<(&str, &str)>,
+    access: impl Fn(&FieldDecl<'_>) -> TokenStream,
+    container: &ContainerGenerics<'_>,
+) -> TokenStream {
+    let serialize_tag = tag.map(|(tag, variant_name)| {
+        quote! {
+            _serde::ser::SerializeMap::serialize_entry(&mut __serde_state, #tag, #variant_name)?;
+        }
+    });
+    let serialize_fields = fields
+        .iter()
+        .filter(|field| !field.attrs.skip_serializing)
+        .map(|field| {
+            let value = access(field);
+            let call = serialize_field_expr(field, value.clone(), container);
+            if field.attrs.flatten {
+                quote! {
+                    _serde::Serialize::serialize(
+                        #call,
+                        _serde_state::__private::ser::FlatMapSerializer(&mut __serde_state),
+                    )?;
+                }
+            } else {
+                let key = &field.name.serialize;
+                let serialize = quote! {
+                    _serde::ser::SerializeMap::serialize_entry(&mut __serde_state, #key, #call)?;
+                };
+                match &field.attrs.skip_serializing_if {
+                    Some(skip_if) => quote! {
+                        if !#skip_if(#value) {
+                            #serialize
+                        }
+                    },
+                    None => serialize,
+                }
+            }
+        });
+
+    quote! {
+        let mut __serde_state =
+            _serde::Serializer::serialize_map(__serializer, ::core::option::Option::None)?;
+        #serialize_tag
+        #(#serialize_fields)*
+        _serde::ser::SerializeMap::end(__serde_state)
+    }
+}
+
+/// Serializes named fields through `trait_path`, `SerializeStruct` or `SerializeStructVariant`.
+/// Returns the length hint, which counts only the fields that will be written, and the
+/// statements writing them. Fields omitted by `skip_serializing_if` go through `skip_field`.
+fn serialize_struct_fields(
+    fields: &[FieldDecl<'_>],
+    trait_path: TokenStream,
+    access: impl Fn(&FieldDecl<'_>) -> TokenStream,
+    container: &ContainerGenerics<'_>,
+) -> (TokenStream, Vec<TokenStream>) {
+    let included: Vec<_> = fields
+        .iter()
+        .filter(|field| !field.attrs.skip_serializing)
+        .collect();
+    let always = included
+        .iter()
+        .filter(|field| field.attrs.skip_serializing_if.is_none())
+        .count();
+    let conditional = included.iter().filter_map(|field| {
+        let skip_if = field.attrs.skip_serializing_if.as_ref()?;
+        let value = access(field);
+        Some(quote!(+ if #skip_if(#value) { 0 } else { 1 }))
+    });
+    let len = quote!(#always #(#conditional)*);
+
+    let statements = included
+        .iter()
+        .map(|field| {
+            let value = access(field);
+            let key = &field.name.serialize;
+            let call = serialize_field_expr(field, value.clone(), container);
+            let serialize = quote! {
+                #trait_path::serialize_field(&mut __serde_state, #key, #call)?;
+            };
+            match &field.attrs.skip_serializing_if {
+                Some(skip_if) => quote! {
+                    if #skip_if(#value) {
+                        #trait_path::skip_field(&mut __serde_state, #key)?;
+                    } else {
+                        #serialize
+                    }
+                },
+                None => serialize,
+            }
+        })
+        .collect();
+    (len, statements)
+}
+
+/// `&self.member`, or `&getter(self)` for a field of a remote struct read through a getter.
+fn self_field(field: &FieldDecl<'_>, member: TokenStream) -> TokenStream {
+    match &field.attrs.getter {
+        Some(getter) => {
+            let ty = field.ty();
+            quote!(_serde_state::__private::ser::constrain::<#ty>(&#getter(self)))
+        }
+        None => quote!(&self.#member),
+    }
+}
+
 fn serialize_unit_struct(ident: &syn::Ident) -> TokenStream {
     quote! {
         _serde::Serializer::serialize_unit_struct(__serializer, stringify!(#ident))
@@ -296,79 +562,149 @@ fn serialize_unit_struct(ident: &syn::Ident) -> TokenStream {
 fn serialize_field_expr(
     field: &FieldDecl<'_>,
     value: TokenStream,
-    explicit_state: Option<&Type>,
-    state_bound: Option<&Type>,
+    container: &ContainerGenerics<'_>,
 ) -> TokenStream {
-    if let Some(with) = &field.attrs.with {
-        let ty = field.ty();
-        match explicit_state {
-            Some(state_ty) => quote!(&{
-                struct __SerdeStateWith<'state> {
-                    value: &'state #ty,
-                    state: &'state #state_ty,
-                }
-
-                impl<'state> _serde::Serialize for __SerdeStateWith<'state> {
-                    fn serialize<__S>(&self, __serializer: __S) -> Result<__S::Ok, __S::Error>
-                    where
-                        __S: _serde::Serializer,
-                    {
-                        #with::serialize_state(self.value, self.state, __serializer)
-                    }
-                }
+    if let Some(with) = &field.attrs.serialize_with {
+        serialize_with_wrapper(with, &[field.ty()], &[value], container)
+    } else {
+        match field.mode() {
+            ItemMode::Stateful => quote!(&_serde_state::ser::StateRef::new(#value, __state)),
+            ItemMode::Stateless => quote!(#value),
+        }
+    }
+}
 
-                __SerdeStateWith { value: #value, state: __state }
-            }),
-            None => {
-                let bound = state_bound_clause(state_bound);
-                quote!(&{
-                    struct __SerdeStateWith<'state, State: ?Sized #bound> {
-                        value: &'state #ty,
-                        state: &'state State,
-                    }
+/// What a `serialize_with` wrapper needs to be generic over the same parameters as the impl it
+/// is declared in, since the wrapped field types may mention them.
+struct ContainerGenerics<'a> {
+    ident: &'a syn::Ident,
+    generics: &'a Generics,
+    impl_generics: &'a Generics,
+    where_clause: &'a Option<syn::WhereClause>,
+    state_tokens: &'a TokenStream,
+}
 
-                    impl<'state, State: ?Sized #bound> _serde::Serialize
-                        for __SerdeStateWith<'state, State>
-                    {
-                        fn serialize<__S>(&self, __serializer: __S) -> Result<__S::Ok, __S::Error>
-                        where
-                            __S: _serde::Serializer,
-                        {
-                            #with::serialize_state(self.value, self.state, __serializer)
-                        }
-                    }
+/// Wraps `values` in a `Serialize` impl that calls the `serialize_with` function `with` on them,
+/// followed by the state if the function takes it and the serializer.
+fn serialize_with_wrapper(
+    with: &WithFn,
+    types: &[&Type],
+    values: &[TokenStream],
+    container: &ContainerGenerics<'_>,
+) -> TokenStream {
+    let ContainerGenerics {
+        ident,
+        generics,
+        impl_generics,
+        where_clause,
+        state_tokens,
+    } = *container;
+    let mut wrapper_generics = impl_generics.clone();
+    wrapper_generics.params.insert(0, parse_quote!('__a));
+    let (wrapper_impl_generics, wrapper_ty_generics, _) = wrapper_generics.split_for_impl();
+    let (_, ty_generics, container_where_clause) = generics.split_for_impl();
+    let where_clause_tokens = quote_where_clause(where_clause);
+    let indices = (0..types.len()).map(syn::Index::from);
+    let call = match with {
+        WithFn::Stateless(path) => quote!(#path(#(self.values.#indices,)* __serializer)),
+        WithFn::Stateful(path) => quote!(#path(#(self.values.#indices,)* self.state, __serializer)),
+    };
+    quote!(&{
+        struct __SerdeWith #wrapper_impl_generics #container_where_clause {
+            values: (#(&'__a #types,)*),
+            state: &'__a #state_tokens,
+            _marker: ::core::marker::PhantomData<&'__a #ident #ty_generics>,
+        }
 
-                    __SerdeStateWith { value: #value, state: __state }
-                })
+        impl #wrapper_impl_generics _serde::Serialize
+            for __SerdeWith #wrapper_ty_generics #where_clause_tokens
+        {
+            fn serialize<__S>(&self, __serializer: __S) -> Result<__S::Ok, __S::Error>
+            where
+                __S: _serde::Serializer,
+            {
+                #call
             }
         }
-    } else {
-        match field.mode() {
-            ItemMode::Stateful => quote!(&_serde_state::__private::wrap_serialize(#value, __state)),
-            ItemMode::Stateless => quote!(#value),
+
+        __SerdeWith {
+            values: (#(#values,)*),
+            state: __state,
+            _marker: ::core::marker::PhantomData,
         }
-    }
+    })
+}
+
+/// For a variant with `#[serde(serialize_with)]`, returns the pattern binding its fields by
+/// reference, to follow the variant's path, and the wrapper serializing them.
+fn serialize_variant_with(
+    variant: &VariantDecl<'_>,
+    with: &WithFn,
+    index: u32,
+    container: &ContainerGenerics<'_>,
+) -> (TokenStream, TokenStream) {
+    let fields = &variant.fields.fields;
+    let bindings: Vec<_> = match variant.fields.style {
+        FieldsStyle::Named => fields
+            .iter()
+            .map(|field| field.ident().unwrap().clone())
+            .collect(),
+        _ => (0..fields.len())
+            .map(|i| format_ident!("__variant_{}_field{}", index, i))
+            .collect(),
+    };
+    let pattern = match variant.fields.style {
+        FieldsStyle::Unit => quote!(),
+        FieldsStyle::Unnamed => quote!(( #(ref #bindings),* )),
+        FieldsStyle::Named => quote!({ #(ref #bindings),* }),
+    };
+    let types: Vec<_> = fields.iter().map(|field| field.ty()).collect();
+    let values: Vec<_> = bindings.iter().map(|binding| quote!(#binding)).collect();
+    let wrapper = serialize_with_wrapper(with, &types, &values, container);
+    (pattern, wrapper)
 }
 
 fn serialize_enum_body(
     ident: &syn::Ident,
     data: &EnumDecl<'_>,
-    explicit_state: Option<&Type>,
-    state_bound: Option<&Type>,
+    tag: &TagType,
+    container: &ContainerGenerics<'_>,
 ) -> syn::Result<TokenStream> {
     let type_name = ident.to_string();
     let variants = data
         .variants
         .iter()
         .enumerate()
-        .map(|(index, variant)| {
-            serialize_enum_variant(
+        .map(|(index, variant)| match tag {
+            _ if variant.untagged => Ok(serialize_untagged_variant(
+                ident,
+                variant,
+                index as u32,
+                container,
+            )),
+            TagType::Untagged => Ok(serialize_untagged_variant(
+                ident,
+                variant,
+                index as u32,
+                container,
+            )),
+            TagType::External => {
+                serialize_enum_variant(variant, index as u32, &type_name, container)
+            }
+            TagType::Internal { tag } => Ok(serialize_internally_tagged_variant(
+                variant,
+                index as u32,
+                &type_name,
+                tag,
+                container,
+            )),
+            TagType::Adjacent { tag, content } => Ok(serialize_adjacently_tagged_variant(
                 variant,
                 index as u32,
                 &type_name,
-                explicit_state,
-                state_bound,
-            )
+                tag,
+                content,
+            )),
         })
         .collect::<syn::Result<Vec<_>>>()?;
 
@@ -383,11 +719,24 @@ fn serialize_enum_variant(
     variant: &VariantDecl<'_>,
     index: u32,
     type_name: &str,
-    explicit_state: Option<&Type>,
-    state_bound: Option<&Type>,
+    container: &ContainerGenerics<'_>,
 ) -> syn::Result<TokenStream> {
     let variant_ident = variant.ident;
-    let variant_name = variant_ident.to_string();
+    let variant_name = &variant.name.serialize;
+    if let Some(with) = &variant.serialize_with {
+        let (pattern, wrapper) = serialize_variant_with(variant, with, index, container);
+        return Ok(quote! {
+            Self::#variant_ident #pattern => {
+                _serde::Serializer::serialize_newtype_variant(
+                    __serializer,
+                    #type_name,
+                    #index,
+                    #variant_name,
+                    #wrapper,
+                )
+            }
+        });
+    }
     let tokens = match variant.fields.style {
         FieldsStyle::Unit => {
             quote! {
@@ -404,7 +753,7 @@ fn serialize_enum_variant(
         FieldsStyle::Unnamed if variant.fields.fields.len() == 1 => {
             let binding = format_ident!("__variant_{}_field", index);
             let field = &variant.fields.fields[0];
-            let call = serialize_field_expr(field, quote!(#binding), explicit_state, state_bound);
+            let call = serialize_field_expr(field, quote!(#binding), container);
             quote! {
                 Self::#variant_ident(ref #binding) => {
                     _serde::Serializer::serialize_newtype_variant(
@@ -418,30 +767,26 @@ fn serialize_enum_variant(
             }
         }
         FieldsStyle::Unnamed => {
-            let len = variant.fields.fields.len();
-            let bindings: Vec<_> = (0..len)
+            let len = serialized_tuple_len(&variant.fields.fields);
+            let bindings: Vec<_> = (0..variant.fields.fields.len())
                 .map(|i| format_ident!("__variant_{}_field{}", index, i))
                 .collect();
-            let serialize_fields =
-                bindings
-                    .iter()
-                    .zip(variant.fields.fields.iter())
-                    .map(|(binding, field)| {
-                        let call = serialize_field_expr(
-                            field,
-                            quote!(#binding),
-                            explicit_state,
-                            state_bound,
-                        );
-                        quote! {
-                            _serde::ser::SerializeTupleVariant::serialize_field(
-                                &mut __serde_state,
-                                #call,
-                            )?;
-                        }
-                    });
+            let patterns = tuple_patterns(&variant.fields.fields, &bindings);
+            let serialize_fields = bindings
+                .iter()
+                .zip(variant.fields.fields.iter())
+                .filter(|(_, field)| !field.attrs.skip_serializing)
+                .map(|(binding, field)| {
+                    let call = serialize_field_expr(field, quote!(#binding), container);
+                    quote! {
+                        _serde::ser::SerializeTupleVariant::serialize_field(
+                            &mut __serde_state,
+                            #call,
+                        )?;
+                    }
+                });
             quote! {
-                Self::#variant_ident( #(ref #bindings),* ) => {
+                Self::#variant_ident( #(#patterns),* ) => {
                     let mut __serde_state = _serde::Serializer::serialize_tuple_variant(
                         __serializer,
                         #type_name,
@@ -454,6 +799,20 @@ fn serialize_enum_variant(
                 }
             }
         }
+        FieldsStyle::Named if variant.fields.has_flatten() => quote! {
+            Self::#variant_ident { .. } => {
+                _serde::Serializer::serialize_newtype_variant(
+                    __serializer,
+                    #type_name,
+                    #index,
+                    #variant_name,
+                    &__VariantContent {
+                        value: self,
+                        state: __state,
+                    },
+                )
+            }
+        },
         FieldsStyle::Named => {
             let field_idents: Vec<_> = variant
                 .fields
@@ -461,64 +820,379 @@ fn serialize_enum_variant(
                 .iter()
                 .map(|field| field.ident().unwrap())
                 .collect();
-            let len = variant
+            let (len, serialize_fields) = serialize_struct_fields(
+                &variant.fields.fields,
+                quote!(_serde::ser::SerializeStructVariant),
+                |field| field.ident().unwrap().to_token_stream(),
+                container,
+            );
+            quote! {
+                Self::#variant_ident { #(ref #field_idents),* } => {
+                    let mut __serde_state = _serde::Serializer::serialize_struct_variant(
+                        __serializer,
+                        #type_name,
+                        #index,
+                        #variant_name,
+                        #len,
+                    )?;
+                    #(#serialize_fields)*
+                    _serde::ser::SerializeStructVariant::end(__serde_state)
+                }
+            }
+        }
+    };
+
+    Ok(tokens)
+}
+
+fn serialize_internally_tagged_variant(
+    variant: &VariantDecl<'_>,
+    index: u32,
+    type_name: &str,
+    tag: &str,
+    container: &ContainerGenerics<'_>,
+) -> TokenStream {
+    let variant_ident = variant.ident;
+    let variant_name = &variant.name.serialize;
+    if let Some(with) = &variant.serialize_with {
+        let (pattern, wrapper) = serialize_variant_with(variant, with, index, container);
+        return quote! {
+            Self::#variant_ident #pattern => {
+                _serde_state::__private::ser::serialize_tagged_newtype(
+                    __serializer,
+                    #type_name,
+                    #variant_name,
+                    #tag,
+                    #variant_name,
+                    #wrapper,
+                )
+            }
+        };
+    }
+    match variant.fields.style {
+        FieldsStyle::Unit => quote! {
+            Self::#variant_ident => {
+                let mut __serde_state = _serde::Serializer::serialize_struct(
+                    __serializer,
+                    #type_name,
+                    1,
+                )?;
+                _serde::ser::SerializeStruct::serialize_field(
+                    &mut __serde_state,
+                    #tag,
+                    #variant_name,
+                )?;
+                _serde::ser::SerializeStruct::end(__serde_state)
+            }
+        },
+        FieldsStyle::Unnamed => {
+            let binding = format_ident!("__variant_{}_field", index);
+            let field = &variant.fields.fields[0];
+            let call = serialize_field_expr(field, quote!(#binding), container);
+            quote! {
+                Self::#variant_ident(ref #binding) => {
+                    _serde_state::__private::ser::serialize_tagged_newtype(
+                        __serializer,
+                        #type_name,
+                        #variant_name,
+                        #tag,
+                        #variant_name,
+                        #call,
+                    )
+                }
+            }
+        }
+        FieldsStyle::Named if variant.fields.has_flatten() => {
+            let field_idents = variant
                 .fields
                 .fields
                 .iter()
-                .filter(|field| !field.attrs.skip)
-                .count();
-            let serialize_fields = variant
+                .map(|field| field.ident().unwrap());
+            let body = serialize_flattened_fields(
+                &variant.fields.fields,
+                Some((tag, variant_name)),
+                |field| field.ident().unwrap().to_token_stream(),
+                container,
+            );
+            quote! {
+                Self::#variant_ident { #(ref #field_idents),* } => {
+                    #body
+                }
+            }
+        }
+        FieldsStyle::Named => {
+            let field_idents: Vec<_> = variant
                 .fields
                 .fields
                 .iter()
-                .filter(|field| !field.attrs.skip)
-                .map(|field| {
-                    let ident = field.ident().unwrap();
-                    let name = field.attrs.key(ident);
-                    let call =
-                        serialize_field_expr(field, quote!(#ident), explicit_state, state_bound);
+                .map(|field| field.ident().unwrap())
+                .collect();
+            let (len, serialize_fields) = serialize_struct_fields(
+                &variant.fields.fields,
+                quote!(_serde::ser::SerializeStruct),
+                |field| field.ident().unwrap().to_token_stream(),
+                container,
+            );
+            quote! {
+                Self::#variant_ident { #(ref #field_idents),* } => {
+                    let mut __serde_state = _serde::Serializer::serialize_struct(
+                        __serializer,
+                        #type_name,
+                        1 + #len,
+                    )?;
+                    _serde::ser::SerializeStruct::serialize_field(
+                        &mut __serde_state,
+                        #tag,
+                        #variant_name,
+                    )?;
+                    #(#serialize_fields)*
+                    _serde::ser::SerializeStruct::end(__serde_state)
+                }
+            }
+        }
+    }
+}
+
+fn serialize_adjacently_tagged_variant(
+    variant: &VariantDecl<'_>,
+    index: u32,
+    type_name: &str,
+    tag: &str,
+    content: &str,
+) -> TokenStream {
+    let variant_ident = variant.ident;
+    let variant_name = &variant.name.serialize;
+    let serialize_variant = quote! {
+        &_serde_state::__private::ser::AdjacentlyTaggedEnumVariant {
+            enum_name: #type_name,
+            variant_index: #index,
+            variant_name: #variant_name,
+        }
+    };
+    match variant.fields.style {
+        FieldsStyle::Unit if variant.serialize_with.is_none() => quote! {
+            Self::#variant_ident => {
+                let mut __serde_state = _serde::Serializer::serialize_struct(
+                    __serializer,
+                    #type_name,
+                    1,
+                )?;
+                _serde::ser::SerializeStruct::serialize_field(
+                    &mut __serde_state,
+                    #tag,
+                    #serialize_variant,
+                )?;
+                _serde::ser::SerializeStruct::end(__serde_state)
+            }
+        },
+        // The content of variants with `serialize_with` is written even if they have no fields.
+        _ => quote! {
+            Self::#variant_ident { .. } => {
+                let mut __serde_state = _serde::Serializer::serialize_struct(
+                    __serializer,
+                    #type_name,
+                    2,
+                )?;
+                _serde::ser::SerializeStruct::serialize_field(
+                    &mut __serde_state,
+                    #tag,
+                    #serialize_variant,
+                )?;
+                _serde::ser::SerializeStruct::serialize_field(
+                    &mut __serde_state,
+                    #content,
+                    &__VariantContent {
+                        value: self,
+                        state: __state,
+                    },
+                )?;
+                _serde::ser::SerializeStruct::end(__serde_state)
+            }
+        },
+    }
+}
+
+/// Emits `__VariantContent`, a `Serialize` view of the enum that writes only the content of the
+/// current variant, using the same layout as an untagged variant. Used for the content of
+/// adjacently tagged enums and of externally tagged struct variants with flattened fields.
+fn variant_content_wrapper(
+    decl: &TypeDecl,
+    data: &EnumDecl<'_>,
+    impl_generics: &Generics,
+    where_clause: &Option<syn::WhereClause>,
+    state_tokens: &TokenStream,
+) -> TokenStream {
+    let ident = decl.ident;
+    let container = ContainerGenerics {
+        ident,
+        generics: decl.generics,
+        impl_generics,
+        where_clause,
+        state_tokens,
+    };
+    let mut wrapper_generics = impl_generics.clone();
+    wrapper_generics.params.insert(0, parse_quote!('__a));
+    let (wrapper_impl_generics, wrapper_ty_generics, _) = wrapper_generics.split_for_impl();
+    let (_, ty_generics, enum_where_clause) = decl.generics.split_for_impl();
+    let where_clause_tokens = quote_where_clause(where_clause);
+    let arms = data.variants.iter().enumerate().map(|(index, variant)| {
+        serialize_untagged_variant(ident, variant, index as u32, &container)
+    });
+
+    quote! {
+        struct __VariantContent #wrapper_impl_generics #enum_where_clause {
+            value: &'__a #ident #ty_generics,
+            state: &'__a #state_tokens,
+        }
+
+        impl #wrapper_impl_generics _serde::Serialize
+            for __VariantContent #wrapper_ty_generics #where_clause_tokens
+        {
+            fn serialize<__S>(&self, __serializer: __S) -> ::core::result::Result<__S::Ok, __S::Error>
+            where
+                __S: _serde::Serializer,
+            {
+                let __state = self.state;
+                match *self.value {
+                    #(#arms)*
+                }
+            }
+        }
+    }
+}
+
+/// Serializes a variant's fields without any indication of which variant they belong to.
+fn serialize_untagged_variant(
+    ident: &syn::Ident,
+    variant: &VariantDecl<'_>,
+    index: u32,
+    container: &ContainerGenerics<'_>,
+) -> TokenStream {
+    let variant_ident = variant.ident;
+    let variant_name = &variant.name.serialize;
+    if let Some(with) = &variant.serialize_with {
+        let (pattern, wrapper) = serialize_variant_with(variant, with, index, container);
+        return quote! {
+            #ident::#variant_ident #pattern => _serde::Serialize::serialize(#wrapper, __serializer),
+        };
+    }
+    match variant.fields.style {
+        FieldsStyle::Unit => quote! {
+            #ident::#variant_ident => _serde::Serializer::serialize_unit(__serializer),
+        },
+        FieldsStyle::Unnamed if variant.fields.fields.len() == 1 => {
+            let binding = format_ident!("__variant_{}_field", index);
+            let field = &variant.fields.fields[0];
+            let call = serialize_field_expr(field, quote!(#binding), container);
+            quote! {
+                #ident::#variant_ident(ref #binding) => {
+                    _serde::Serialize::serialize(#call, __serializer)
+                }
+            }
+        }
+        FieldsStyle::Unnamed => {
+            let len = serialized_tuple_len(&variant.fields.fields);
+            let bindings: Vec<_> = (0..variant.fields.fields.len())
+                .map(|i| format_ident!("__variant_{}_field{}", index, i))
+                .collect();
+            let patterns = tuple_patterns(&variant.fields.fields, &bindings);
+            let serialize_fields = bindings
+                .iter()
+                .zip(variant.fields.fields.iter())
+                .filter(|(_, field)| !field.attrs.skip_serializing)
+                .map(|(binding, field)| {
+                    let call = serialize_field_expr(field, quote!(#binding), container);
                     quote! {
-                        _serde::ser::SerializeStructVariant::serialize_field(
+                        _serde::ser::SerializeTuple::serialize_element(
                             &mut __serde_state,
-                            #name,
                             #call,
                         )?;
                     }
                 });
             quote! {
-                Self::#variant_ident { #(ref #field_idents),* } => {
-                    let mut __serde_state = _serde::Serializer::serialize_struct_variant(
+                #ident::#variant_ident( #(#patterns),* ) => {
+                    let mut __serde_state = _serde::Serializer::serialize_tuple(__serializer, #len)?;
+                    #(#serialize_fields)*
+                    _serde::ser::SerializeTuple::end(__serde_state)
+                }
+            }
+        }
+        FieldsStyle::Named if variant.fields.has_flatten() => {
+            let field_idents = variant
+                .fields
+                .fields
+                .iter()
+                .map(|field| field.ident().unwrap());
+            let body = serialize_flattened_fields(
+                &variant.fields.fields,
+                None,
+                |field| field.ident().unwrap().to_token_stream(),
+                container,
+            );
+            quote! {
+                #ident::#variant_ident { #(ref #field_idents),* } => {
+                    #body
+                }
+            }
+        }
+        FieldsStyle::Named => {
+            let field_idents: Vec<_> = variant
+                .fields
+                .fields
+                .iter()
+                .map(|field| field.ident().unwrap())
+                .collect();
+            let (len, serialize_fields) = serialize_struct_fields(
+                &variant.fields.fields,
+                quote!(_serde::ser::SerializeStruct),
+                |field| field.ident().unwrap().to_token_stream(),
+                container,
+            );
+            quote! {
+                #ident::#variant_ident { #(ref #field_idents),* } => {
+                    let mut __serde_state = _serde::Serializer::serialize_struct(
                         __serializer,
-                        #type_name,
-                        #index,
                         #variant_name,
                         #len,
                     )?;
                     #(#serialize_fields)*
-                    _serde::ser::SerializeStructVariant::end(__serde_state)
+                    _serde::ser::SerializeStruct::end(__serde_state)
                 }
             }
         }
-    };
-
-    Ok(tokens)
+    }
 }
 
 struct FieldType<'a> {
     ty: &'a syn::Type,
     mode: ItemMode,
+    /// From `#[serde(bound)]`, replacing the bound inferred from `ty` and `mode`.
+    bound: Option<&'a [WherePredicate]>,
 }
 
 impl<'a> FieldType<'a> {
     fn new(ty: &'a syn::Type, mode: ItemMode) -> Self {
-        FieldType { ty, mode }
+        FieldType {
+            ty,
+            mode,
+            bound: None,
+        }
     }
 }
 
 fn collect_field_types_from_fields<'a>(fields: &'a FieldsDecl<'a>) -> Vec<FieldType<'a>> {
     let mut result = Vec::new();
     for field in &fields.fields {
-        if field.attrs.skip {
+        if let Some(bound) = &field.attrs.bound.serialize {
+            result.push(FieldType {
+                bound: Some(bound),
+                ..FieldType::new(field.ty(), field.mode())
+            });
+            continue;
+        }
+        if field.attrs.skip_serializing
+            || matches!(field.attrs.serialize_with, Some(WithFn::Stateless(_)))
+        {
             continue;
         }
         result.push(FieldType::new(field.ty(), field.mode()));
@@ -529,11 +1203,53 @@ fn collect_field_types_from_fields<'a>(fields: &'a FieldsDecl<'a>) -> Vec<FieldT
 fn collect_field_types_from_enum<'a>(data: &'a EnumDecl<'a>) -> Vec<FieldType<'a>> {
     let mut result = Vec::new();
     for variant in &data.variants {
+        if matches!(variant.serialize_with, Some(WithFn::Stateless(_))) {
+            continue;
+        }
         result.extend(collect_field_types_from_fields(&variant.fields));
     }
     result
 }
 
+/// Adds the container's `#[serde(bound)]` if it has one, and the inferred bounds otherwise: on the
+/// field types with perfect derive, or on the type parameters once the state is given.
+fn add_serialize_bounds(
+    where_clause: &mut Option<syn::WhereClause>,
+    decl: &TypeDecl,
+    field_types: &[FieldType<'_>],
+    state_ty: &TokenStream,
+) {
+    if let Some(bound) = &decl.attrs.bound.serialize {
+        add_where_predicates(where_clause, bound);
+    } else if decl.attrs.state.is_none() && decl.attrs.state_bound.is_none() {
+        add_serialize_bounds_from_types(where_clause, field_types, state_ty);
+    } else {
+        add_serialize_bounds_from_type_params(
+            where_clause,
+            decl.generics,
+            state_ty,
+            decl.attrs.mode,
+        );
+        for field in field_types {
+            add_where_predicates(where_clause, field.bound.unwrap_or_default());
+        }
+    }
+}
+
+fn add_where_predicates(
+    where_clause: &mut Option<syn::WhereClause>,
+    predicates: &[WherePredicate],
+) {
+    if predicates.is_empty() {
+        return;
+    }
+    let clause = where_clause.get_or_insert_with(|| syn::WhereClause {
+        where_token: Default::default(),
+        predicates: Default::default(),
+    });
+    clause.predicates.extend(predicates.iter().cloned());
+}
+
 fn add_serialize_bounds_from_types(
     where_clause: &mut Option<syn::WhereClause>,
     field_types: &[FieldType<'_>],
@@ -549,6 +1265,10 @@ fn add_serialize_bounds_from_types(
     });
 
     for field in field_types {
+        if let Some(bound) = field.bound {
+            clause.predicates.extend(bound.iter().cloned());
+            continue;
+        }
         let ty = field.ty;
         match field.mode {
             ItemMode::Stateful => clause
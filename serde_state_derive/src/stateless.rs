@@ -0,0 +1,60 @@
+use crate::dummy;
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{parse_quote, DeriveInput, GenericParam};
+
+pub fn expand_derive_stateless(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let ident = &input.ident;
+
+    let mut ser_generics = input.generics.clone();
+    ser_generics.params.push(parse_quote!(__State: ?Sized));
+    ser_generics
+        .make_where_clause()
+        .predicates
+        .push(parse_quote!(Self: _serde::Serialize));
+    let (ser_impl_generics, _, ser_where_clause) = ser_generics.split_for_impl();
+
+    let mut de_generics = input.generics.clone();
+    de_generics
+        .params
+        .insert(0, GenericParam::Lifetime(parse_quote!('de)));
+    de_generics.params.push(parse_quote!(__State: ?Sized));
+    de_generics
+        .make_where_clause()
+        .predicates
+        .push(parse_quote!(Self: _serde::Deserialize<'de>));
+    let (de_impl_generics, _, de_where_clause) = de_generics.split_for_impl();
+
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+
+    let impl_block = quote! {
+        #[automatically_derived]
+        impl #ser_impl_generics _serde_state::SerializeState<__State> for #ident #ty_generics #ser_where_clause {
+            fn serialize_state<__S>(
+                &self,
+                _state: &__State,
+                __serializer: __S,
+            ) -> ::core::result::Result<__S::Ok, __S::Error>
+            where
+                __S: _serde::Serializer,
+            {
+                _serde::Serialize::serialize(self, __serializer)
+            }
+        }
+
+        #[automatically_derived]
+        impl #de_impl_generics _serde_state::DeserializeState<'de, __State> for #ident #ty_generics #de_where_clause {
+            fn deserialize_state<__D>(
+                _state: &__State,
+                __deserializer: __D,
+            ) -> ::core::result::Result<Self, __D::Error>
+            where
+                __D: _serde::Deserializer<'de>,
+            {
+                _serde::Deserialize::deserialize(__deserializer)
+            }
+        }
+    };
+
+    Ok(dummy::wrap_in_const(None, impl_block))
+}
@@ -1,10 +1,20 @@
-use crate::attrs::{parse_field_attrs, parse_variant_attrs, FieldAttrs, ItemMode};
+use crate::attrs::{
+    parse_bound, parse_default, parse_field_attrs, parse_ser_de, parse_variant_attrs, DefaultValue,
+    FieldAttrs, ItemMode, SerDe, WithFn,
+};
+use crate::case::{RenameRule, RenameRules};
 use proc_macro2::Span;
+use std::collections::BTreeSet;
+use syn::ext::IdentExt;
+use syn::meta::ParseNestedMeta;
 use syn::spanned::Spanned;
-use syn::{Attribute, Data, DataEnum, DataStruct, DeriveInput, Fields, Type};
+use syn::{
+    Attribute, Data, DataEnum, DataStruct, DeriveInput, Fields, LitStr, Token, Type, WherePredicate,
+};
 
 pub struct TypeDecl<'a> {
     pub ident: &'a syn::Ident,
+    pub vis: &'a syn::Visibility,
     pub generics: &'a syn::Generics,
     pub attrs: ContainerAttributes,
     pub data: TypeData<'a>,
@@ -25,13 +35,22 @@ pub struct EnumDecl<'a> {
 
 pub struct VariantDecl<'a> {
     pub ident: &'a syn::Ident,
+    pub name: Name,
     pub fields: FieldsDecl<'a>,
+    pub untagged: bool,
+    /// `#[serde(serialize_with)]` and friends: the function receives all the variant's fields.
+    pub serialize_with: Option<WithFn>,
+    pub deserialize_with: Option<WithFn>,
 }
 
 pub struct FieldsDecl<'a> {
     pub style: FieldsStyle,
     pub fields: Vec<FieldDecl<'a>>,
     pub span: Span,
+    /// From the container's `#[serde(deny_unknown_fields)]`.
+    pub deny_unknown_fields: bool,
+    /// From the container's `#[serde_state(no_rollback)]`.
+    pub no_rollback: bool,
 }
 
 pub enum FieldsStyle {
@@ -42,19 +61,104 @@ pub enum FieldsStyle {
 
 pub struct FieldDecl<'a> {
     pub field: &'a syn::Field,
+    pub name: Name,
     pub attrs: FieldAttrs,
 }
 
+/// The names a field or variant is written and read under, after `rename` and `rename_all`.
+pub struct Name {
+    pub serialize: String,
+    pub deserialize: String,
+    /// Extra names from `#[serde(alias = "...")]`, accepted when deserializing.
+    pub aliases: Vec<String>,
+}
+
+impl Name {
+    fn new(
+        rename: &SerDe<String>,
+        aliases: &[String],
+        rules: RenameRules,
+        apply: fn(RenameRule, &str) -> String,
+        ident: &str,
+    ) -> Self {
+        Name {
+            serialize: rename
+                .serialize
+                .clone()
+                .unwrap_or_else(|| apply(rules.serialize, ident)),
+            deserialize: rename
+                .deserialize
+                .clone()
+                .unwrap_or_else(|| apply(rules.deserialize, ident)),
+            aliases: aliases.to_vec(),
+        }
+    }
+
+    /// Every name accepted when deserializing, the main one and the aliases, sorted like
+    /// serde_derive lists them in `FIELDS` and `VARIANTS`.
+    pub fn deserialize_names(&self) -> BTreeSet<&str> {
+        std::iter::once(self.deserialize.as_str())
+            .chain(self.aliases.iter().map(String::as_str))
+            .collect()
+    }
+}
+
+/// Rejects an alias that is also a deserialization name of another field or variant, since the
+/// generated visitor could only ever map it to one of them.
+fn check_aliases<'n>(kind: &str, names: impl Iterator<Item = (&'n Name, Span)>) -> syn::Result<()> {
+    let names: Vec<_> = names.collect();
+    for (index, (name, span)) in names.iter().enumerate() {
+        for alias in &name.aliases {
+            let conflict = names.iter().enumerate().any(|(other, (other_name, _))| {
+                other != index && other_name.deserialize_names().contains(alias.as_str())
+            });
+            if conflict {
+                return Err(syn::Error::new(
+                    *span,
+                    format!(
+                        "alias `{}` conflicts with the name of another {}",
+                        alias, kind
+                    ),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
 impl<'a> TypeDecl<'a> {
     pub fn from_derive_input(input: &'a DeriveInput) -> syn::Result<Self> {
         let attrs = ContainerAttributes::from_attrs(&input.attrs)?;
         let data = match &input.data {
-            Data::Struct(data) => TypeData::Struct(StructDecl::from_data(data, attrs.mode)?),
-            Data::Enum(data) => TypeData::Enum(EnumDecl::from_data(data, attrs.mode)?),
+            Data::Struct(data) => {
+                if attrs.rename_all_fields.is_some() {
+                    return Err(syn::Error::new(
+                        input.ident.span(),
+                        "#[serde(rename_all_fields)] can only be used on enums",
+                    ));
+                }
+                TypeData::Struct(StructDecl::from_data(data, &attrs)?)
+            }
+            Data::Enum(data) => TypeData::Enum(EnumDecl::from_data(data, &attrs)?),
             Data::Union(_) => unreachable!("unions are handled before TypeDecl construction"),
         };
+        check_tag(&input.ident, &attrs.tag, &data)?;
+        if attrs.default.is_some() {
+            match &data {
+                TypeData::Struct(data) if matches!(data.fields.style, FieldsStyle::Named) => {}
+                _ => {
+                    return Err(syn::Error::new(
+                        input.ident.span(),
+                        "#[serde(default)] can only be used on structs with named fields",
+                    ));
+                }
+            }
+        }
+        check_getters(&attrs, &data)?;
+        check_state_proxies(&attrs)?;
         Ok(TypeDecl {
             ident: &input.ident,
+            vis: &input.vis,
             generics: &input.generics,
             attrs,
             data,
@@ -63,66 +167,191 @@ impl<'a> TypeDecl<'a> {
 }
 
 impl<'a> StructDecl<'a> {
-    fn from_data(data: &'a DataStruct, mode: ItemMode) -> syn::Result<Self> {
+    fn from_data(data: &'a DataStruct, attrs: &ContainerAttributes) -> syn::Result<Self> {
         Ok(StructDecl {
-            fields: FieldsDecl::from_fields(&data.fields, mode)?,
+            fields: FieldsDecl::from_fields(
+                &data.fields,
+                attrs.mode,
+                attrs.rename_all,
+                attrs.deny_unknown_fields,
+                attrs.no_rollback,
+            )?,
         })
     }
 }
 
 impl<'a> EnumDecl<'a> {
-    fn from_data(data: &'a DataEnum, mode: ItemMode) -> syn::Result<Self> {
+    fn from_data(data: &'a DataEnum, container: &ContainerAttributes) -> syn::Result<Self> {
         let mut variants = Vec::new();
         for variant in &data.variants {
-            let variant_mode = parse_variant_attrs(&variant.attrs, mode)?.mode();
+            let attrs = parse_variant_attrs(&variant.attrs, container.mode)?;
+            if !attrs.untagged && variants.iter().any(|v: &VariantDecl| v.untagged) {
+                return Err(syn::Error::new(
+                    variant.ident.span(),
+                    "all variants with the #[serde(untagged)] attribute must be placed at the end of the enum",
+                ));
+            }
             variants.push(VariantDecl {
                 ident: &variant.ident,
-                fields: FieldsDecl::from_fields(&variant.fields, variant_mode)?,
+                name: Name::new(
+                    &attrs.rename,
+                    &attrs.aliases,
+                    container.rename_all,
+                    RenameRule::apply_to_variant,
+                    &variant.ident.unraw().to_string(),
+                ),
+                fields: FieldsDecl::from_fields(
+                    &variant.fields,
+                    attrs.mode(),
+                    container.rename_all_fields.unwrap_or_default(),
+                    container.deny_unknown_fields,
+                    container.no_rollback,
+                )?,
+                untagged: attrs.untagged,
+                serialize_with: attrs.serialize_with,
+                deserialize_with: attrs.deserialize_with,
             });
         }
+        check_aliases(
+            "variant",
+            variants
+                .iter()
+                .map(|variant| (&variant.name, variant.ident.span())),
+        )?;
         Ok(EnumDecl { variants })
     }
 }
 
+impl<'a> EnumDecl<'a> {
+    /// Splits the variants into the tagged ones and the trailing `#[serde(untagged)]` ones.
+    pub fn split_untagged(&self) -> (&[VariantDecl<'a>], &[VariantDecl<'a>]) {
+        let tagged = self
+            .variants
+            .iter()
+            .take_while(|variant| !variant.untagged)
+            .count();
+        self.variants.split_at(tagged)
+    }
+}
+
 impl<'a> FieldsDecl<'a> {
-    fn from_fields(fields: &'a Fields, mode: ItemMode) -> syn::Result<Self> {
+    fn from_fields(
+        fields: &'a Fields,
+        mode: ItemMode,
+        rules: RenameRules,
+        deny_unknown_fields: bool,
+        no_rollback: bool,
+    ) -> syn::Result<Self> {
         let span = fields.span();
         match fields {
             Fields::Named(named) => {
                 let mut result = Vec::with_capacity(named.named.len());
                 for field in &named.named {
-                    result.push(FieldDecl::new(field, mode)?);
+                    result.push(FieldDecl::new(field, mode, rules)?);
                 }
+                check_aliases(
+                    "field",
+                    result
+                        .iter()
+                        .filter(|field| !field.attrs.skip_deserializing && !field.attrs.flatten)
+                        .map(|field| (&field.name, field.field.span())),
+                )?;
                 Ok(FieldsDecl {
                     style: FieldsStyle::Named,
                     fields: result,
                     span,
+                    deny_unknown_fields,
+                    no_rollback,
                 })
             }
             Fields::Unnamed(unnamed) => {
                 let mut result = Vec::with_capacity(unnamed.unnamed.len());
                 for field in &unnamed.unnamed {
-                    result.push(FieldDecl::new(field, mode)?);
+                    let field = FieldDecl::new(field, mode, rules)?;
+                    if field.attrs.flatten {
+                        return Err(syn::Error::new(
+                            field.field.span(),
+                            "#[serde(flatten)] can only be used on named fields",
+                        ));
+                    }
+                    // Skipped tuple fields are filled in instead of read, so that is the only
+                    // place a default applies.
+                    if field.attrs.default.is_some() && !field.attrs.skip_deserializing {
+                        return Err(syn::Error::new(
+                            field.field.span(),
+                            "#[serde(default)] on a tuple field requires #[serde(skip_deserializing)]",
+                        ));
+                    }
+                    if field.attrs.skip_serializing_if.is_some() {
+                        return Err(syn::Error::new(
+                            field.field.span(),
+                            "#[serde(skip_serializing_if)] can only be used on named fields",
+                        ));
+                    }
+                    result.push(field);
+                }
+                if let [field] = &result[..] {
+                    if let Some(skip) = field.attrs.skip_name() {
+                        return Err(syn::Error::new(
+                            field.field.span(),
+                            format!(
+                                "#[serde({})] cannot be used on the only field of a newtype",
+                                skip
+                            ),
+                        ));
+                    }
                 }
                 Ok(FieldsDecl {
                     style: FieldsStyle::Unnamed,
                     fields: result,
                     span,
+                    deny_unknown_fields,
+                    no_rollback,
                 })
             }
             Fields::Unit => Ok(FieldsDecl {
                 style: FieldsStyle::Unit,
                 fields: Vec::new(),
                 span,
+                deny_unknown_fields,
+                no_rollback,
             }),
         }
     }
+
+    /// Whether any field is `#[serde(flatten)]`, which switches the struct to a map layout.
+    pub fn has_flatten(&self) -> bool {
+        self.fields.iter().any(|field| field.attrs.flatten)
+    }
 }
 
 impl<'a> FieldDecl<'a> {
-    fn new(field: &'a syn::Field, default_mode: ItemMode) -> syn::Result<Self> {
+    fn new(field: &'a syn::Field, default_mode: ItemMode, rules: RenameRules) -> syn::Result<Self> {
         let attrs = parse_field_attrs(&field.attrs, default_mode)?;
-        Ok(FieldDecl { field, attrs })
+        if attrs.flatten {
+            if let Some(skip) = attrs.skip_name() {
+                return Err(syn::Error::new(
+                    field.span(),
+                    format!(
+                        "#[serde(flatten)] cannot be combined with #[serde({})]",
+                        skip
+                    ),
+                ));
+            }
+        }
+        // Tuple fields are never looked up by name.
+        let ident = match &field.ident {
+            Some(ident) => ident.unraw().to_string(),
+            None => String::new(),
+        };
+        let name = Name::new(
+            &attrs.rename,
+            &attrs.aliases,
+            rules,
+            RenameRule::apply_to_field,
+            &ident,
+        );
+        Ok(FieldDecl { field, name, attrs })
     }
 
     pub fn ty(&self) -> &'a Type {
@@ -138,6 +367,182 @@ impl<'a> FieldDecl<'a> {
     }
 }
 
+/// How an enum encodes which variant it holds.
+pub enum TagType {
+    /// `{"Variant": content}`, serde's default.
+    External,
+    /// `{"tag": "Variant", ...fields}`, from `#[serde(tag = "...")]`.
+    Internal { tag: String },
+    /// `{"tag": "Variant", "content": content}`, from `#[serde(tag = "...", content = "...")]`.
+    Adjacent { tag: String, content: String },
+    /// Just the content, from `#[serde(untagged)]`.
+    Untagged,
+}
+
+/// A proxy type the value is deserialized as and then converted from.
+pub enum FromProxy {
+    /// `#[serde(from = "Proxy")]`, through `From<Proxy>`.
+    From(Type),
+    /// `#[serde(try_from = "Proxy")]`, through `TryFrom<Proxy>`.
+    TryFrom(Type),
+    /// `#[serde_state(from_state = "path")]`: `path(proxy, state)`. The proxy type comes from
+    /// `from_state(type = "Proxy", fn = "path")`, or else is inferred from `path`.
+    State(syn::Path, Option<Type>),
+    /// `#[serde_state(try_from_state = "path")]`: like `State`, but `path` returns a `Result`
+    /// whose error is reported through `de::Error::custom`.
+    TryState(syn::Path, Option<Type>),
+}
+
+/// A proxy type the value is converted to and then serialized as.
+pub enum IntoProxy {
+    /// `#[serde(into = "Proxy")]`, through `Into<Proxy>` on a clone of the value.
+    Into(Type),
+    /// `#[serde_state(into_state = "path")]`: `path(&value, state)`, with the proxy type given
+    /// or inferred as for `from_state`.
+    State(syn::Path, Option<Type>),
+}
+
+/// `from_state = "path"`, `try_from_state = "path"` or `into_state = "path"`, or the
+/// `from_state(type = "Proxy", fn = "path")` form that also names the proxy type.
+fn parse_state_proxy(meta: &ParseNestedMeta) -> syn::Result<(syn::Path, Option<Type>)> {
+    if meta.input.peek(Token![=]) {
+        let value: LitStr = meta.value()?.parse()?;
+        return Ok((value.parse()?, None));
+    }
+    let mut path = None;
+    let mut ty = None;
+    meta.parse_nested_meta(|inner| {
+        if inner.path.is_ident("type") {
+            let value: LitStr = inner.value()?.parse()?;
+            ty = Some(value.parse()?);
+            return Ok(());
+        }
+        if inner.path.is_ident("fn") {
+            let value: LitStr = inner.value()?.parse()?;
+            path = Some(value.parse()?);
+            return Ok(());
+        }
+        Err(inner.error("expected `type` or `fn`"))
+    })?;
+    match (path, ty) {
+        (Some(path), Some(ty)) => Ok((path, Some(ty))),
+        _ => Err(meta.error("expected both `type = \"...\"` and `fn = \"...\"`")),
+    }
+}
+
+/// Without a named proxy type, the bound on the proxy can't be written, so it only holds when
+/// the state is a concrete type.
+fn check_state_proxies(attrs: &ContainerAttributes) -> syn::Result<()> {
+    if attrs.state.is_some() {
+        return Ok(());
+    }
+    let unnamed = [
+        match &attrs.from {
+            Some(FromProxy::State(path, None)) => Some(("from_state", path)),
+            Some(FromProxy::TryState(path, None)) => Some(("try_from_state", path)),
+            _ => None,
+        },
+        match &attrs.into {
+            Some(IntoProxy::State(path, None)) => Some(("into_state", path)),
+            _ => None,
+        },
+    ];
+    match unnamed.into_iter().flatten().next() {
+        Some((name, path)) => Err(syn::Error::new(
+            path.span(),
+            format!(
+                "`{name} = \"...\"` requires `serde_state(state = ..)`; with a generic state, name the proxy type with `{name}(type = \"...\", fn = \"...\")`"
+            ),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// `getter` reads a private field of a remote struct, so it needs both.
+fn check_getters(attrs: &ContainerAttributes, data: &TypeData<'_>) -> syn::Result<()> {
+    let (fields, is_enum) = match data {
+        TypeData::Struct(data) => (vec![&data.fields], false),
+        TypeData::Enum(data) => (data.variants.iter().map(|v| &v.fields).collect(), true),
+    };
+    for field in fields.into_iter().flat_map(|fields| &fields.fields) {
+        if field.attrs.getter.is_none() {
+            continue;
+        }
+        let message = if is_enum {
+            "#[serde(getter = \"...\")] is not allowed in an enum"
+        } else if attrs.remote.is_none() {
+            "#[serde(getter = \"...\")] can only be used in structs that have #[serde(remote = \"...\")]"
+        } else {
+            continue;
+        };
+        return Err(syn::Error::new(field.field.span(), message));
+    }
+    Ok(())
+}
+
+fn check_tag(ident: &syn::Ident, tag: &TagType, data: &TypeData<'_>) -> syn::Result<()> {
+    let (tag, content) = match tag {
+        TagType::External => return Ok(()),
+        TagType::Untagged => {
+            if let TypeData::Struct(_) = data {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    "#[serde(untagged)] can only be used on enums",
+                ));
+            }
+            return Ok(());
+        }
+        TagType::Internal { tag } => (tag, None),
+        TagType::Adjacent { tag, content } => (tag, Some(content)),
+    };
+    let data = match data {
+        TypeData::Struct(_) => {
+            return Err(syn::Error::new(
+                ident.span(),
+                "#[serde(tag = \"...\")] can only be used on enums",
+            ));
+        }
+        TypeData::Enum(data) => data,
+    };
+    if let Some(content) = content {
+        if tag == content {
+            return Err(syn::Error::new(
+                ident.span(),
+                format!(
+                    "enum tags `{}` for type and content conflict with each other",
+                    tag
+                ),
+            ));
+        }
+        return Ok(());
+    }
+    for variant in &data.variants {
+        match variant.fields.style {
+            FieldsStyle::Unnamed if variant.fields.fields.len() != 1 => {
+                return Err(syn::Error::new(
+                    variant.ident.span(),
+                    "#[serde(tag = \"...\")] cannot be used with tuple variants",
+                ));
+            }
+            FieldsStyle::Named => {
+                for field in &variant.fields.fields {
+                    let serialized = !field.attrs.skip_serializing && field.name.serialize == *tag;
+                    let deserialized =
+                        !field.attrs.skip_deserializing && field.name.deserialize == *tag;
+                    if !field.attrs.flatten && (serialized || deserialized) {
+                        return Err(syn::Error::new(
+                            field.field.span(),
+                            "variant field name conflicts with internal tag",
+                        ));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
 pub struct ContainerAttributes {
     pub transparent: bool,
     pub serde_path: Option<syn::Path>,
@@ -145,6 +550,20 @@ pub struct ContainerAttributes {
     pub state_bound: Option<Type>,
     pub default_state: Option<Type>,
     pub mode: ItemMode,
+    pub tag: TagType,
+    pub default: Option<DefaultValue>,
+    pub rename_all: RenameRules,
+    pub rename_all_fields: Option<RenameRules>,
+    pub deny_unknown_fields: bool,
+    /// `#[serde(bound = "..")]`: used instead of all the inferred bounds.
+    pub bound: SerDe<Vec<WherePredicate>>,
+    pub from: Option<FromProxy>,
+    pub into: Option<IntoProxy>,
+    /// `#[serde(remote = "Path")]`: the foreign type this definition mirrors.
+    pub remote: Option<syn::Path>,
+    /// `#[serde_state(no_rollback)]`: untagged variants and flattened `Option` fields are tried
+    /// without snapshotting and restoring the state.
+    pub no_rollback: bool,
 }
 
 impl ContainerAttributes {
@@ -156,7 +575,20 @@ impl ContainerAttributes {
             state_bound: None,
             default_state: None,
             mode: ItemMode::Stateful,
+            tag: TagType::External,
+            default: None,
+            rename_all: RenameRules::default(),
+            rename_all_fields: None,
+            deny_unknown_fields: false,
+            bound: SerDe::default(),
+            from: None,
+            into: None,
+            remote: None,
+            no_rollback: false,
         };
+        let mut tag = None;
+        let mut content = None;
+        let mut untagged = None;
 
         for attr in attrs {
             let is_serde = attr.path().is_ident("serde");
@@ -174,6 +606,167 @@ impl ContainerAttributes {
                     result.serde_path = Some(path);
                     return Ok(());
                 }
+                if meta.path.is_ident("tag") {
+                    if !is_serde {
+                        return Err(meta.error("`tag` must be specified with `serde(tag = ..)`"));
+                    }
+                    if tag.is_some() {
+                        return Err(meta.error("duplicate `tag` attribute"));
+                    }
+                    let value: LitStr = meta.value()?.parse()?;
+                    tag = Some(value.value());
+                    return Ok(());
+                }
+                if meta.path.is_ident("default") {
+                    if !is_serde {
+                        return Err(meta.error("`default` must be specified with `serde(default)`"));
+                    }
+                    if result.default.is_some() {
+                        return Err(meta.error("duplicate `default` attribute"));
+                    }
+                    result.default = Some(parse_default(&meta)?);
+                    return Ok(());
+                }
+                if meta.path.is_ident("rename_all") {
+                    if !is_serde {
+                        return Err(
+                            meta.error("`rename_all` must be specified with `serde(rename_all = ..)`")
+                        );
+                    }
+                    result.rename_all = parse_rename_rules(&meta)?;
+                    return Ok(());
+                }
+                if meta.path.is_ident("rename_all_fields") {
+                    if !is_serde {
+                        return Err(meta.error(
+                            "`rename_all_fields` must be specified with `serde(rename_all_fields = ..)`",
+                        ));
+                    }
+                    result.rename_all_fields = Some(parse_rename_rules(&meta)?);
+                    return Ok(());
+                }
+                if meta.path.is_ident("deny_unknown_fields") {
+                    if !is_serde {
+                        return Err(meta.error(
+                            "`deny_unknown_fields` must be specified with `serde(deny_unknown_fields)`",
+                        ));
+                    }
+                    result.deny_unknown_fields = true;
+                    return Ok(());
+                }
+                if meta.path.is_ident("bound") {
+                    if !is_serde {
+                        return Err(meta.error("`bound` must be specified with `serde(bound = ..)`"));
+                    }
+                    result.bound = parse_bound(&meta)?;
+                    return Ok(());
+                }
+                if meta.path.is_ident("from") {
+                    if !is_serde {
+                        return Err(meta.error(
+                            "`from` must be specified with `serde(from = ..)`",
+                        ));
+                    }
+                    if result.from.is_some() {
+                        return Err(meta.error("only one of `from`, `try_from`, `from_state` and `try_from_state` can be used"));
+                    }
+                    let value: LitStr = meta.value()?.parse()?;
+                    result.from = Some(FromProxy::From(value.parse()?));
+                    return Ok(());
+                }
+                if meta.path.is_ident("try_from") {
+                    if !is_serde {
+                        return Err(meta.error(
+                            "`try_from` must be specified with `serde(try_from = ..)`",
+                        ));
+                    }
+                    if result.from.is_some() {
+                        return Err(meta.error("only one of `from`, `try_from`, `from_state` and `try_from_state` can be used"));
+                    }
+                    let value: LitStr = meta.value()?.parse()?;
+                    result.from = Some(FromProxy::TryFrom(value.parse()?));
+                    return Ok(());
+                }
+                if meta.path.is_ident("from_state") {
+                    if !is_serde_state {
+                        return Err(meta.error(
+                            "`from_state` must be specified with `serde_state(from_state = ..)`",
+                        ));
+                    }
+                    if result.from.is_some() {
+                        return Err(meta.error("only one of `from`, `try_from`, `from_state` and `try_from_state` can be used"));
+                    }
+                    let (path, ty) = parse_state_proxy(&meta)?;
+                    result.from = Some(FromProxy::State(path, ty));
+                    return Ok(());
+                }
+                if meta.path.is_ident("try_from_state") {
+                    if !is_serde_state {
+                        return Err(meta.error(
+                            "`try_from_state` must be specified with `serde_state(try_from_state = ..)`",
+                        ));
+                    }
+                    if result.from.is_some() {
+                        return Err(meta.error("only one of `from`, `try_from`, `from_state` and `try_from_state` can be used"));
+                    }
+                    let (path, ty) = parse_state_proxy(&meta)?;
+                    result.from = Some(FromProxy::TryState(path, ty));
+                    return Ok(());
+                }
+                if meta.path.is_ident("into") {
+                    if !is_serde {
+                        return Err(meta.error(
+                            "`into` must be specified with `serde(into = ..)`",
+                        ));
+                    }
+                    if result.into.is_some() {
+                        return Err(meta.error("only one of `into` and `into_state` can be used"));
+                    }
+                    let value: LitStr = meta.value()?.parse()?;
+                    result.into = Some(IntoProxy::Into(value.parse()?));
+                    return Ok(());
+                }
+                if meta.path.is_ident("into_state") {
+                    if !is_serde_state {
+                        return Err(meta.error(
+                            "`into_state` must be specified with `serde_state(into_state = ..)`",
+                        ));
+                    }
+                    if result.into.is_some() {
+                        return Err(meta.error("only one of `into` and `into_state` can be used"));
+                    }
+                    let (path, ty) = parse_state_proxy(&meta)?;
+                    result.into = Some(IntoProxy::State(path, ty));
+                    return Ok(());
+                }
+                if meta.path.is_ident("remote") {
+                    if !is_serde {
+                        return Err(meta.error("`remote` must be specified with `serde(remote = ..)`"));
+                    }
+                    let value: LitStr = meta.value()?.parse()?;
+                    result.remote = Some(value.parse()?);
+                    return Ok(());
+                }
+                if meta.path.is_ident("untagged") {
+                    if !is_serde {
+                        return Err(meta.error("`untagged` must be specified with `serde(untagged)`"));
+                    }
+                    untagged = Some(meta.path.span());
+                    return Ok(());
+                }
+                if meta.path.is_ident("content") {
+                    if !is_serde {
+                        return Err(
+                            meta.error("`content` must be specified with `serde(content = ..)`")
+                        );
+                    }
+                    if content.is_some() {
+                        return Err(meta.error("duplicate `content` attribute"));
+                    }
+                    let value: LitStr = meta.value()?.parse()?;
+                    content = Some((value.value(), value.span()));
+                    return Ok(());
+                }
                 if meta.path.is_ident("state") {
                     if !is_serde_state {
                         return Err(
@@ -223,6 +816,13 @@ impl ContainerAttributes {
                     result.default_state = Some(ty);
                     return Ok(());
                 }
+                if meta.path.is_ident("no_rollback") {
+                    if !is_serde_state {
+                        return Err(meta.error("`no_rollback` must be specified with `serde_state`"));
+                    }
+                    result.no_rollback = true;
+                    return Ok(());
+                }
                 if meta.path.is_ident("stateless") {
                     if !is_serde_state {
                         return Err(meta.error("`stateless` must be specified with `serde_state`"));
@@ -245,6 +845,40 @@ impl ContainerAttributes {
             })?;
         }
 
+        result.tag = match (tag, content, untagged) {
+            (None, None, None) => TagType::External,
+            (None, None, Some(_)) => TagType::Untagged,
+            (Some(tag), None, None) => TagType::Internal { tag },
+            (Some(tag), Some((content, _)), None) => TagType::Adjacent { tag, content },
+            (Some(_), _, Some(span)) => {
+                return Err(syn::Error::new(
+                    span,
+                    "enum cannot be both untagged and tagged",
+                ));
+            }
+            (None, Some((_, span)), _) => {
+                return Err(syn::Error::new(
+                    span,
+                    "#[serde(content = \"...\")] requires #[serde(tag = \"...\")]",
+                ));
+            }
+        };
+
         Ok(result)
     }
 }
+
+/// Parses `rename_all = ".."` or `rename_all(serialize = "..", deserialize = "..")`.
+fn parse_rename_rules(meta: &ParseNestedMeta) -> syn::Result<RenameRules> {
+    let rules = parse_ser_de(meta)?;
+    let parse = |rule: Option<LitStr>| match rule {
+        Some(rule) => {
+            RenameRule::from_str(&rule.value()).map_err(|msg| syn::Error::new(rule.span(), msg))
+        }
+        None => Ok(RenameRule::None),
+    };
+    Ok(RenameRules {
+        serialize: parse(rules.serialize)?,
+        deserialize: parse(rules.deserialize)?,
+    })
+}